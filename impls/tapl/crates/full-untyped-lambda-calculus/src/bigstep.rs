@@ -0,0 +1,424 @@
+use crate::context::*;
+use crate::evaluate::*;
+use crate::syntax::*;
+use std::fmt;
+
+/// The stack to run `derive` on. Derivations nest as deep as the recursion
+/// of the program they evaluate.
+pub const DERIVE_STACK: usize = 1 << 28;
+
+/// A big-step derivation of `term ⇓ value`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Derivation {
+    pub rule: &'static str,
+    pub premises: Vec<Derivation>,
+    pub term: Term,
+    pub value: Term,
+}
+
+impl Derivation {
+    fn new(rule: &'static str, premises: Vec<Derivation>, term: &Term, value: Term) -> Self {
+        Derivation {
+            rule,
+            premises,
+            term: term.clone(),
+            value,
+        }
+    }
+
+    /// Number of rule applications in the derivation.
+    pub fn size(&self) -> usize {
+        1 + self.premises.iter().map(Derivation::size).sum::<usize>()
+    }
+}
+
+impl fmt::Display for Derivation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn render(d: &Derivation, depth: usize, f: &mut fmt::Formatter) -> fmt::Result {
+            writeln!(f, "{}{}: {} ⇓ {}", "  ".repeat(depth), d.rule, d.term, d.value)?;
+            for premise in &d.premises {
+                render(premise, depth + 1, f)?;
+            }
+            Ok(())
+        }
+
+        render(self, 0, f)
+    }
+}
+
+impl<'a> Evaluator<'a> {
+    /// Evaluates `term` with the big-step rules of TAPL exercise 5.3.8,
+    /// extended to the rest of the language.
+    ///
+    /// Where no rule applies, a `Stuck` node records the partially evaluated
+    /// term, which is the same normal form `evaluate_top` gets stuck on.
+    pub fn derive(&mut self, context: &Context, term: &Term) -> Result<Derivation, EvalError> {
+        let fi = FileInfo::default();
+
+        Ok(match term {
+            t if is_value(t) => Derivation::new("B-Value", vec![], t, t.clone()),
+
+            Term::Var(_, Var { index, .. }) => match context.get_binding(*index as usize) {
+                Some(Binding::TermBind(box bound)) => {
+                    let d1 = self.derive(context, &bound)?;
+                    let value = d1.value.clone();
+                    Derivation::new("B-Var", vec![d1], term, value)
+                }
+                _ => Derivation::new("Stuck", vec![], term, term.clone()),
+            },
+
+            Term::If(_, box t1, box t2, box t3) => {
+                let d1 = self.derive(context, t1)?;
+                let (rule, branch) = match &d1.value {
+                    Term::True(_) => ("B-IfTrue", t2),
+                    Term::False(_) => ("B-IfFalse", t3),
+                    v1 => {
+                        let value = Term::If(fi, box v1.clone(), box t2.clone(), box t3.clone());
+                        return Ok(Derivation::new("Stuck", vec![d1], term, value));
+                    }
+                };
+                let d2 = self.derive(context, branch)?;
+                let value = d2.value.clone();
+
+                Derivation::new(rule, vec![d1, d2], term, value)
+            }
+
+            Term::Let(_, name, box t1, box t2) => {
+                let d1 = self.derive(context, t1)?;
+                if !is_value(&d1.value) {
+                    let value = Term::Let(fi, name.clone(), box d1.value.clone(), box t2.clone());
+                    return Ok(Derivation::new("Stuck", vec![d1], term, value));
+                }
+                let d2 = self.derive(context, &t2.substitute_top(&d1.value))?;
+                let value = d2.value.clone();
+
+                Derivation::new("B-Let", vec![d1, d2], term, value)
+            }
+
+            Term::LetPattern(..)
+            | Term::Record(..)
+            | Term::Projection(..)
+            | Term::TimesFloat(..) => self.derive_data(context, term)?,
+
+            Term::Application(app_info, box t1, box t2) => {
+                let d1 = self.derive(context, t1)?;
+                if !is_value(&d1.value) {
+                    let value = Term::Application(fi, box d1.value.clone(), box t2.clone());
+                    return Ok(Derivation::new("Stuck", vec![d1], term, value));
+                }
+                let d2 = self.derive(context, t2)?;
+                if !is_value(&d2.value) {
+                    let value = Term::Application(fi, box d1.value.clone(), box d2.value.clone());
+                    return Ok(Derivation::new("Stuck", vec![d1, d2], term, value));
+                }
+
+                match (&d1.value, &d2.value) {
+                    (Term::Abstraction(_, _, box t12), v2) => {
+                        let d3 = self.derive(context, &t12.substitute_top(v2))?;
+                        let value = d3.value.clone();
+                        Derivation::new("B-App", vec![d1, d2, d3], term, value)
+                    }
+                    (Term::Host(host_info, host, args), v2) => {
+                        let args: Vec<Term> = args.iter().cloned().chain([v2.clone()]).collect();
+
+                        match args.len() == host.arity {
+                            true => {
+                                let result = host.call(self.io, &args).map_err(|e| {
+                                    EvalError::Runtime(app_info.clone(), format!("{}: {}", host.name, e))
+                                })?;
+                                let d3 = self.derive(context, &result)?;
+                                let value = d3.value.clone();
+                                Derivation::new("B-AppHost", vec![d1, d2, d3], term, value)
+                            }
+                            false => {
+                                let value = Term::Host(host_info.clone(), host.clone(), args);
+                                Derivation::new("B-AppHostPartial", vec![d1, d2], term, value)
+                            }
+                        }
+                    }
+                    (v1, v2) => {
+                        let value = Term::Application(fi, box v1.clone(), box v2.clone());
+                        Derivation::new("Stuck", vec![d1, d2], term, value)
+                    }
+                }
+            }
+
+            Term::Successor(_, box t1) => {
+                let d1 = self.derive(context, t1)?;
+                let value = Term::Successor(fi, box d1.value.clone());
+                let rule = match is_numeric(&value) {
+                    true => "B-Succ",
+                    false => "Stuck",
+                };
+
+                Derivation::new(rule, vec![d1], term, value)
+            }
+
+            Term::Predecessor(_, box t1) => {
+                let d1 = self.derive(context, t1)?;
+                let (rule, value) = match &d1.value {
+                    Term::Zero(_) => ("B-PredZero", Term::Zero(fi)),
+                    Term::Successor(_, box nv) if is_numeric(nv) => ("B-PredSucc", nv.clone()),
+                    v1 => ("Stuck", Term::Predecessor(fi, box v1.clone())),
+                };
+
+                Derivation::new(rule, vec![d1], term, value)
+            }
+
+            Term::IsZero(_, box t1) => {
+                let d1 = self.derive(context, t1)?;
+                let (rule, value) = match &d1.value {
+                    Term::Zero(_) => ("B-IsZeroZero", Term::True(fi)),
+                    Term::Successor(_, box nv) if is_numeric(nv) => {
+                        ("B-IsZeroSucc", Term::False(fi))
+                    }
+                    v1 => ("Stuck", Term::IsZero(fi, box v1.clone())),
+                };
+
+                Derivation::new(rule, vec![d1], term, value)
+            }
+
+            _ => Derivation::new("Stuck", vec![], term, term.clone()),
+        })
+    }
+
+    /// Rules for records, patterns and floats, kept apart from `derive` so
+    /// that its stack frame stays small: it recurses once per premise.
+    #[inline(never)]
+    fn derive_data(&mut self, context: &Context, term: &Term) -> Result<Derivation, EvalError> {
+        match term {
+            Term::LetPattern(let_info, pattern, box t1, box t2) => {
+                self.derive_let_pattern(context, term, let_info, pattern, t1, t2)
+            }
+            Term::Record(_, fields) => self.derive_record(context, term, fields),
+            Term::TimesFloat(_, box t1, box t2) => self.derive_times_float(context, term, t1, t2),
+            Term::Projection(proj_info, box t1, label) => {
+                self.derive_projection(context, term, proj_info, t1, label)
+            }
+            _ => Ok(Derivation::new("Stuck", vec![], term, term.clone())),
+        }
+    }
+
+    fn derive_let_pattern(
+        &mut self,
+        context: &Context,
+        term: &Term,
+        let_info: &FileInfo,
+        pattern: &Pattern,
+        t1: &Term,
+        t2: &Term,
+    ) -> Result<Derivation, EvalError> {
+        let fi = FileInfo::default();
+        let d1 = self.derive(context, t1)?;
+        if !is_value(&d1.value) {
+            let value = Term::LetPattern(fi, pattern.clone(), box d1.value.clone(), box t2.clone());
+            return Ok(Derivation::new("Stuck", vec![d1], term, value));
+        }
+        let values = pattern
+            .matches(&d1.value)
+            .map_err(|e| EvalError::Runtime(let_info.clone(), e))?;
+        let body = values
+            .iter()
+            .enumerate()
+            .rev()
+            .fold(t2.clone(), |t, (i, v)| t.substitute_top(&v.shift(i as i32)));
+        let d2 = self.derive(context, &body)?;
+        let value = d2.value.clone();
+
+        Ok(Derivation::new("B-LetPattern", vec![d1, d2], term, value))
+    }
+
+    fn derive_record(
+        &mut self,
+        context: &Context,
+        term: &Term,
+        fields: &[(String, Box<Term>)],
+    ) -> Result<Derivation, EvalError> {
+        let mut premises = vec![];
+        let mut values = vec![];
+        for (label, box field) in fields {
+            match values.iter().all(|(_, box v)| is_value(v)) {
+                true => {
+                    let d = self.derive(context, field)?;
+                    values.push((label.clone(), box d.value.clone()));
+                    premises.push(d);
+                }
+                false => values.push((label.clone(), box field.clone())),
+            }
+        }
+        let value = Term::Record(FileInfo::default(), values);
+        let rule = match is_value(&value) {
+            true => "B-Rcd",
+            false => "Stuck",
+        };
+
+        Ok(Derivation::new(rule, premises, term, value))
+    }
+
+    fn derive_projection(
+        &mut self,
+        context: &Context,
+        term: &Term,
+        proj_info: &FileInfo,
+        t1: &Term,
+        label: &str,
+    ) -> Result<Derivation, EvalError> {
+        let d1 = self.derive(context, t1)?;
+
+        Ok(match &d1.value {
+            record @ Term::Record(_, fields) if is_value(record) => {
+                match fields.iter().find(|(field, _)| field == label) {
+                    Some((_, box value)) => {
+                        let value = value.clone();
+                        Derivation::new("B-ProjRcd", vec![d1], term, value)
+                    }
+                    None => {
+                        return Err(EvalError::Runtime(
+                            proj_info.clone(),
+                            format!("no label `{}` in {}", label, record),
+                        ))
+                    }
+                }
+            }
+            v1 => {
+                let value = Term::Projection(FileInfo::default(), box v1.clone(), label.into());
+                Derivation::new("Stuck", vec![d1], term, value)
+            }
+        })
+    }
+
+    fn derive_times_float(
+        &mut self,
+        context: &Context,
+        term: &Term,
+        t1: &Term,
+        t2: &Term,
+    ) -> Result<Derivation, EvalError> {
+        let fi = FileInfo::default();
+        let d1 = self.derive(context, t1)?;
+        if !is_value(&d1.value) {
+            let value = Term::TimesFloat(fi, box d1.value.clone(), box t2.clone());
+            return Ok(Derivation::new("Stuck", vec![d1], term, value));
+        }
+        let d2 = self.derive(context, t2)?;
+        let (rule, value) = match (&d1.value, &d2.value) {
+            (Term::Float(_, f1), Term::Float(_, f2)) => ("B-TimesFloat", Term::Float(fi, f1 * f2)),
+            (v1, v2) => ("Stuck", Term::TimesFloat(fi, box v1.clone(), box v2.clone())),
+        };
+
+        Ok(Derivation::new(rule, vec![d1, d2], term, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bigstep::*;
+    use crate::io::*;
+    use crate::parser::*;
+
+    fn assert_agrees(input: &str) -> Vec<Derivation> {
+        let (parsed, context) = parse(input).expect("Parse error");
+        let mut io = MemoryIo::default();
+        let mut evaluator = Evaluator::new(&mut io);
+        let mut derivations = vec![];
+
+        for command in &parsed {
+            if let Command::Eval(_, term) = command {
+                let term = close_over_globals(&context, term);
+                let derivation = evaluator.derive(&context, &term).expect("Failed to derive");
+
+                assert_eq!(
+                    derivation.value.without_locations(),
+                    evaluator.evaluate_top(&context, &term).unwrap().without_locations()
+                );
+                derivations.push(derivation);
+            }
+        }
+
+        derivations
+    }
+
+    #[test]
+    fn test_derivation_rendering() {
+        let derivations = assert_agrees("(λx. iszero x) 0;");
+
+        assert_eq!(
+            format!("{}", derivations[0]),
+            "B-App: (λx. iszero x) 0 ⇓ true\n  \
+             B-Value: λx. iszero x ⇓ λx. iszero x\n  \
+             B-Value: 0 ⇓ 0\n  \
+             B-IsZeroZero: iszero 0 ⇓ true\n    \
+             B-Value: 0 ⇓ 0\n"
+        );
+        assert_eq!(derivations[0].size(), 5);
+    }
+
+    #[test]
+    fn test_stuck_terms() {
+        let derivations = assert_agrees("let x = 1 in +x x; if 0 then 1 else 2; - true;");
+
+        assert!(derivations.iter().all(|d| d.rule == "Stuck" || d.rule == "B-Let"));
+        assert_eq!(derivations[1].rule, "Stuck");
+    }
+
+    #[test]
+    fn test_bigstep_agrees_with_evaluate_top() {
+        for input in [
+            "let y = true in let x = 1 in y x;",
+            "let y = true; let x = 1 in y x;",
+            "(lambda x. x) 1;",
+            "if true then 1.0 else 9.0;",
+            "iszero (- (+ 0)); - 0; (λx. λy. x) 3;",
+            "let {a = x, b = {c = y}} = {a = 1, b = {c = true}} in { d = y, e = + x }.e;",
+            "{ a = 1, b = (λx. x) 2, c = iszero true, d = - 1 };",
+            "timesfloat 2.0 ((λx. x) (-1.5)); timesfloat (timesfloat 0.5 4.0) true;",
+            include_str!("lambda-files/test1.f"),
+            include_str!("lambda-files/test2.f"),
+            include_str!("lambda-files/test3.f"),
+            include_str!("lambda-files/test4.f"),
+            include_str!("lambda-files/test5.f"),
+            include_str!("lambda-files/test6.f"),
+        ] {
+            assert_agrees(input);
+        }
+    }
+
+    #[test]
+    fn test_bigstep_agrees_on_deep_programs() {
+        // Derivations of recursive programs nest as deep as their recursion.
+        // Fibonacci numbers past the fifth take tens of seconds to evaluate
+        // without optimizations, so test7.f stops there.
+        let test7: String = include_str!("lambda-files/test7.f")
+            .lines()
+            .take_while(|line| !line.contains("churchnat 6"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let deep = std::thread::Builder::new().stack_size(DERIVE_STACK).spawn(move || {
+            assert_agrees(&test7);
+        });
+
+        deep.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_bigstep_host_functions() {
+        let mut context = Context::default();
+        context.register_io();
+        let (parsed, context) =
+            parse_with_context("(λx. λy. y) (print 1) (print 2);", context).expect("Parse error");
+        let mut io = MemoryIo::default();
+
+        if let Command::Eval(_, term) = &parsed[0] {
+            let term = close_over_globals(&context, term);
+            let derivation = Evaluator::new(&mut io).derive(&context, &term).unwrap();
+
+            assert_eq!(
+                derivation.value.without_locations(),
+                Term::from_int(2, FileInfo::default())
+            );
+            assert_eq!(io.output(), ["1", "2"]);
+        } else {
+            panic!()
+        }
+    }
+}
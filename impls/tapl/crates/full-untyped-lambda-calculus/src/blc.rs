@@ -0,0 +1,293 @@
+use crate::parser::is_keyword;
+use crate::syntax::*;
+
+/// Tromp's Binary Lambda Calculus: `00 M` is an abstraction, `01 M N` an
+/// application and `1^(i+1) 0` the variable with de Bruijn index `i`.
+#[derive(Debug, PartialEq)]
+pub enum BlcError {
+    /// The term uses something other than variables, abstractions,
+    /// applications and lets.
+    NotPure(String),
+    UnexpectedEnd,
+    TrailingBits(usize),
+    InvalidChar(char),
+    /// A variable index with no binder above it.
+    FreeVariable(usize),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BlcFormat {
+    /// One `0` or `1` character per bit.
+    Text,
+    /// Bits packed most significant first, the last byte padded with zeros.
+    Bytes,
+}
+
+/// Encodes a closed, hydrated term. Lets are encoded as the application of
+/// an abstraction.
+pub fn encode(term: &Term) -> Result<Vec<bool>, BlcError> {
+    fn walk(term: &Term, depth: usize, bits: &mut Vec<bool>) -> Result<(), BlcError> {
+        match term {
+            Term::Var(_, var) if var.index as usize >= depth => {
+                return Err(BlcError::FreeVariable(var.index as usize))
+            }
+            Term::Var(_, var) => {
+                bits.extend((0..=var.index).map(|_| true));
+                bits.push(false);
+            }
+            Term::Abstraction(_, _, box t1) => {
+                bits.extend([false, false]);
+                walk(t1, depth + 1, bits)?;
+            }
+            Term::Application(_, box t1, box t2) => {
+                bits.extend([false, true]);
+                walk(t1, depth, bits)?;
+                walk(t2, depth, bits)?;
+            }
+            Term::Let(_, _, box t1, box t2) => {
+                bits.extend([false, true, false, false]);
+                walk(t2, depth + 1, bits)?;
+                walk(t1, depth, bits)?;
+            }
+            term => return Err(BlcError::NotPure(format!("{}", term))),
+        }
+
+        Ok(())
+    }
+
+    let mut bits = vec![];
+    walk(term, 0, &mut bits)?;
+
+    Ok(bits)
+}
+
+/// Names for decoded binders: `a` through `z`, then `aa`, `ab` and so on,
+/// primed where they would spell a keyword.
+fn binder_name(depth: usize) -> String {
+    fn letters(depth: usize) -> String {
+        let letter = (b'a' + (depth % 26) as u8) as char;
+
+        match depth / 26 {
+            0 => letter.to_string(),
+            n => format!("{}{}", letters(n - 1), letter),
+        }
+    }
+
+    match letters(depth) {
+        name if is_keyword(&name) => format!("{}'", name),
+        name => name,
+    }
+}
+
+/// A term `decode_prefix` has started but not finished.
+enum Partial {
+    /// The body is next.
+    Abstraction,
+    /// The function is next.
+    Function,
+    /// The argument to this function is next.
+    Argument(Term),
+}
+
+/// Decodes a single closed term, returning it along with the number of bits
+/// it used. Keeps the terms it is inside of on a stack of its own, as the
+/// input may nest as deeply as it is long.
+fn decode_prefix(bits: &[bool]) -> Result<(Term, usize), BlcError> {
+    let mut pos = 0;
+    let mut names = vec![];
+    let mut partials = vec![];
+
+    let mut next = || {
+        let bit = bits.get(pos).copied().ok_or(BlcError::UnexpectedEnd);
+        pos += 1;
+        bit
+    };
+
+    loop {
+        let mut term = match (next()?, next()?) {
+            (false, false) => {
+                names.push(binder_name(names.len()));
+                partials.push(Partial::Abstraction);
+                continue;
+            }
+            (false, true) => {
+                partials.push(Partial::Function);
+                continue;
+            }
+            (true, second) => {
+                let mut index = 0;
+                let mut bit = second;
+                while bit {
+                    index += 1;
+                    bit = next()?;
+                }
+
+                match names.len().checked_sub(index + 1) {
+                    Some(binder) => Term::Var(
+                        FileInfo::default(),
+                        Var::new(&names[binder], index as i32, names.len() as i32),
+                    ),
+                    None => return Err(BlcError::FreeVariable(index)),
+                }
+            }
+        };
+
+        loop {
+            match partials.pop() {
+                Some(Partial::Abstraction) => {
+                    let name = names.pop().unwrap();
+                    term = Term::Abstraction(FileInfo::default(), name, box term);
+                }
+                Some(Partial::Function) => {
+                    partials.push(Partial::Argument(term));
+                    break;
+                }
+                Some(Partial::Argument(function)) => {
+                    term = Term::Application(FileInfo::default(), box function, box term);
+                }
+                None => return Ok((term, pos)),
+            }
+        }
+    }
+}
+
+pub fn decode(bits: &[bool]) -> Result<Term, BlcError> {
+    match decode_prefix(bits)? {
+        (term, used) if used == bits.len() => Ok(term),
+        (_, used) => Err(BlcError::TrailingBits(bits.len() - used)),
+    }
+}
+
+pub fn to_text(bits: &[bool]) -> String {
+    bits.iter().map(|bit| if *bit { '1' } else { '0' }).collect()
+}
+
+/// Reads `0`/`1` text, ignoring whitespace.
+pub fn from_text(text: &str) -> Result<Vec<bool>, BlcError> {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '0' => Ok(false),
+            '1' => Ok(true),
+            c => Err(BlcError::InvalidChar(c)),
+        })
+        .collect()
+}
+
+pub fn pack(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (i, bit)| byte | ((*bit as u8) << (7 - i)))
+        })
+        .collect()
+}
+
+pub fn unpack(bytes: &[u8]) -> Vec<bool> {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| byte & (1 << (7 - i)) != 0))
+        .collect()
+}
+
+pub fn encode_as(term: &Term, format: BlcFormat) -> Result<Vec<u8>, BlcError> {
+    let bits = encode(term)?;
+
+    Ok(match format {
+        BlcFormat::Text => to_text(&bits).into_bytes(),
+        BlcFormat::Bytes => pack(&bits),
+    })
+}
+
+/// Decodes a term, allowing up to seven bits of zero padding after a packed
+/// term.
+pub fn decode_as(input: &[u8], format: BlcFormat) -> Result<Term, BlcError> {
+    match format {
+        BlcFormat::Text => decode(&from_text(&String::from_utf8_lossy(input))?),
+        BlcFormat::Bytes => {
+            let bits = unpack(input);
+            let (term, used) = decode_prefix(&bits)?;
+
+            match bits.len() - used {
+                padding if padding < 8 && bits[used..].iter().all(|bit| !bit) => Ok(term),
+                trailing => Err(BlcError::TrailingBits(trailing)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blc::*;
+    use crate::test_util::{closed, hydrated};
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(to_text(&encode(&closed("λx. x;")).unwrap()), "0010");
+        assert_eq!(to_text(&encode(&closed("λx. λy. x;")).unwrap()), "0000110");
+        assert_eq!(
+            to_text(&encode(&closed("λx. x x;")).unwrap()),
+            "00011010"
+        );
+        assert!(matches!(
+            encode(&closed("λx. iszero x;")),
+            Err(BlcError::NotPure(_))
+        ));
+
+        assert_eq!(encode(&hydrated("let g = λx. x; λy. g y;")), Err(BlcError::FreeVariable(1)));
+    }
+
+    #[test]
+    fn test_decode() {
+        let term = decode(&from_text("0000 110").unwrap()).unwrap();
+
+        assert_eq!(format!("{}", term), "λa b. a");
+        assert_eq!(decode(&from_text("0").unwrap()), Err(BlcError::UnexpectedEnd));
+        assert_eq!(decode(&from_text("110").unwrap()), Err(BlcError::FreeVariable(1)));
+        assert_eq!(decode(&from_text("001011").unwrap()), Err(BlcError::TrailingBits(2)));
+        assert_eq!(from_text("012"), Err(BlcError::InvalidChar('2')));
+        assert_eq!(binder_name(26 * 9 + 5), "if'");
+    }
+
+    #[test]
+    fn test_decode_deep() {
+        for prefix in ["00", "01"] {
+            let bits = from_text(&prefix.repeat(1 << 20)).unwrap();
+
+            assert_eq!(decode(&bits), Err(BlcError::UnexpectedEnd));
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = r#"
+        let czero = λs. λz. z;
+        let scc = λn. λs. λz. s (n s z);
+        let plus = λm. λn. λs. λz. m s (n s z);
+        let times = λm. λn. m (plus n) czero;
+        times (scc (scc czero)) (scc czero);
+        "#;
+        let term = closed(input);
+
+        for format in [BlcFormat::Text, BlcFormat::Bytes] {
+            let encoded = encode_as(&term, format).unwrap();
+            let decoded = decode_as(&encoded, format).unwrap();
+
+            assert_eq!(encode_as(&decoded, format).unwrap(), encoded);
+        }
+    }
+
+    #[test]
+    fn test_pack() {
+        let bits = from_text("0000110").unwrap();
+
+        assert_eq!(pack(&bits), [0b00001100]);
+        assert_eq!(unpack(&pack(&bits))[..7], bits[..]);
+        assert_eq!(
+            decode_as(&[0b00001100, 0], BlcFormat::Bytes),
+            Err(BlcError::TrailingBits(9))
+        );
+    }
+}
@@ -0,0 +1,527 @@
+use crate::context::*;
+use crate::evaluate::*;
+use crate::host::*;
+use crate::io::*;
+use crate::syntax::*;
+use std::fmt;
+
+/// Primitive operations and constants that survive compilation. Operations
+/// are strict in the arguments they inspect; `If` only forces its condition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Prim {
+    Nat(i32),
+    Bool(bool),
+    String(String),
+    Float(f32),
+    Succ,
+    Pred,
+    IsZero,
+    If,
+    TimesFloat,
+    Host(HostFunction),
+}
+
+impl Prim {
+    fn arity(&self) -> usize {
+        match self {
+            Prim::Nat(_) | Prim::Bool(_) | Prim::String(_) | Prim::Float(_) => 0,
+            Prim::Succ | Prim::Pred | Prim::IsZero => 1,
+            Prim::TimesFloat => 2,
+            Prim::If => 3,
+            Prim::Host(host) => host.arity,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comb {
+    S,
+    K,
+    I,
+    B,
+    C,
+    /// A variable that has not been abstracted yet; never present in the
+    /// output of `compile`.
+    Var(usize),
+    Prim(Prim),
+    App(Box<Comb>, Box<Comb>),
+}
+
+impl fmt::Display for Comb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comb::S => write!(f, "S"),
+            Comb::K => write!(f, "K"),
+            Comb::I => write!(f, "I"),
+            Comb::B => write!(f, "B"),
+            Comb::C => write!(f, "C"),
+            Comb::Var(idx) => write!(f, "#{}", idx),
+            Comb::Prim(Prim::Nat(n)) => write!(f, "{}", n),
+            Comb::Prim(Prim::Bool(b)) => write!(f, "{}", b),
+            Comb::Prim(Prim::String(s)) => write!(f, "{:?}", s),
+            Comb::Prim(Prim::Float(flt)) => write!(f, "{:?}", flt),
+            Comb::Prim(Prim::Succ) => write!(f, "succ"),
+            Comb::Prim(Prim::Pred) => write!(f, "pred"),
+            Comb::Prim(Prim::IsZero) => write!(f, "iszero"),
+            Comb::Prim(Prim::If) => write!(f, "if"),
+            Comb::Prim(Prim::TimesFloat) => write!(f, "timesfloat"),
+            Comb::Prim(Prim::Host(host)) => write!(f, "{}", host.name),
+            Comb::App(box c1, c2 @ box Comb::App(_, _)) => write!(f, "{} ({})", c1, c2),
+            Comb::App(box c1, box c2) => write!(f, "{} {}", c1, c2),
+        }
+    }
+}
+
+fn app(c1: Comb, c2: Comb) -> Comb {
+    Comb::App(box c1, box c2)
+}
+
+fn occurs(comb: &Comb, idx: usize) -> bool {
+    match comb {
+        Comb::Var(i) => *i == idx,
+        Comb::App(box c1, box c2) => occurs(c1, idx) || occurs(c2, idx),
+        _ => false,
+    }
+}
+
+/// Renumbers the free variables of a combinator that does not mention `#0`
+/// so that it can be moved outside the innermost binder.
+fn lower(comb: Comb) -> Comb {
+    match comb {
+        Comb::Var(i) => Comb::Var(i - 1),
+        Comb::App(box c1, box c2) => app(lower(c1), lower(c2)),
+        comb => comb,
+    }
+}
+
+/// Turner's bracket abstraction of `#0`, with the η, B and C optimizations.
+fn abstract_var(comb: Comb) -> Comb {
+    match comb {
+        Comb::Var(0) => Comb::I,
+        comb if !occurs(&comb, 0) => app(Comb::K, lower(comb)),
+        Comb::App(box c1, box Comb::Var(0)) if !occurs(&c1, 0) => lower(c1),
+        Comb::App(box c1, box c2) => match (occurs(&c1, 0), occurs(&c2, 0)) {
+            (true, true) => app(app(Comb::S, abstract_var(c1)), abstract_var(c2)),
+            (true, false) => app(app(Comb::C, abstract_var(c1)), lower(c2)),
+            _ => app(app(Comb::B, lower(c1)), abstract_var(c2)),
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Compiles a hydrated, closed term to combinators. Records are not
+/// supported.
+pub fn compile(term: &Term) -> Result<Comb, EvalError> {
+    fn walk(term: &Term, binders: usize) -> Result<Comb, EvalError> {
+        Ok(match term {
+            Term::Var(_, Var { name, index, .. }) if (*index as usize) < binders => {
+                Comb::Var(*index as usize)
+            }
+            Term::Var(_, Var { name, .. }) => {
+                return Err(EvalError::EvalError(format!("cannot compile free variable {}", name)))
+            }
+            Term::Abstraction(_, _, box t1) => abstract_var(walk(t1, binders + 1)?),
+            Term::Application(_, box t1, box t2) => app(walk(t1, binders)?, walk(t2, binders)?),
+            Term::Let(_, _, box t1, box t2) => {
+                app(abstract_var(walk(t2, binders + 1)?), walk(t1, binders)?)
+            }
+            Term::If(_, box t1, box t2, box t3) => app(
+                app(
+                    app(Comb::Prim(Prim::If), walk(t1, binders)?),
+                    walk(t2, binders)?,
+                ),
+                walk(t3, binders)?,
+            ),
+            Term::True(_) => Comb::Prim(Prim::Bool(true)),
+            Term::False(_) => Comb::Prim(Prim::Bool(false)),
+            Term::Zero(_) => Comb::Prim(Prim::Nat(0)),
+            Term::String(_, s) => Comb::Prim(Prim::String(s.clone())),
+            Term::Float(_, flt) => Comb::Prim(Prim::Float(*flt)),
+            Term::Successor(_, box t1) => app(Comb::Prim(Prim::Succ), walk(t1, binders)?),
+            Term::Predecessor(_, box t1) => app(Comb::Prim(Prim::Pred), walk(t1, binders)?),
+            Term::IsZero(_, box t1) => app(Comb::Prim(Prim::IsZero), walk(t1, binders)?),
+            Term::TimesFloat(_, box t1, box t2) => app(
+                app(Comb::Prim(Prim::TimesFloat), walk(t1, binders)?),
+                walk(t2, binders)?,
+            ),
+            Term::Host(_, host, args) => args.iter().try_fold(
+                Comb::Prim(Prim::Host(host.clone())),
+                |comb, arg| Ok(app(comb, walk(arg, binders)?)),
+            )?,
+            Term::Record(_, _) | Term::Projection(_, _, _) | Term::LetPattern(_, _, _, _) => {
+                return Err(EvalError::EvalError(String::from(
+                    "records cannot be compiled to combinators",
+                )))
+            }
+        })
+    }
+
+    walk(term, 0)
+}
+
+/// Translates combinators back into lambda terms, replacing each combinator
+/// by its definition.
+pub fn decompile(comb: &Comb) -> Term {
+    fn var(name: &str, index: i32, size: i32) -> Term {
+        Term::Var(FileInfo::default(), Var::new(name, index, size))
+    }
+    fn lam(name: &str, body: Term) -> Term {
+        Term::Abstraction(FileInfo::default(), name.into(), box body)
+    }
+    fn ap(t1: Term, t2: Term) -> Term {
+        Term::Application(FileInfo::default(), box t1, box t2)
+    }
+
+    let fi = FileInfo::default();
+
+    match comb {
+        Comb::S => lam(
+            "x",
+            lam(
+                "y",
+                lam(
+                    "z",
+                    ap(
+                        ap(var("x", 2, 3), var("z", 0, 3)),
+                        ap(var("y", 1, 3), var("z", 0, 3)),
+                    ),
+                ),
+            ),
+        ),
+        Comb::K => lam("x", lam("y", var("x", 1, 2))),
+        Comb::I => lam("x", var("x", 0, 1)),
+        Comb::B => lam(
+            "x",
+            lam("y", lam("z", ap(var("x", 2, 3), ap(var("y", 1, 3), var("z", 0, 3))))),
+        ),
+        Comb::C => lam(
+            "x",
+            lam("y", lam("z", ap(ap(var("x", 2, 3), var("z", 0, 3)), var("y", 1, 3)))),
+        ),
+        Comb::Var(idx) => var(&format!("x{}", idx), *idx as i32, 0),
+        Comb::Prim(Prim::Nat(n)) => Term::from_int(*n, fi),
+        Comb::Prim(Prim::Bool(true)) => Term::True(fi),
+        Comb::Prim(Prim::Bool(false)) => Term::False(fi),
+        Comb::Prim(Prim::String(s)) => Term::String(fi, s.clone()),
+        Comb::Prim(Prim::Float(flt)) => Term::Float(fi, *flt),
+        Comb::Prim(Prim::Succ) => lam("n", Term::Successor(fi, box var("n", 0, 1))),
+        Comb::Prim(Prim::Pred) => lam("n", Term::Predecessor(fi, box var("n", 0, 1))),
+        Comb::Prim(Prim::IsZero) => lam("n", Term::IsZero(fi, box var("n", 0, 1))),
+        Comb::Prim(Prim::If) => lam(
+            "c",
+            lam(
+                "t",
+                lam(
+                    "e",
+                    Term::If(fi, box var("c", 2, 3), box var("t", 1, 3), box var("e", 0, 3)),
+                ),
+            ),
+        ),
+        Comb::Prim(Prim::TimesFloat) => lam(
+            "a",
+            lam(
+                "b",
+                Term::TimesFloat(fi, box var("a", 1, 2), box var("b", 0, 2)),
+            ),
+        ),
+        Comb::Prim(Prim::Host(host)) => Term::Host(fi, host.clone(), vec![]),
+        Comb::App(box c1, box c2) => ap(decompile(c1), decompile(c2)),
+    }
+}
+
+type NodeId = usize;
+
+#[derive(Debug, Clone)]
+enum Node {
+    App(NodeId, NodeId),
+    Atom(Comb),
+    Ind(NodeId),
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct CombStats {
+    /// Combinator and primitive rewrites.
+    pub reductions: usize,
+    /// Graph nodes allocated, including the initial graph.
+    pub allocations: usize,
+}
+
+/// A lazy graph reducer for combinator expressions. Redexes are overwritten
+/// in place, so an argument duplicated by `S` is reduced at most once.
+pub struct Reducer<'a> {
+    nodes: Vec<Node>,
+    stats: CombStats,
+    io: &'a mut dyn Io,
+}
+
+impl<'a> Reducer<'a> {
+    pub fn new(io: &'a mut dyn Io) -> Self {
+        Reducer {
+            nodes: vec![],
+            stats: CombStats::default(),
+            io,
+        }
+    }
+
+    pub fn stats(&self) -> CombStats {
+        self.stats
+    }
+
+    fn alloc(&mut self, node: Node) -> NodeId {
+        self.stats.allocations += 1;
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn load(&mut self, comb: &Comb) -> NodeId {
+        match comb {
+            Comb::App(box c1, box c2) => {
+                let n1 = self.load(c1);
+                let n2 = self.load(c2);
+                self.alloc(Node::App(n1, n2))
+            }
+            atom => self.alloc(Node::Atom(atom.clone())),
+        }
+    }
+
+    fn follow(&self, mut node: NodeId) -> NodeId {
+        while let Node::Ind(next) = self.nodes[node] {
+            node = next;
+        }
+        node
+    }
+
+    /// Reduces `comb` to normal form where it is a constant, or to weak head
+    /// normal form otherwise, and decompiles the result.
+    pub fn evaluate(&mut self, comb: &Comb) -> Result<Term, EvalError> {
+        let root = self.load(comb);
+        let root = self.whnf(root)?;
+
+        Ok(decompile(&self.read_back(root)))
+    }
+
+    fn read_back(&self, node: NodeId) -> Comb {
+        match &self.nodes[self.follow(node)] {
+            Node::App(n1, n2) => app(self.read_back(*n1), self.read_back(*n2)),
+            Node::Atom(comb) => comb.clone(),
+            Node::Ind(_) => unreachable!(),
+        }
+    }
+
+    fn whnf(&mut self, root: NodeId) -> Result<NodeId, EvalError> {
+        let mut stack: Vec<NodeId> = vec![];
+        let mut current = self.follow(root);
+
+        loop {
+            match self.nodes[current].clone() {
+                Node::Ind(next) => current = next,
+                Node::App(n1, _) => {
+                    stack.push(current);
+                    current = self.follow(n1);
+                }
+                Node::Atom(head) => {
+                    let arity = match &head {
+                        Comb::S | Comb::B | Comb::C => 3,
+                        Comb::K => 2,
+                        Comb::I => 1,
+                        Comb::Prim(prim) => prim.arity(),
+                        Comb::Var(idx) => {
+                            return Err(EvalError::EvalError(format!("free variable #{}", idx)))
+                        }
+                        Comb::App(_, _) => unreachable!(),
+                    };
+
+                    if stack.len() < arity || arity == 0 {
+                        if arity == 0 && !stack.is_empty() {
+                            return Err(EvalError::EvalError(format!("cannot apply {}", head)));
+                        }
+                        return Ok(self.follow(root));
+                    }
+
+                    let spine: Vec<NodeId> = stack.split_off(stack.len() - arity);
+                    let args: Vec<NodeId> = spine
+                        .iter()
+                        .rev()
+                        .map(|node| match self.nodes[*node] {
+                            Node::App(_, arg) => arg,
+                            _ => unreachable!(),
+                        })
+                        .collect();
+                    let redex = spine[0];
+
+                    self.stats.reductions += 1;
+                    self.nodes[redex] = self.rewrite(&head, &args)?;
+                    current = redex;
+                }
+            }
+        }
+    }
+
+    fn rewrite(&mut self, head: &Comb, args: &[NodeId]) -> Result<Node, EvalError> {
+        Ok(match (head, args) {
+            (Comb::I, [x]) => Node::Ind(*x),
+            (Comb::K, [x, _]) => Node::Ind(*x),
+            (Comb::S, [f, g, x]) => {
+                let fx = self.alloc(Node::App(*f, *x));
+                let gx = self.alloc(Node::App(*g, *x));
+                Node::App(fx, gx)
+            }
+            (Comb::B, [f, g, x]) => {
+                let gx = self.alloc(Node::App(*g, *x));
+                Node::App(*f, gx)
+            }
+            (Comb::C, [f, g, x]) => {
+                let fx = self.alloc(Node::App(*f, *x));
+                Node::App(fx, *g)
+            }
+            (Comb::Prim(Prim::If), [c, t, e]) => match self.constant(*c)? {
+                Prim::Bool(true) => Node::Ind(*t),
+                Prim::Bool(false) => Node::Ind(*e),
+                other => return Err(self.mismatch("a boolean", other)),
+            },
+            (Comb::Prim(Prim::Succ), [n]) => match self.constant(*n)? {
+                Prim::Nat(n) => Node::Atom(Comb::Prim(Prim::Nat(n + 1))),
+                other => return Err(self.mismatch("a number", other)),
+            },
+            (Comb::Prim(Prim::Pred), [n]) => match self.constant(*n)? {
+                Prim::Nat(n) => Node::Atom(Comb::Prim(Prim::Nat(std::cmp::max(n - 1, 0)))),
+                other => return Err(self.mismatch("a number", other)),
+            },
+            (Comb::Prim(Prim::IsZero), [n]) => match self.constant(*n)? {
+                Prim::Nat(n) => Node::Atom(Comb::Prim(Prim::Bool(n == 0))),
+                other => return Err(self.mismatch("a number", other)),
+            },
+            (Comb::Prim(Prim::TimesFloat), [a, b]) => match (self.constant(*a)?, self.constant(*b)?) {
+                (Prim::Float(a), Prim::Float(b)) => Node::Atom(Comb::Prim(Prim::Float(a * b))),
+                (Prim::Float(_), other) | (other, _) => return Err(self.mismatch("a float", other)),
+            },
+            (Comb::Prim(Prim::Host(host)), args) => {
+                let mut terms = vec![];
+                for arg in args {
+                    let arg = self.whnf(*arg)?;
+                    terms.push(decompile(&self.read_back(arg)));
+                }
+
+                let result = host
+                    .call(self.io, &terms)
+                    .map_err(|e| EvalError::EvalError(format!("{}: {}", host.name, e)))?;
+
+                Node::Ind(self.load(&compile(&result)?))
+            }
+            _ => unreachable!(),
+        })
+    }
+
+    fn constant(&mut self, node: NodeId) -> Result<Prim, EvalError> {
+        let node = self.whnf(node)?;
+
+        match &self.nodes[node] {
+            Node::Atom(Comb::Prim(prim)) if prim.arity() == 0 => Ok(prim.clone()),
+            _ => Err(EvalError::EvalError(format!(
+                "expected a constant, got {}",
+                self.read_back(node)
+            ))),
+        }
+    }
+
+    fn mismatch(&self, expected: &str, prim: Prim) -> EvalError {
+        EvalError::EvalError(format!("expected {}, got {}", expected, Comb::Prim(prim)))
+    }
+}
+
+/// Compiles a command's term along with the globals it mentions, and reduces
+/// it with the combinator reducer.
+pub fn evaluate_combinators(
+    context: &Context,
+    term: &Term,
+    io: &mut dyn Io,
+) -> Result<(Comb, Term, CombStats), EvalError> {
+    let comb = compile(&close_over_globals(context, term))?;
+    let mut reducer = Reducer::new(io);
+    let value = reducer.evaluate(&comb)?;
+
+    Ok((comb, value, reducer.stats()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::combinators::*;
+    use crate::memo::alpha_key;
+    use crate::test_util::last_term;
+
+    fn compiled(input: &str) -> Comb {
+        let (term, context) = last_term(input);
+
+        compile(&close_over_globals(&context, &term)).expect("Failed to compile")
+    }
+
+    #[test]
+    fn test_bracket_abstraction() {
+        assert_eq!(compiled("λx. x;"), Comb::I);
+        assert_eq!(compiled("λx. λy. x;"), Comb::K);
+        assert_eq!(format!("{}", compiled("λx. λy. y x;")), "C I");
+        assert_eq!(format!("{}", compiled("λf. λg. λx. f (g x);")), "B");
+        assert_eq!(format!("{}", compiled("λx. x x;")), "S I I");
+    }
+
+    #[test]
+    fn test_decompile() {
+        let (term, context) = last_term("λa. λb. λc. a c (b c);");
+
+        assert_eq!(
+            alpha_key(&decompile(&Comb::S)),
+            alpha_key(&close_over_globals(&context, &term))
+        );
+        assert_eq!(
+            format!("{}", decompile(&compiled("λx. λy. y x;"))),
+            "(λx y z. x z y) (λx. x)"
+        );
+    }
+
+    #[test]
+    fn test_combinators_agree_with_evaluator() {
+        for input in [
+            include_str!("lambda-files/test1.f"),
+            include_str!("lambda-files/test2.f"),
+            include_str!("lambda-files/test4.f"),
+            include_str!("lambda-files/test5.f"),
+        ] {
+            let (term, context) = last_term(input);
+            let mut io = MemoryIo::default();
+            let (_, value, stats) =
+                evaluate_combinators(&context, &term, &mut io).expect("Failed to evaluate");
+
+            let expectation = evaluate_command(&mut MemoryIo::default(), &context, &term).unwrap();
+
+            assert_eq!(value.without_locations(), expectation.without_locations());
+            assert!(stats.reductions > 0);
+        }
+    }
+
+    #[test]
+    fn test_combinators_church_arithmetic() {
+        let input = r#"
+        let czero = λs. λz. z;
+        let scc = λn. λs. λz. s (n s z);
+        let plus = λm. λn. λs. λz. m s (n s z);
+        let times = λm. λn. m (plus n) czero;
+        let Y = λf. (λx. f(λy. x x y)) (λx. f(λy. x x y));
+        let cn = λfn. λn. if iszero n then czero else scc (fn (-n));
+        let churchnat = Y cn;
+        let realnat = λm. m (λx. + x) 0;
+        realnat (times (churchnat 3) (plus (churchnat 2) (churchnat 4)));
+        "#;
+        let (term, context) = last_term(input);
+        let (_, value, _) = evaluate_combinators(&context, &term, &mut MemoryIo::default())
+            .expect("Failed to evaluate");
+
+        assert_eq!(value, Term::from_int(18, FileInfo::default()));
+    }
+
+    #[test]
+    fn test_combinators_reject_records() {
+        let (term, context) = last_term("{a = 1};");
+
+        assert!(evaluate_combinators(&context, &term, &mut MemoryIo::default()).is_err());
+    }
+}
@@ -1,5 +1,6 @@
+use crate::fixity::*;
+use crate::literals::Literals;
 use crate::syntax::*;
-use std::marker::PhantomData;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ContextMember {
@@ -7,14 +8,8 @@ pub struct ContextMember {
     pub binding: Binding,
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct Context(Vec<ContextMember>);
-
-impl Default for Context {
-    fn default() -> Context {
-        Context(vec![])
-    }
-}
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Context(Vec<ContextMember>, Fixities, Literals);
 
 impl Context {
     pub fn append_binding(&mut self, el: ContextMember) {
@@ -33,7 +28,7 @@ impl Context {
 
         next.append_binding(el);
 
-        return next;
+        next
     }
 
     pub fn update_binding(&mut self, name: &str, binding: &Binding) -> Result<(), String> {
@@ -90,10 +85,46 @@ impl Context {
             ))
     }
 
+    /// The same names with every binding dropped, which is all name
+    /// resolution needs and much cheaper to clone.
+    pub fn names(&self) -> Context {
+        Context(
+            self.0
+                .iter()
+                .map(|ContextMember { name, .. }| ContextMember {
+                    name: name.clone(),
+                    binding: Binding::NameBind,
+                })
+                .collect(),
+            Fixities::default(),
+            self.2,
+        )
+    }
+
+    pub fn declare_fixity(&mut self, operator: &str, fixity: Fixity) {
+        self.1.declare(operator, fixity)
+    }
+
+    pub fn fixities(&self) -> &Fixities {
+        &self.1
+    }
+
+    pub fn set_literals(&mut self, literals: Literals) {
+        self.2 = literals
+    }
+
+    pub fn literals(&self) -> Literals {
+        self.2
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn get_binding(&self, idx: usize) -> Option<Binding> {
         if let Some(ctx_member) = self.0.get(idx) {
             return Some(ctx_member.binding.shift((idx as i32) + 1));
@@ -145,13 +176,10 @@ mod tests {
             binding: Binding::NameBind,
         });
 
-        assert_eq!(
-            context.0.contains(&ContextMember {
-                name: "test".into(),
-                binding: Binding::NameBind
-            }),
-            true
-        );
+        assert!(context.0.contains(&ContextMember {
+            name: "test".into(),
+            binding: Binding::NameBind
+        }));
     }
 
     #[test]
@@ -160,13 +188,10 @@ mod tests {
 
         context.append_name("test");
 
-        assert_eq!(
-            context.0.contains(&ContextMember {
-                name: "test".into(),
-                binding: Binding::NameBind
-            }),
-            true
-        );
+        assert!(context.0.contains(&ContextMember {
+            name: "test".into(),
+            binding: Binding::NameBind
+        }));
     }
 
     #[test]
@@ -175,8 +200,8 @@ mod tests {
 
         context.append_name("exists");
 
-        assert_eq!(context.is_name_bound("exists"), true);
-        assert_eq!(context.is_name_bound("nonexists"), false);
+        assert!(context.is_name_bound("exists"));
+        assert!(!context.is_name_bound("nonexists"));
     }
 
     #[test]
@@ -10,8 +10,10 @@ impl VisitWithContext for Term {
         fn walk(context: &Context, container_size: i32, term: &Term) -> Term {
             match term {
                 Term::String(_, _) => term.clone(),
+                // A name `resolve::unbound` would report points past the end
+                // of the context, where evaluation finds no binding for it.
                 Term::Var(file_info, Var { name, .. }) => {
-                    let index = context.lookup_idx_by_name(name).unwrap();
+                    let index = context.lookup_idx_by_name(name).unwrap_or(context.len());
                     Term::Var(
                         file_info.clone(),
                         Var::new(name, index as i32, container_size),
@@ -26,7 +28,7 @@ impl VisitWithContext for Term {
                     box walk(context, container_size, t3),
                 ),
                 Term::Let(file_info, name, box t1, box t2) => {
-                    let ctx1 = context.add_name(&name);
+                    let ctx1 = context.add_name(name);
                     Term::Let(
                         file_info.clone(),
                         name.clone(),
@@ -34,6 +36,16 @@ impl VisitWithContext for Term {
                         box walk(&ctx1, container_size + 1, t2),
                     )
                 }
+                Term::LetPattern(file_info, pattern, box t1, box t2) => {
+                    let binders = pattern.binders();
+                    let ctx1 = binders.iter().fold(context.clone(), |ctx, name| ctx.add_name(name));
+                    Term::LetPattern(
+                        file_info.clone(),
+                        pattern.clone(),
+                        box walk(context, container_size, t1),
+                        box walk(&ctx1, container_size + binders.len() as i32, t2),
+                    )
+                }
                 Term::Projection(file_info, box t1, l) => Term::Projection(
                     file_info.clone(),
                     box walk(context, container_size, t1),
@@ -80,9 +92,16 @@ impl VisitWithContext for Term {
                     box walk(context, container_size, t1),
                     box walk(context, container_size, t2),
                 ),
+                Term::Host(file_info, host, args) => Term::Host(
+                    file_info.clone(),
+                    host.clone(),
+                    args.iter()
+                        .map(|arg| walk(context, container_size, arg))
+                        .collect(),
+                ),
             }
         }
 
-        walk(global_context, 0, &self)
+        walk(global_context, 0, self)
     }
 }
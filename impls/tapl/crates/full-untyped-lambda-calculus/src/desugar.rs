@@ -0,0 +1,342 @@
+use crate::context::Context;
+use crate::fixity::*;
+use crate::literals::*;
+use crate::surface::*;
+use crate::syntax::*;
+
+/// A surface term that has no core equivalent, and where it is.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DesugarError(pub FileInfo, pub String);
+
+/// Lowers the surface syntax to the core `Term`s the evaluators understand.
+/// Multi-argument abstractions become nested abstractions, each binding of a
+/// `let` or `where` becomes its own `let`, so later bindings can refer to
+/// earlier ones, infix operators become applications of the functions the
+/// fixities in `context` give them, number literals become the numbers
+/// `context` asks for, and `q.n` becomes the qualified name of
+/// an import when `context` binds one.
+pub fn desugar(surface: &Surface, context: &Context) -> Result<Term, DesugarError> {
+    desugar_under(surface, context, &[])
+}
+
+/// `desugar` beneath the local binders `bound`, innermost last.
+fn desugar_under(
+    surface: &Surface,
+    context: &Context,
+    bound: &[String],
+) -> Result<Term, DesugarError> {
+    let go = |s: &Surface| desugar_under(s, context, bound);
+    let under = |names: &[String], s: &Surface| {
+        desugar_under(s, context, &[bound, names].concat())
+    };
+    let fixities = context.fixities();
+
+    Ok(match surface {
+        Surface::String(info, s) => Term::String(info.clone(), s.clone()),
+        Surface::Var(info, name) => Term::Var(info.clone(), Var::new(name, 0, 0)),
+        Surface::True(info) => Term::True(info.clone()),
+        Surface::False(info) => Term::False(info.clone()),
+        Surface::Nat(info, digits) => {
+            let n = match digits.parse() {
+                Ok(n) if n <= MAX_LITERAL => n,
+                _ => {
+                    return Err(DesugarError(
+                        info.clone(),
+                        format!("number literals go up to {}", MAX_LITERAL),
+                    ));
+                }
+            };
+            // A unary literal means the global `succ` and `zero`, which a
+            // local binding of the same name would capture.
+            let literals = context.literals();
+            if let Some(name) = ["succ", "zero"]
+                .iter()
+                .find(|name| literals == Literals::Unary && bound.iter().any(|b| b == *name))
+            {
+                return Err(DesugarError(
+                    info.clone(),
+                    format!("unary literals need the global `{}`, which is bound here", name),
+                ));
+            }
+
+            literals.term(n, info.clone())
+        }
+        Surface::Float(info, digits) => Term::Float(info.clone(), digits.parse().unwrap()),
+        Surface::If(info, box t1, box t2, box t3) => {
+            Term::If(info.clone(), box go(t1)?, box go(t2)?, box go(t3)?)
+        }
+        Surface::Let(info, definitions, box body) | Surface::Where(info, box body, definitions) => {
+            let names = definitions.iter().map(|d| d.name.clone()).collect::<Vec<String>>();
+
+            definitions.iter().enumerate().rev().try_fold(
+                under(&names, body)?,
+                |body, (i, definition)| {
+                    Ok(Term::Let(
+                        info.clone(),
+                        definition.name.clone(),
+                        box desugar_definition_under(
+                            definition,
+                            context,
+                            &[bound, &names[..i]].concat(),
+                        )?,
+                        box body,
+                    ))
+                },
+            )?
+        }
+        Surface::LetPattern(info, pattern, box t1, box t2) => {
+            let binders = pattern.binders();
+            if let Some(name) = binders
+                .iter()
+                .enumerate()
+                .find_map(|(i, name)| binders[..i].contains(name).then_some(name))
+            {
+                return Err(DesugarError(
+                    info.clone(),
+                    format!("`{}` is bound more than once in the pattern", name),
+                ));
+            }
+
+            let binders = binders.iter().map(|name| name.to_string()).collect::<Vec<String>>();
+
+            Term::LetPattern(info.clone(), pattern.clone(), box go(t1)?, box under(&binders, t2)?)
+        }
+        Surface::Abstraction(info, params, box body) => {
+            abstract_params(info, params, under(params, body)?)
+        }
+        Surface::Application(info, box t1, box t2) => {
+            Term::Application(info.clone(), box go(t1)?, box go(t2)?)
+        }
+        Surface::Infix(info, box first, rest) => {
+            let rest = rest
+                .iter()
+                .map(|(operator, operand)| Ok((operator.clone(), go(operand)?)))
+                .collect::<Result<Vec<(Operator, Term)>, DesugarError>>()?;
+
+            reassociate(
+                go(first)?,
+                rest,
+                |Operator(_, symbol)| fixities.resolve(symbol),
+                |Operator(op_info, symbol), lhs, rhs| {
+                    let function = fixities.resolve(&symbol).function;
+
+                    Term::Application(
+                        info.clone(),
+                        box Term::Application(
+                            info.clone(),
+                            box Term::Var(op_info, Var::new(&function, 0, 0)),
+                            box lhs,
+                        ),
+                        box rhs,
+                    )
+                },
+            )
+            .map_err(|(Operator(op_info, _), message)| DesugarError(op_info, message))?
+        }
+        Surface::Record(info, fields) => Term::Record(
+            info.clone(),
+            fields
+                .iter()
+                .map(|(name, field)| Ok((name.clone(), box go(field)?)))
+                .collect::<Result<Vec<(String, Box<Term>)>, DesugarError>>()?,
+        ),
+        Surface::Projection(info, box t1, label) => match qualified_name(surface, context) {
+            Some(name) if context.is_name_bound(&name) => {
+                Term::Var(info.clone(), Var::new(&name, 0, 0))
+            }
+            _ => Term::Projection(info.clone(), box go(t1)?, label.clone()),
+        },
+        Surface::Successor(info, box t1) => Term::Successor(info.clone(), box go(t1)?),
+        Surface::Predecessor(info, box t1) => Term::Predecessor(info.clone(), box go(t1)?),
+        Surface::IsZero(info, box t1) => Term::IsZero(info.clone(), box go(t1)?),
+        Surface::TimesFloat(info, box t1, box t2) => {
+            Term::TimesFloat(info.clone(), box go(t1)?, box go(t2)?)
+        }
+    })
+}
+
+/// The term a definition binds its name to.
+pub fn desugar_definition(
+    definition: &Definition,
+    context: &Context,
+) -> Result<Term, DesugarError> {
+    desugar_definition_under(definition, context, &[])
+}
+
+fn desugar_definition_under(
+    definition: &Definition,
+    context: &Context,
+    bound: &[String],
+) -> Result<Term, DesugarError> {
+    Ok(abstract_params(
+        &definition.info,
+        &definition.params,
+        desugar_under(&definition.body, context, &[bound, &definition.params].concat())?,
+    ))
+}
+
+/// `q.n` read as a single name, for a `q` that is not a global. Local
+/// variables are not known here, so an import called `q` hides a local one.
+fn qualified_name(surface: &Surface, context: &Context) -> Option<String> {
+    match surface {
+        Surface::Var(_, qualifier) if !context.is_name_bound(qualifier) => Some(qualifier.clone()),
+        Surface::Projection(_, box t1, label) => {
+            qualified_name(t1, context).map(|qualifier| format!("{}.{}", qualifier, label))
+        }
+        _ => None,
+    }
+}
+
+fn abstract_params(info: &FileInfo, params: &[String], body: Term) -> Term {
+    params
+        .iter()
+        .rev()
+        .fold(body, |body, param| Term::Abstraction(info.clone(), param.clone(), box body))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::desugar::*;
+    use crate::parser::*;
+    use lalrpop_util::ParseError;
+
+    fn same_as(sugared: &str, plain: &str) {
+        let (sugared, _) = parse(sugared).expect("parse error");
+        let (plain, _) = parse(plain).expect("parse error");
+
+        assert_eq!(sugared.without_locations(), plain.without_locations());
+    }
+
+    #[test]
+    fn test_multi_argument_abstraction() {
+        same_as("λx y z. x z (y z);", "λx. λy. λz. x z (y z);");
+        same_as("lambda f x. f x;", "λf. λx. f x;");
+    }
+
+    #[test]
+    fn test_multi_binding_let() {
+        same_as(
+            "let x = 1, y = + x in iszero y;",
+            "let x = 1 in let y = + x in iszero y;",
+        );
+        same_as("let k x y = x in k;", "let k = λx. λy. x in k;");
+    }
+
+    #[test]
+    fn test_function_definition() {
+        same_as("let const x y = x; const;", "let const = λx. λy. x; const;");
+        same_as("let f x = g x where g y = y;", "let f = λx. let g = λy. y in g x;");
+    }
+
+    #[test]
+    fn test_where() {
+        same_as(
+            "twice id 0 where twice f x = f (f x), id x = x;",
+            "let twice = λf. λx. f (f x) in let id = λx. x in twice id 0;",
+        );
+        same_as("(x where x = 1) (y where y = 2);", "(let x = 1 in x) (let y = 2 in y);");
+    }
+
+    #[test]
+    fn test_infix() {
+        let declarations = "infixl 6 + = plus; infixl 7 * = times; infixr 5 :: = cons; infix 4 == = eq;";
+
+        same_as(
+            &format!("{} a + b * c + d;", declarations),
+            &format!("{} plus (plus a (times b c)) d;", declarations),
+        );
+        same_as(
+            &format!("{} x :: y :: nil == (λz. z) l;", declarations),
+            &format!("{} eq (cons x (cons y nil)) ((λz. z) l);", declarations),
+        );
+    }
+
+    #[test]
+    fn test_undeclared_operator() {
+        let (commands, _) = parse("f x <> + y;").expect("parse error");
+
+        match &commands[0] {
+            Command::Eval(_, Term::Application(_, box Term::Application(_, box Term::Var(_, var), _), box Term::Successor(..))) => {
+                assert_eq!(var.name, "<>")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_infix_errors() {
+        let input = "infix 4 == = eq;
+a == b == c;";
+        let error = parse(input).unwrap_err();
+
+        match error {
+            ParseError::User {
+                error: DesugarError(info, message),
+            } => {
+                assert_eq!((info.line(), info.column()), (2, 8));
+                assert!(message.contains("`infix 4`"));
+            }
+            _ => panic!(),
+        }
+
+        match parse("let {a = x, b = {c = x}} = r in x;").unwrap_err() {
+            ParseError::User {
+                error: DesugarError(_, message),
+            } => assert_eq!(message, "`x` is bound more than once in the pattern"),
+            _ => panic!(),
+        }
+
+        for input in ["infixl 10 +++ = f;", "infixl 99999999999 +++ = f;"] {
+            match parse(input).unwrap_err() {
+                ParseError::User {
+                    error: DesugarError(info, message),
+                } => {
+                    assert_eq!(message, "precedences go from 0 to 9");
+                    assert_eq!(info.column(), 8);
+                }
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_literals() {
+        same_as("#literals church; 2;", "#literals church; λs z. s (s z);");
+        same_as("#literals unary; 2;", "#literals unary; succ (succ zero);");
+        same_as("#literals unary; #literals native; 1;", "#literals unary; #literals native; + 0;");
+
+        for input in ["1001;", "99999999999999999999;"] {
+            match parse(input).unwrap_err() {
+                ParseError::User {
+                    error: DesugarError(_, message),
+                } => assert_eq!(message, "number literals go up to 1000"),
+                _ => panic!(),
+            }
+        }
+
+        same_as("#literals unary; λs. 1;", "#literals unary; λs. succ zero;");
+        same_as("#literals church; λsucc. 1;", "#literals church; λsucc. λs z. s z;");
+
+        for input in [
+            "#literals unary; (λsucc. 2) (λn. true);",
+            "#literals unary; let zero = 1 in 0;",
+            "#literals unary; let f zero = 0;",
+            "#literals unary; let {a = succ} = r in 1;",
+            "#literals unary; 0 where zero = true, one = 1;",
+        ] {
+            match parse(input).unwrap_err() {
+                ParseError::User {
+                    error: DesugarError(_, message),
+                } => assert!(message.starts_with("unary literals need the global")),
+                _ => panic!("{} should not parse", input),
+            }
+        }
+
+        match parse("#literals roman; #numbers church;") {
+            Err(ParseError::User {
+                error: DesugarError(_, message),
+            }) => assert!(message.starts_with("`roman` is not a kind of literal")),
+            _ => panic!(),
+        }
+    }
+}
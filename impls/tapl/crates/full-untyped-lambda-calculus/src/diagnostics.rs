@@ -0,0 +1,299 @@
+use crate::desugar::DesugarError;
+use crate::evaluate::EvalError;
+use crate::limits::Limit;
+use crate::parser::location::Source;
+use crate::parser::SyntaxError;
+use crate::syntax::FileInfo;
+use lalrpop_util::lexer::Token;
+use lalrpop_util::ParseError;
+use std::io::IsTerminal;
+
+/// An error ready to be shown to a user: a message, the source it points at
+/// and any notes to print underneath.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub location: Option<FileInfo>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: &str) -> Self {
+        Diagnostic {
+            message: message.into(),
+            location: None,
+            notes: vec![],
+        }
+    }
+
+    pub fn at(mut self, location: FileInfo) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    pub fn note(mut self, note: &str) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn from_parse_error(filename: &str, input: &str, error: &SyntaxError) -> Self {
+        let source = Source::new(filename, input);
+
+        match error {
+            ParseError::InvalidToken { location } => {
+                Diagnostic::error("unrecognized character").at(source.info(*location, *location + 1))
+            }
+            ParseError::UnrecognizedEOF { location, expected } => {
+                Diagnostic::error("unexpected end of input")
+                    .at(source.info(*location, *location))
+                    .note(&expected_in_words(expected))
+            }
+            ParseError::UnrecognizedToken {
+                token: (l, Token(_, text), r),
+                expected,
+            } => Diagnostic::error(&format!("unexpected `{}`", text))
+                .at(source.info(*l, *r))
+                .note(&expected_in_words(expected)),
+            ParseError::ExtraToken {
+                token: (l, Token(_, text), r),
+            } => Diagnostic::error(&format!("unexpected `{}` after the end of input", text))
+                .at(source.info(*l, *r)),
+            ParseError::User {
+                error: DesugarError(info, message),
+            } => Diagnostic::error(message).at(info.clone()),
+        }
+    }
+
+    /// Renders the diagnostic with the offending line of `source` and a caret
+    /// under the span, using ANSI colours if `colour` is set.
+    pub fn render(&self, source: &str, colour: bool) -> String {
+        let paint = |code: &str, text: &str| match colour {
+            true => format!("\x1b[{}m{}\x1b[0m", code, text),
+            false => String::from(text),
+        };
+        let mut out = format!("{}: {}\n", paint("1;31", "error"), paint("1", &self.message));
+
+        match &self.location {
+            Some(info) if info.is_known() && (info.line() as usize) <= source.lines().count().max(1) => {
+                let line = source.lines().nth(info.line() as usize - 1).unwrap_or("");
+                let gutter = " ".repeat(info.line().to_string().len());
+                let (start, end) = info.span();
+                let width = source
+                    .get(start..end.max(start))
+                    .map_or(1, |text| text.lines().next().unwrap_or("").chars().count())
+                    .max(1);
+
+                out.push_str(&format!("{}{} {}\n", gutter, paint("1;34", "-->"), info));
+                out.push_str(&format!("{} {}\n", gutter, paint("1;34", "|")));
+                out.push_str(&format!("{} {}\n", paint("1;34", &format!("{} |", info.line())), line));
+                out.push_str(&format!(
+                    "{} {}{}\n",
+                    gutter,
+                    paint("1;34", "|"),
+                    paint(
+                        "1;31",
+                        &format!(
+                            "{}{}",
+                            " ".repeat(info.column() as usize),
+                            "^".repeat(width)
+                        )
+                    )
+                ));
+            }
+            Some(info) if info.is_known() => {
+                out.push_str(&format!(" {} {}\n", paint("1;34", "-->"), info));
+            }
+            _ => {}
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!("{} {}\n", paint("1;34", "="), note));
+        }
+
+        out
+    }
+}
+
+impl EvalError {
+    /// Every problem this error stands for; a failed parse reports each
+    /// syntax error in the file.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        vec![match self {
+            EvalError::Parse(diagnostics) => return diagnostics.clone(),
+            EvalError::NoFile(e) => Diagnostic::error(&format!("could not read file: {}", e)),
+            EvalError::EvalError(e) => Diagnostic::error(e),
+            EvalError::Runtime(info, e) => Diagnostic::error(e).at(info.clone()),
+            EvalError::Unbound(name, info) => {
+                Diagnostic::error(&format!("`{}` is not bound", name)).at(info.clone())
+            }
+            EvalError::LimitExceeded(limit, actual) => {
+                let what = match limit {
+                    Limit::TermSize(_) => "term size",
+                    Limit::BindingDepth(_) => "binding depth",
+                    Limit::Memory(_) => "memory",
+                };
+
+                Diagnostic::error(&format!("{} limit exceeded", what))
+                    .note(&format!("reached {}, the limit is {}", actual, limit.max()))
+            }
+        }]
+    }
+
+    /// Renders every diagnostic, separated by blank lines.
+    pub fn render(&self, source: &str, colour: bool) -> String {
+        self.diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.render(source, colour))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Describes one of LALRPOP's expected terminals, which are either quoted
+/// literals or quoted regular expressions.
+fn describe_terminal(terminal: &str) -> String {
+    match terminal {
+        r###"r#"[a-zA-Z_À-ÖØ-öø-ɏΑ-Ωα-κμ-ωА-я][a-zA-Z0-9_'À-ÖØ-öø-ɏΑ-Ωα-κμ-ωА-я]*"#"### => {
+            String::from("a name")
+        }
+        r###"r#"[!$%&*+\\-/<=>?@^|~:]+"#"### => String::from("an operator"),
+        r###"r#"[0-9]+"#"### => String::from("a number"),
+        r###"r#"[0-9]+\\.[0-9]+"#"### => String::from("a decimal number"),
+        r###"r#"\\(-[0-9]+\\.[0-9]+\\)"#"### => String::from("a negative decimal number"),
+        r###"r#""#"### => String::from("the end of input"),
+        r###"r#"\"[^\"]*\""#"### => String::from("a string"),
+        quoted if quoted.starts_with('"') => format!("`{}`", quoted.trim_matches('"')),
+        other => format!("`{}`", other),
+    }
+}
+
+fn expected_in_words(expected: &[String]) -> String {
+    let mut words: Vec<String> = vec![];
+    for terminal in expected {
+        let word = describe_terminal(terminal);
+        if !words.contains(&word) {
+            words.push(word);
+        }
+    }
+
+    match words.split_last() {
+        None => String::from("nothing else was expected here"),
+        Some((last, [])) => format!("expected {}", last),
+        Some((last, rest)) => format!("expected {} or {}", rest.join(", "), last),
+    }
+}
+
+/// Whether stderr is a terminal that should get colours, honouring
+/// `NO_COLOR`.
+pub fn use_colour() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::*;
+    use crate::diagnostics::*;
+    use crate::evaluate::*;
+    use crate::io::*;
+    use crate::parser::*;
+    use crate::test_util::TempDir;
+
+    fn parse_diagnostic(input: &str) -> Diagnostic {
+        let error = parse_file("test.f", input, Context::default()).unwrap_err();
+
+        Diagnostic::from_parse_error("test.f", input, &error)
+    }
+
+    #[test]
+    fn test_unexpected_token() {
+        let input = "let x = 1;\nλy. iszero );";
+        let diagnostic = parse_diagnostic(input);
+
+        assert_eq!(diagnostic.message, "unexpected `)`");
+        assert!(diagnostic.notes[0].starts_with("expected "));
+        assert!(diagnostic.notes[0].contains("a name"));
+        assert!(!diagnostic.notes[0].contains("r#"));
+        assert_eq!(
+            diagnostic.render(input, false),
+            format!(
+                "error: unexpected `)`\n \
+                 --> test.f:2:12\n  \
+                 |\n\
+                 2 | λy. iszero );\n  \
+                 |            ^\n\
+                 = {}\n",
+                diagnostic.notes[0]
+            )
+        );
+    }
+
+    #[test]
+    fn test_operator_grouping() {
+        let input = "infix 4 == = eq;\na == b == c;";
+        let diagnostic = parse_diagnostic(input);
+
+        assert!(diagnostic.message.starts_with("cannot mix `infix 4` and `infix 4`"));
+        assert!(diagnostic.render(input, false).ends_with("2 | a == b == c;\n  |        ^^\n"));
+    }
+
+    #[test]
+    fn test_unexpected_end() {
+        let diagnostic = parse_diagnostic("λx. x");
+
+        assert_eq!(diagnostic.message, "unexpected end of input");
+        assert!(diagnostic.notes[0].contains("`;`"));
+        assert!(diagnostic.render("λx. x", true).contains("\x1b[1;31merror"));
+    }
+
+    #[test]
+    fn test_all_syntax_errors() {
+        let dir = TempDir::new();
+        let input = "λx. );\nlet y = 1;\nif then;\ny;";
+        let path = dir.write("all_syntax_errors.f", input);
+
+        let error = eval(path.to_str().unwrap()).unwrap_err();
+        let diagnostics = error.diagnostics();
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].location.as_ref().unwrap().line(), 1);
+        assert_eq!(diagnostics[1].message, "unexpected `then`");
+        assert_eq!(diagnostics[1].location.as_ref().unwrap().line(), 3);
+        assert_eq!(error.render(input, false).matches("error: ").count(), 2);
+    }
+
+    #[test]
+    fn test_unbound_name() {
+        let input = "let f = λx. x;\nf (g 1);";
+        let error = Evaluator::new(&mut MemoryIo::default())
+            .eval_source(input, Context::default())
+            .unwrap_err();
+        let rendered = error.render(input, false);
+
+        assert!(matches!(error, EvalError::Parse(ref diagnostics) if diagnostics.len() == 1));
+        assert!(rendered.starts_with("error: `g` is not bound\n --> 2:4\n"));
+        assert!(rendered.ends_with("2 | f (g 1);\n  |    ^\n"));
+    }
+
+    #[test]
+    fn test_runtime_error() {
+        let mut context = Context::default();
+        context.register_io();
+        let input = "\n  read_nat 0;";
+        let error = Evaluator::new(&mut MemoryIo::new(&["ten"]))
+            .eval_source(input, context)
+            .unwrap_err();
+        let rendered = error.render(input, false);
+
+        assert!(rendered.contains("2 |   read_nat 0;\n  |   ^^^^^^^^^^\n"));
+    }
+
+    #[test]
+    fn test_limit_exceeded() {
+        let error = EvalError::LimitExceeded(Limit::TermSize(10), 12);
+
+        assert_eq!(
+            error.render("", false),
+            "error: term size limit exceeded\n= reached 12, the limit is 10\n"
+        );
+    }
+}
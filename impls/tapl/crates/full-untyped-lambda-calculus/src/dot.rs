@@ -0,0 +1,287 @@
+use crate::evaluate::is_numeric;
+use crate::fixity::Fixities;
+use crate::memo::alpha_key;
+use crate::pretty::Style;
+use crate::syntax::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// How many terms a reduction graph draws unless `--dot-nodes` says otherwise;
+/// Graphviz takes minutes to lay out many more.
+pub const MAX_NODES: usize = 200;
+
+/// What `--dot` draws for each evaluated term.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DotGraph {
+    /// The term's syntax tree.
+    Tree,
+    /// The terms evaluation steps through.
+    Reduction,
+}
+
+impl FromStr for DotGraph {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(DotGraph::Tree),
+            "reduction" => Ok(DotGraph::Reduction),
+            _ => Err(format!("`{}` is not a graph; expected tree or reduction", s)),
+        }
+    }
+}
+
+/// `s` as the inside of a DOT string, each line left-justified.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\l")
+}
+
+/// The subterms drawn below `term`, each with the label of the edge to it
+/// and how many variables `term` binds in it.
+fn children(term: &Term) -> Vec<(&str, &Term, usize)> {
+    match term {
+        term if is_numeric(term) => vec![],
+        Term::If(_, box t1, box t2, box t3) => {
+            vec![("if", t1, 0), ("then", t2, 0), ("else", t3, 0)]
+        }
+        Term::Let(_, _, box t1, box t2) => vec![("", t1, 0), ("in", t2, 1)],
+        Term::LetPattern(_, pattern, box t1, box t2) => {
+            vec![("", t1, 0), ("in", t2, pattern.binders().len())]
+        }
+        Term::Record(_, fields) => {
+            fields.iter().map(|(label, box t)| (label.as_str(), t, 0)).collect()
+        }
+        Term::Abstraction(_, _, box t) => vec![("", t, 1)],
+        Term::Projection(_, box t, _)
+        | Term::Successor(_, box t)
+        | Term::Predecessor(_, box t)
+        | Term::IsZero(_, box t) => vec![("", t, 0)],
+        Term::Application(_, box t1, box t2) | Term::TimesFloat(_, box t1, box t2) => {
+            vec![("", t1, 0), ("", t2, 0)]
+        }
+        Term::Host(_, _, args) => args.iter().map(|arg| ("", arg, 0)).collect(),
+        _ => vec![],
+    }
+}
+
+fn label(term: &Term, binders: usize) -> String {
+    match term {
+        Term::Var(_, var) if (var.index as usize) < binders => {
+            format!("{} ({})", var.name, var.index)
+        }
+        Term::Var(_, var) => var.name.clone(),
+        term if is_numeric(term) => term.to_string(),
+        Term::If(..) => "if".into(),
+        Term::Let(_, x, _, _) => format!("let {}", x),
+        Term::LetPattern(_, pattern, _, _) => format!("let {}", pattern),
+        Term::Record(..) => "{}".into(),
+        Term::Projection(_, _, label) => format!(".{}", label),
+        Term::Abstraction(_, x, _) => format!("λ{}", x),
+        Term::Application(..) => "@".into(),
+        Term::Successor(..) => "succ".into(),
+        Term::Predecessor(..) => "pred".into(),
+        Term::IsZero(..) => "iszero".into(),
+        Term::TimesFloat(..) => "timesfloat".into(),
+        Term::Host(_, host, _) => host.name.clone(),
+        term => term.to_string(),
+    }
+}
+
+/// `term`'s syntax tree, with a dashed edge from each variable to the
+/// binder its de Bruijn index refers to. Variables bound outside `term` are
+/// drawn by name alone.
+pub fn term_tree(term: &Term) -> String {
+    fn walk(term: &Term, binders: &mut Vec<usize>, out: &mut String, nodes: &mut usize) -> usize {
+        let id = *nodes;
+        *nodes += 1;
+        writeln!(out, "  n{} [label=\"{}\"];", id, escape(&label(term, binders.len()))).unwrap();
+
+        if let Term::Var(_, var) = term {
+            if let Some(binder) = binders.len().checked_sub(var.index as usize + 1) {
+                let edge = format!("n{} -> n{}", id, binders[binder]);
+                writeln!(out, "  {} [style=dashed, constraint=false];", edge).unwrap();
+            }
+        }
+        for (edge, child, binds) in children(term) {
+            binders.extend(std::iter::repeat_n(id, binds));
+            let child_id = walk(child, binders, out, nodes);
+            binders.truncate(binders.len() - binds);
+
+            match edge {
+                "" => writeln!(out, "  n{} -> n{};", id, child_id),
+                edge => writeln!(out, "  n{} -> n{} [label=\"{}\"];", id, child_id, escape(edge)),
+            }
+            .unwrap();
+        }
+
+        id
+    }
+
+    let mut out = String::from("digraph term {\n  node [shape=plaintext];\n");
+    walk(term, &mut vec![], &mut out, &mut 0);
+    out.push_str("}\n");
+
+    out
+}
+
+/// The reduction sequence from `term` through `steps`, as returned by
+/// `Evaluator::trace`, one node per distinct term and each edge labelled
+/// with the rule that took it. The term evaluation stops at is circled twice.
+/// Past `max_nodes` terms the graph ends in a note of how many steps it leaves
+/// out.
+pub fn reduction(
+    term: &Term,
+    steps: &[(&str, Term)],
+    fixities: &Fixities,
+    style: &Style,
+    max_nodes: usize,
+) -> String {
+    let mut out = String::from("digraph reduction {\n");
+    out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+    let mut ids = HashMap::from([(alpha_key(term), 0)]);
+    let mut terms = vec![term];
+    let mut edges = String::new();
+    let mut last = 0;
+    let mut left_out = 0;
+
+    for (i, (rule, t)) in steps.iter().enumerate() {
+        let key = alpha_key(t);
+        let next = match ids.get(&key) {
+            Some(&id) => id,
+            None if terms.len() >= max_nodes => {
+                left_out = steps.len() - i;
+                break;
+            }
+            None => {
+                ids.insert(key, terms.len());
+                terms.push(t);
+                terms.len() - 1
+            }
+        };
+        writeln!(edges, "  n{} -> n{} [label=\"{}\"];", last, next, escape(rule)).unwrap();
+        last = next;
+    }
+
+    let cycle = left_out == 0 && terms.len() <= steps.len();
+    if left_out > 0 {
+        writeln!(out, "  more [label=\"{} more steps\", shape=plaintext];", left_out).unwrap();
+        writeln!(edges, "  n{} -> more [style=dashed];", last).unwrap();
+    }
+    for (id, t) in terms.iter().enumerate() {
+        let label = escape(&(t.pretty(fixities, style) + "\n"));
+        let stop = match id == last && !cycle && left_out == 0 {
+            true => ", peripheries=2",
+            false => "",
+        };
+        writeln!(out, "  n{} [label=\"{}\"{}];", id, label, stop).unwrap();
+    }
+    out.push_str(&edges);
+    out.push_str("}\n");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::*;
+    use crate::dot::*;
+    use crate::evaluate::*;
+    use crate::io::*;
+    use crate::parser::parse;
+
+    fn terms(input: &str, close: fn(&Context, &Term) -> Term) -> (Vec<Term>, Context) {
+        let (commands, context) = parse(input).expect("parse error");
+        let terms = commands
+            .into_iter()
+            .filter_map(|command| match command {
+                Command::Eval(_, term) => Some(close(&context, &term)),
+                _ => None,
+            })
+            .collect();
+
+        (terms, context)
+    }
+
+    #[test]
+    fn test_term_tree() {
+        let (terms, _) = terms("let y = 1;\nλf x. f (x {a = y});", |context, term| {
+            hydrate_vars(&context.names(), term)
+        });
+
+        assert_eq!(
+            term_tree(&terms[0]),
+            "digraph term {
+  node [shape=plaintext];
+  n0 [label=\"λf\"];
+  n1 [label=\"λx\"];
+  n2 [label=\"@\"];
+  n3 [label=\"f (1)\"];
+  n3 -> n0 [style=dashed, constraint=false];
+  n2 -> n3;
+  n4 [label=\"@\"];
+  n5 [label=\"x (0)\"];
+  n5 -> n1 [style=dashed, constraint=false];
+  n4 -> n5;
+  n6 [label=\"{}\"];
+  n7 [label=\"y\"];
+  n6 -> n7 [label=\"a\"];
+  n4 -> n6;
+  n2 -> n4;
+  n1 -> n2;
+  n0 -> n1;
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_reduction() {
+        let (terms, context) = terms(
+            "let id = λx. x;\nif iszero 0 then id 1 else 2;\n(λx. x x) (λx. x x);",
+            close_over_globals,
+        );
+        let mut io = MemoryIo::default();
+        let mut evaluator = Evaluator::new(&mut io);
+        let steps = evaluator.trace(&context, &terms[0]).unwrap();
+        let rules: Vec<&str> = steps.iter().map(|(rule, _)| *rule).collect();
+
+        assert_eq!(rules, ["E-LetV", "E-IsZeroZero", "E-IfTrue", "E-AppAbs"]);
+        assert_eq!(
+            steps.last().unwrap().1.without_locations(),
+            Term::from_int(1, FileInfo::default())
+        );
+
+        let graph =
+            reduction(&terms[0], &steps, context.fixities(), &Style::default(), MAX_NODES);
+
+        assert!(graph.contains("  n3 -> n4 [label=\"E-AppAbs\"];\n"));
+        assert!(graph.contains("  n4 [label=\"1\\l\", peripheries=2];\n"));
+
+        let graph = reduction(&terms[0], &steps, context.fixities(), &Style::default(), 3);
+
+        assert!(graph.contains("  n1 -> n2 [label=\"E-IsZeroZero\"];\n"));
+        assert!(graph.contains("  more [label=\"2 more steps\", shape=plaintext];\n"));
+        assert!(graph.contains("  n2 -> more [style=dashed];\n"));
+        assert!(!graph.contains("n3") && !graph.contains("peripheries"));
+
+        let omega = evaluator.trace(&context, &terms[1]).unwrap();
+
+        assert_eq!(omega.len(), 1);
+        assert_eq!(
+            reduction(&terms[1], &omega, context.fixities(), &Style::default(), MAX_NODES),
+            "digraph reduction {
+  node [shape=box, fontname=\"monospace\"];
+  n0 [label=\"(λx. x x) (λx. x x)\\l\"];
+  n0 -> n0 [label=\"E-AppAbs\"];
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_dot_graph() {
+        assert_eq!("tree".parse(), Ok(DotGraph::Tree));
+        assert!("forest".parse::<DotGraph>().is_err());
+    }
+}
@@ -1,29 +1,46 @@
 use crate::context::*;
+use crate::blc::{self, BlcFormat};
+use crate::combinators::*;
 use crate::context_visitor::*;
-use crate::parser::parse;
+use crate::desugar::DesugarError;
+use crate::diagnostics::Diagnostic;
+use crate::dot::{self, DotGraph};
+use crate::io::*;
+use crate::limits::*;
+use crate::literals::Literals;
+use crate::memo::*;
+use crate::modules::Modules;
+use crate::parallel::*;
+use crate::parser::parse_module;
+use crate::pretty::Style;
+use crate::resolve::unbound;
+use crate::serialize::{self, AstFormat, Stage};
 use crate::syntax::*;
-use lalrpop_util::lexer::Token;
-use lalrpop_util::ParseError;
+use std::collections::HashSet;
 use std::fs;
+use std::mem;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum EvalError {
     NoFile(String),
-    Parse(String),
+    Parse(Vec<Diagnostic>),
     EvalError(String),
+    /// An error raised while evaluating the term at a known location.
+    Runtime(FileInfo, String),
+    Unbound(String, FileInfo),
+    LimitExceeded(Limit, usize),
 }
 
-fn is_numeric(t: &Term) -> bool {
-    let x = match t {
+pub(crate) fn is_numeric(t: &Term) -> bool {
+    match t {
         Term::Zero(_) => true,
         Term::Successor(_, box t1) => is_numeric(t1),
         _ => false,
-    };
-
-    x
+    }
 }
 
-fn is_value(t: &Term) -> bool {
+pub(crate) fn is_value(t: &Term) -> bool {
     match t {
         Term::String(_, _) => true,
         Term::True(_) => true,
@@ -31,173 +48,733 @@ fn is_value(t: &Term) -> bool {
         _ if is_numeric(t) => true,
         Term::Abstraction(_, _, _) => true,
         Term::Float(_, _) => true,
-        Term::Record(_, fields) => fields.iter().all(|(name, box term)| is_value(term)),
+        Term::Host(_, _, _) => true,
+        Term::Record(_, fields) => fields.iter().all(|(_, box term)| is_value(term)),
         _ => false,
     }
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct EvalOptions {
+    pub parallel: Option<usize>,
+    pub limits: Limits,
+    pub memo: bool,
+    pub graph: bool,
+    pub combinators: bool,
+    pub derivation: bool,
+    pub blc_input: Option<BlcFormat>,
+    pub blc_output: Option<BlcFormat>,
+    /// Directories `use` looks in after the importing file's own.
+    pub search_path: Vec<PathBuf>,
+    /// Whether to `use "std"` before the file.
+    pub prelude: bool,
+    /// What the file's integer literals mean until a `#literals` pragma.
+    pub literals: Literals,
+    /// Read the file as a program written by `--to-json` or `--to-sexp`.
+    pub ast_input: Option<AstFormat>,
+    /// Write the program out at a stage instead of printing each result.
+    pub ast_output: Option<(AstFormat, Stage)>,
+    /// Draw each evaluated term as a Graphviz graph instead.
+    pub dot: Option<DotGraph>,
+    /// The most terms a reduction graph draws, `dot::MAX_NODES` if unset.
+    pub dot_nodes: Option<usize>,
+}
+
+impl EvalOptions {
+    /// Whether results are printed as text, rather than standard output
+    /// being left to BLC, a written-out program or graphs.
+    pub fn prints_results(&self) -> bool {
+        self.blc_output.is_none() && self.ast_output.is_none() && self.dot.is_none()
+    }
+}
+
 pub fn eval(file_name: &str) -> Result<(), EvalError> {
+    eval_with_options(file_name, &EvalOptions::default())
+}
+
+pub fn eval_with_options(file_name: &str, options: &EvalOptions) -> Result<(), EvalError> {
+    let mut context = Context::default();
+    context.register_io();
+
+    eval_with_context(file_name, context, options)
+}
+
+pub fn eval_with_context(
+    file_name: &str,
+    context: Context,
+    options: &EvalOptions,
+) -> Result<(), EvalError> {
+    if let Some(format) = options.blc_input {
+        return eval_blc(file_name, format, options);
+    }
+
     let file = fs::read_to_string(file_name).map_err(|e| EvalError::NoFile(format!("{:?}", e)))?;
-    let (commands, mut context) = parse(&file).map_err(|e| EvalError::Parse(format!("{:?}", e)))?;
-    println!("{}", context);
+    let mut modules = Modules::new(&context).with_search_path(&options.search_path);
+    let mut context = context;
+    if options.prelude {
+        modules
+            .import(&FileInfo::default(), &Import::from("std"), &mut context)
+            .map_err(|DesugarError(_, e)| EvalError::EvalError(e))?;
+    }
+    context.set_literals(options.literals);
+    let (commands, context) = match options.ast_input {
+        Some(format) => load_ast(file_name, &file, format, context, &mut modules)?,
+        None => {
+            let (commands, context, errors) =
+                parse_module(file_name, &file, context, &mut modules);
+            if !errors.is_empty() {
+                return Err(EvalError::Parse(
+                    errors
+                        .iter()
+                        .map(|e| Diagnostic::from_parse_error(file_name, &file, e))
+                        .collect(),
+                ));
+            }
+
+            (commands, context)
+        }
+    };
+    let unbound = unbound(&commands, &context);
+    if !unbound.is_empty() {
+        return Err(EvalError::Parse(unbound));
+    }
+    let (mut stdout, mut stderr) = (StdIo, StderrIo);
+    let io: &mut dyn Io = match options.prints_results() {
+        true => &mut stdout,
+        false => &mut stderr,
+    };
+    let mut evaluator = Evaluator::new(io).with_limits(options.limits);
+    if options.memo {
+        evaluator = evaluator.with_memo();
+    }
+    if let Some((format, stage)) = options.ast_output {
+        let commands = at_stage(commands, stage, &context, &mut evaluator)?;
+
+        print!("{}", serialize::write_commands(&commands, format));
+        return Ok(());
+    }
+    if options.prints_results() {
+        println!("{}", context);
+    }
+
+    if let Some(jobs) = options.parallel {
+        let terms: Vec<Term> = commands
+            .into_iter()
+            .filter_map(|command| match command {
+                Command::Eval(info, term) => match reads_input(&context, &term) {
+                    Some(name) => Some(Err(EvalError::Runtime(
+                        info,
+                        format!("{} reads input, which commands do not get with --parallel", name),
+                    ))),
+                    None => Some(Ok(term)),
+                },
+                _ => None,
+            })
+            .collect::<Result<_, _>>()?;
+
+        for CommandResult {
+            term,
+            value,
+            output,
+            elapsed,
+        } in evaluator.evaluate_parallel(&context, &terms, jobs)
+        {
+            let fixities = context.fixities();
+
+            for line in &output {
+                evaluator.io.write_line(line);
+            }
+
+            println!(
+                "{}\n|\t-> {} ({:?})",
+                term.with_fixities(fixities),
+                value?.with_fixities(fixities),
+                elapsed
+            );
+        }
+
+        return Ok(());
+    }
+
     for command in commands {
         match command {
-            Command::Import(s) => {
-                println!("Module {}. Skipping...", s);
+            Command::Import(..) => {}
+            Command::Bind(..) | Command::Fixity(..) | Command::Pragma(..) => {}
+            Command::Eval(_, term) if options.blc_output.is_some() => {
+                write_blc(&close_over_globals(&context, &term), options.blc_output.unwrap())?;
+            }
+            Command::Eval(_, term) if options.dot == Some(DotGraph::Tree) => {
+                print!("{}", dot::term_tree(&hydrate_vars(&context.names(), &term)));
+            }
+            Command::Eval(_, term) if options.dot == Some(DotGraph::Reduction) => {
+                let term = close_over_globals(&context, &term);
+                let steps = evaluator.trace(&context, &term)?;
+                let style = Style::default();
+                let max_nodes = options.dot_nodes.unwrap_or(dot::MAX_NODES);
+
+                print!("{}", dot::reduction(&term, &steps, context.fixities(), &style, max_nodes));
+            }
+            Command::Eval(_, term) if options.derivation => {
+                let derivation = evaluator.derive(&context, &close_over_globals(&context, &term))?;
+
+                println!("{}\n|\t-> {}\n{}", term, derivation.value, derivation);
+            }
+            Command::Eval(_, term) if options.combinators => {
+                let (comb, value, stats) = evaluate_combinators(&context, &term, evaluator.io)?;
+
+                println!("{}\n|\t=> {}\n|\t-> {}", term, comb, value);
+                println!(
+                    "|\tcombinators: {} reductions, {} allocations",
+                    stats.reductions, stats.allocations
+                );
+            }
+            Command::Eval(_, term) if options.graph => {
+                let comparison = evaluator.compare(&context, &term)?;
+                let (stats, graph_stats) = (comparison.substitution_stats, comparison.graph_stats);
+
+                println!("{}\n|\t-> {}", term, comparison.substitution);
+                println!(
+                    "|\tsubstitution: {} steps, {} substitutions",
+                    stats.steps, stats.substitutions
+                );
+                println!(
+                    "|\tgraph: {} steps, {} β-reductions, {} allocations, {} updates{}{}",
+                    graph_stats.steps,
+                    graph_stats.beta,
+                    graph_stats.allocations,
+                    graph_stats.updates,
+                    match alpha_key(&comparison.graph) == alpha_key(&comparison.substitution) {
+                        true => String::new(),
+                        false => format!(" (read back {})", comparison.graph),
+                    },
+                    match comparison.output_agrees {
+                        true => "",
+                        false => " (printed differently)",
+                    }
+                );
             }
-            Command::Bind(_, name, bind) => {}
             Command::Eval(_, term) => {
-                let term_with_global_context =
-                    context
-                        .into_iter()
-                        .fold(
-                            term.clone(),
-                            |t, ContextMember { name, binding }| match binding {
-                                Binding::TermBind(box bound_term) => Term::Let(
-                                    FileInfo::default(),
-                                    name.into(),
-                                    box bound_term.clone(),
-                                    box t,
-                                ),
-                                _ => t,
-                            },
-                        );
-                let term_hydrated = hydrate_vars(&mut context, &term_with_global_context);
-                let eval_term = evaluate_top(&mut context, &term_hydrated);
-
-                println!("{}\n|\t-> {}", term, eval_term);
+                let eval_term = evaluator.evaluate_command(&context, &term)?;
+                let fixities = context.fixities();
+
+                println!(
+                    "{}\n|\t-> {}",
+                    term.with_fixities(fixities),
+                    eval_term.with_fixities(fixities)
+                );
             }
         }
     }
 
+    if let Some(stats) = evaluator.memo_stats() {
+        println!(
+            "memo: {} hits, {} misses, {} entries ({:.1}% hit rate)",
+            stats.hits,
+            stats.misses,
+            stats.entries,
+            stats.hit_rate() * 100.0
+        );
+    }
+
     Ok(())
 }
 
-fn eval_inner(context: &Context, term: &Term) -> Option<Term> {
-    match term {
-        Term::Var(_, Var { name, index, .. }) => match context.get_binding(*index as usize) {
-            Some(Binding::TermBind(box t)) => Some(t),
-            _ => None,
-        },
-
-        Term::If(_, box Term::True(_), box t1, _) => Some(t1.clone()),
-        Term::If(_, box Term::False(_), _, box t2) => Some(t2.clone()),
-        Term::If(fi, box cond, box t1, box t2) => {
-            let t_prime = eval_inner(context, cond)?;
-
-            Some(Term::If(
-                fi.clone(),
-                box t_prime,
-                box t1.clone(),
-                box t2.clone(),
-            ))
+/// Evaluates a single closed term read from a BLC file.
+fn eval_blc(file_name: &str, format: BlcFormat, options: &EvalOptions) -> Result<(), EvalError> {
+    let input = fs::read(file_name).map_err(|e| EvalError::NoFile(format!("{:?}", e)))?;
+    let term =
+        blc::decode_as(&input, format).map_err(|e| {
+            EvalError::Parse(vec![Diagnostic::error(&format!("invalid BLC input: {:?}", e))])
+        })?;
+    let mut io = StdIo;
+    let value = Evaluator::new(&mut io)
+        .with_limits(options.limits)
+        .evaluate_top(&Context::default(), &term)?;
+
+    match options.blc_output {
+        Some(format) => write_blc(&value, format),
+        None => {
+            println!("{}\n|\t-> {}", term, value);
+            Ok(())
         }
+    }
+}
 
-        Term::Let(_, name, box v1, box t1) if is_value(v1) => Some(t1.substitute_top(v1)),
-        Term::Let(file_info, name, box t1, box t2) => {
-            let t_prime = eval_inner(context, t1)?;
+/// Reads a program written by `--to-json` or `--to-sexp`, binding its
+/// imports, names and fixities in `context` as parsing would have.
+fn load_ast(
+    file_name: &str,
+    input: &str,
+    format: AstFormat,
+    mut context: Context,
+    modules: &mut Modules,
+) -> Result<(Vec<Command>, Context), EvalError> {
+    let commands = serialize::read_commands(file_name, input, format).map_err(|e| {
+        EvalError::Parse(vec![Diagnostic::error(&format!("invalid program: {}", e))])
+    })?;
 
-            Some(Term::Let(
-                file_info.clone(),
-                name.clone(),
-                box t_prime,
-                box t2.clone(),
-            ))
+    for command in &commands {
+        match command {
+            Command::Import(info, import) => modules
+                .import(info, import, &mut context)
+                .map_err(|DesugarError(info, e)| EvalError::Runtime(info, e))?,
+            Command::Bind(_, name, binding) => context.append_binding(ContextMember {
+                name: name.clone(),
+                binding: binding.clone(),
+            }),
+            Command::Fixity(_, operator, fixity) => {
+                context.declare_fixity(operator, fixity.clone())
+            }
+            Command::Pragma(_, Pragma::Literals(literals)) => context.set_literals(*literals),
+            Command::Eval(..) => {}
         }
+    }
 
-        Term::Record(file_info, fields) => {
-            unimplemented!("Record type evaluation not yet implemented")
-        }
-        Term::Projection(file_info, box Term::Record(_, fields), name) => {
-            unimplemented!("Projection type evaluation not yet implemented")
-        }
-        Term::Projection(file_info, box t1, name) => {
-            unimplemented!("Projection type evaluation not yet implemented")
-        }
+    Ok((commands, context))
+}
+
+/// `commands` with their terms resolved to de Bruijn indices in `context`,
+/// or with each evaluated term replaced by its value.
+fn at_stage(
+    commands: Vec<Command>,
+    stage: Stage,
+    context: &Context,
+    evaluator: &mut Evaluator,
+) -> Result<Vec<Command>, EvalError> {
+    let names = context.names();
+
+    commands
+        .into_iter()
+        .map(|command| {
+            Ok(match (stage, command) {
+                (Stage::Resolved, Command::Eval(info, term)) => {
+                    Command::Eval(info, hydrate_vars(&names, &term))
+                }
+                (Stage::Resolved, Command::Bind(info, name, Binding::TermBind(box term))) => {
+                    Command::Bind(info, name, Binding::TermBind(box hydrate_vars(&names, &term)))
+                }
+                (Stage::Evaluated, Command::Eval(info, term)) => {
+                    Command::Eval(info, evaluator.evaluate_command(context, &term)?)
+                }
+                (_, command) => command,
+            })
+        })
+        .collect()
+}
+
+fn write_blc(term: &Term, format: BlcFormat) -> Result<(), EvalError> {
+    use std::io::Write;
+
+    let encoded =
+        blc::encode_as(term, format).map_err(|e| EvalError::EvalError(format!("{:?}", e)))?;
+    let mut stdout = std::io::stdout();
+
+    stdout
+        .write_all(&encoded)
+        .and_then(|_| match format {
+            BlcFormat::Text => stdout.write_all(b"\n"),
+            BlcFormat::Bytes => stdout.flush(),
+        })
+        .map_err(|e| EvalError::EvalError(format!("{:?}", e)))
+}
+
+pub fn eval_source(
+    io: &mut dyn Io,
+    source: &str,
+    context: Context,
+) -> Result<Vec<Term>, EvalError> {
+    Evaluator::new(io).eval_source(source, context)
+}
+
+pub fn evaluate_command(
+    io: &mut dyn Io,
+    context: &Context,
+    term: &Term,
+) -> Result<Term, EvalError> {
+    Evaluator::new(io).evaluate_command(context, term)
+}
+
+pub fn evaluate_top(io: &mut dyn Io, context: &Context, term: &Term) -> Result<Term, EvalError> {
+    Evaluator::new(io).evaluate_top(context, term)
+}
+
+pub fn hydrate_vars(context: &Context, term: &Term) -> Term {
+    term.visit_with_context(context)
+}
+
+fn check_limits(meter: &mut Meter, term: &Term) -> Result<(), EvalError> {
+    match meter.check(term) {
+        Some((limit, actual)) => Err(EvalError::LimitExceeded(limit, actual)),
+        None => Ok(()),
+    }
+}
+
+/// Wraps `term` in a `let` for every global binding it mentions and hydrates
+/// the result, giving a closed term that no longer needs `context`.
+pub fn close_over_globals(context: &Context, term: &Term) -> Term {
+    let term_with_global_context =
+        context
+            .into_iter()
+            .fold(
+                term.clone(),
+                |t, ContextMember { name, binding }| match binding {
+                    Binding::TermBind(box bound_term) if t.is_free(name) => Term::Let(
+                        FileInfo::default(),
+                        name.into(),
+                        box bound_term.clone(),
+                        box t,
+                    ),
+                    _ => t,
+                },
+            );
+
+    hydrate_vars(&context.names(), &term_with_global_context)
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct EvalStats {
+    /// Small steps taken by `evaluate_top`.
+    pub steps: usize,
+    /// β-reductions and let bindings, each of which copies a value into a body.
+    pub substitutions: usize,
+}
 
-        Term::Application(file_info, box Term::Abstraction(_, name, t12), v2) if is_value(v2) => {
-            Some(t12.substitute_top(v2))
+pub struct Evaluator<'a> {
+    pub(crate) io: &'a mut dyn Io,
+    pub(crate) limits: Limits,
+    pub(crate) memo: Option<Memo>,
+    /// How many `let`s binding globals the next term `evaluate_top` is
+    /// handed starts with, so they are not held against its limits.
+    globals: usize,
+    stats: EvalStats,
+    /// The computation rule behind the last step, such as `E-AppAbs`.
+    rule: &'static str,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(io: &'a mut dyn Io) -> Self {
+        Evaluator {
+            io,
+            limits: Limits::default(),
+            memo: None,
+            globals: 0,
+            stats: EvalStats::default(),
+            rule: "",
         }
-        Term::Application(file_info, v1, box t2) if is_value(v1) => {
-            let t2_prime = eval_inner(context, t2)?;
-
-            Some(Term::Application(
-                file_info.clone(),
-                v1.clone(),
-                box t2_prime,
-            ))
+    }
+
+    pub fn stats(&self) -> EvalStats {
+        self.stats
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn eval_source(&mut self, source: &str, context: Context) -> Result<Vec<Term>, EvalError> {
+        let mut modules = Modules::new(&context);
+        let (commands, context, errors) = parse_module("", source, context, &mut modules);
+        if let Some(e) = errors.first() {
+            return Err(EvalError::Parse(vec![Diagnostic::from_parse_error("", source, e)]));
         }
-        Term::Application(file_info, t1, t2) => {
-            let t1_prime = eval_inner(context, t1)?;
-
-            Some(Term::Application(
-                file_info.clone(),
-                box t1_prime,
-                t2.clone(),
-            ))
+        let unbound = unbound(&commands, &context);
+        if !unbound.is_empty() {
+            return Err(EvalError::Parse(unbound));
         }
 
-        Term::Successor(file_info, box t1) => {
-            let t1_prime = eval_inner(context, t1)?;
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::Eval(_, term) => Some(self.evaluate_command(&context, term)),
+                _ => None,
+            })
+            .collect()
+    }
 
-            Some(Term::Successor(file_info.clone(), box t1_prime))
+    pub fn evaluate_command(&mut self, context: &Context, term: &Term) -> Result<Term, EvalError> {
+        if let Some((name, info)) = term
+            .free_vars()
+            .into_iter()
+            .find(|(name, _)| !context.is_name_bound(name))
+        {
+            return Err(EvalError::Unbound(name, info));
         }
 
-        Term::Predecessor(_, box Term::Zero(_)) => Some(Term::Zero(FileInfo::default())),
-        Term::Predecessor(_, box Term::Successor(_, box nv_next)) if is_numeric(nv_next) => {
-            Some(nv_next.clone())
-        }
-        Term::Predecessor(file_info, t1) => {
-            let t1_prime = eval_inner(context, t1)?;
+        let term_hydrated = close_over_globals(context, term);
+        self.globals = leading_lets(&term_hydrated) - leading_lets(term);
 
-            Some(Term::Predecessor(file_info.clone(), box t1_prime))
+        let result = match self.memo.is_some() && is_memoizable(&term_hydrated) {
+            true => self.evaluate_memoized(context, &term_hydrated),
+            false => self.evaluate_top(context, &term_hydrated),
+        };
+        self.globals = 0;
+
+        result
+    }
+
+    pub fn evaluate_top(&mut self, context: &Context, term: &Term) -> Result<Term, EvalError> {
+        let mut term = term.clone();
+        let mut meter = Meter::new(self.limits, &term, mem::take(&mut self.globals));
+        check_limits(&mut meter, &term)?;
+
+        while let Some(t_prime) = self.eval_inner(context, &term)? {
+            self.stats.steps += 1;
+            check_limits(&mut meter, &t_prime)?;
+            term = t_prime;
         }
 
-        Term::IsZero(_, box Term::Zero(_)) => Some(Term::True(FileInfo::default())),
-        Term::IsZero(_, box Term::Successor(_, nv)) if is_numeric(nv) => {
-            Some(Term::False(FileInfo::default()))
+        Ok(term)
+    }
+
+    /// Every step `evaluate_top` would take from `term`, without
+    /// memoization, with the rule that reduced the redex in each. Stops early
+    /// if a term comes round again, as evaluation would then go round forever.
+    pub fn trace(
+        &mut self,
+        context: &Context,
+        term: &Term,
+    ) -> Result<Vec<(&'static str, Term)>, EvalError> {
+        let memo = self.memo.take();
+        let mut meter = Meter::new(self.limits, term, mem::take(&mut self.globals));
+        let mut current = term.clone();
+        let mut seen = HashSet::from([alpha_key(term)]);
+        let mut steps = vec![];
+
+        let result = check_limits(&mut meter, term).and_then(|_| loop {
+            match self.eval_inner(context, &current)? {
+                None => break Ok(()),
+                Some(t_prime) => {
+                    self.stats.steps += 1;
+                    check_limits(&mut meter, &t_prime)?;
+                    steps.push((self.rule, t_prime.clone()));
+                    if !seen.insert(alpha_key(&t_prime)) {
+                        break Ok(());
+                    }
+                    current = t_prime;
+                }
+            }
+        });
+        self.memo = memo;
+
+        result.map(|_| steps)
+    }
+
+    /// Steps a subterm in evaluation position. With memoization on, a closed
+    /// subterm is instead evaluated all the way to its (possibly cached)
+    /// normal form, which is what stepping it repeatedly would produce.
+    fn eval_subterm(&mut self, context: &Context, term: &Term) -> Result<Option<Term>, EvalError> {
+        if self.memo.is_none() || !is_memoizable(term) {
+            return self.eval_inner(context, term);
         }
-        Term::IsZero(file_info, t) => {
-            let t_prime = eval_inner(context, t)?;
 
-            Some(Term::IsZero(file_info.clone(), box t_prime.clone()))
+        let value = self.evaluate_memoized(context, term)?;
+
+        match alpha_key(&value) == alpha_key(term) {
+            true => Ok(None),
+            false => Ok(Some(value)),
         }
-        _ => None,
     }
-}
 
-fn hydrate_vars(context: &Context, term: &Term) -> Term {
-    term.visit_with_context(context)
-}
+    /// Steps records, projections and pattern lets (TAPL §11.8). Kept apart
+    /// from `eval_inner` so that its stack frame stays small, as evaluation
+    /// recurses into subterms.
+    #[inline(never)]
+    fn eval_data(&mut self, context: &Context, term: &Term) -> Result<Option<Term>, EvalError> {
+        match term {
+            Term::LetPattern(file_info, pattern, box v1, box t2) if is_value(v1) => {
+                let values = pattern
+                    .matches(v1)
+                    .map_err(|e| EvalError::Runtime(file_info.clone(), e))?;
+                self.stats.substitutions += values.len();
+                self.rule = "E-LetPatternV";
 
-fn evaluate_top(context: &Context, term: &Term) -> Term {
-    if let Some(t_prime) = eval_inner(context, &term) {
-        return evaluate_top(context, &t_prime);
+                Ok(Some(values.iter().enumerate().rev().fold(t2.clone(), |t, (i, v)| {
+                    t.substitute_top(&v.shift(i as i32))
+                })))
+            }
+            Term::LetPattern(file_info, pattern, box t1, box t2) => {
+                Ok(self.eval_subterm(context, t1)?.map(|t_prime| {
+                    Term::LetPattern(
+                        file_info.clone(),
+                        pattern.clone(),
+                        box t_prime,
+                        box t2.clone(),
+                    )
+                }))
+            }
+
+            Term::Record(file_info, fields) => {
+                match fields.iter().position(|(_, box field)| !is_value(field)) {
+                    Some(i) => Ok(self.eval_subterm(context, &fields[i].1)?.map(|field_prime| {
+                        let mut fields = fields.clone();
+                        *fields[i].1 = field_prime;
+                        Term::Record(file_info.clone(), fields)
+                    })),
+                    None => Ok(None),
+                }
+            }
+            Term::Projection(file_info, box record @ Term::Record(_, fields), label)
+                if is_value(record) =>
+            {
+                self.rule = "E-ProjRcd";
+                match fields.iter().find(|(field, _)| field == label) {
+                    Some((_, box value)) => Ok(Some(value.clone())),
+                    None => Err(EvalError::Runtime(
+                        file_info.clone(),
+                        format!("no label `{}` in {}", label, record),
+                    )),
+                }
+            }
+            Term::Projection(file_info, box t1, label) => Ok(self
+                .eval_subterm(context, t1)?
+                .map(|t1_prime| Term::Projection(file_info.clone(), box t1_prime, label.clone()))),
+            _ => unreachable!(),
+        }
     }
 
-    term.clone()
-}
+    fn eval_inner(&mut self, context: &Context, term: &Term) -> Result<Option<Term>, EvalError> {
+        match term {
+            Term::Var(_, Var { index, .. }) => match context.get_binding(*index as usize) {
+                Some(Binding::TermBind(box t)) => {
+                    self.rule = "E-Var";
+                    Ok(Some(t))
+                }
+                _ => Ok(None),
+            },
+
+            Term::If(_, box Term::True(_), box t1, _) => {
+                self.rule = "E-IfTrue";
+                Ok(Some(t1.clone()))
+            }
+            Term::If(_, box Term::False(_), _, box t2) => {
+                self.rule = "E-IfFalse";
+                Ok(Some(t2.clone()))
+            }
+            Term::If(fi, box cond, box t1, box t2) => {
+                Ok(self.eval_subterm(context, cond)?.map(|t_prime| {
+                    Term::If(
+                        fi.clone(),
+                        box t_prime,
+                        box t1.clone(),
+                        box t2.clone(),
+                    )
+                }))
+            }
+
+            Term::Let(_, name, box v1, box t1) if is_value(v1) => {
+                self.stats.substitutions += 1;
+                self.rule = "E-LetV";
+                Ok(Some(t1.substitute_top(v1)))
+            }
+            Term::Let(file_info, name, box t1, box t2) => {
+                Ok(self.eval_subterm(context, t1)?.map(|t_prime| {
+                    Term::Let(
+                        file_info.clone(),
+                        name.clone(),
+                        box t_prime,
+                        box t2.clone(),
+                    )
+                }))
+            }
+
+            Term::LetPattern(..) | Term::Record(..) | Term::Projection(..) => {
+                self.eval_data(context, term)
+            }
+
+            Term::Application(file_info, box Term::Abstraction(_, name, t12), v2)
+                if is_value(v2) =>
+            {
+                self.stats.substitutions += 1;
+                self.rule = "E-AppAbs";
+                Ok(Some(t12.substitute_top(v2)))
+            }
+            Term::Application(file_info, box Term::Host(host_info, host, args), box v2)
+                if is_value(v2) =>
+            {
+                let args: Vec<Term> = args.iter().cloned().chain([v2.clone()]).collect();
+                self.rule = "E-AppHost";
+
+                match args.len() == host.arity {
+                    true => host
+                        .call(self.io, &args)
+                        .map(Some)
+                        .map_err(|e| {
+                            EvalError::Runtime(file_info.clone(), format!("{}: {}", host.name, e))
+                        }),
+                    false => Ok(Some(Term::Host(host_info.clone(), host.clone(), args))),
+                }
+            }
+            Term::Application(file_info, v1, box t2) if is_value(v1) => {
+                Ok(self.eval_subterm(context, t2)?.map(|t2_prime| {
+                    Term::Application(
+                        file_info.clone(),
+                        v1.clone(),
+                        box t2_prime,
+                    )
+                }))
+            }
+            Term::Application(file_info, t1, t2) => {
+                Ok(self.eval_subterm(context, t1)?.map(|t1_prime| {
+                    Term::Application(
+                        file_info.clone(),
+                        box t1_prime,
+                        t2.clone(),
+                    )
+                }))
+            }
+
+            Term::Successor(file_info, box t1) => Ok(self
+                .eval_subterm(context, t1)?
+                .map(|t1_prime| Term::Successor(file_info.clone(), box t1_prime))),
+
+            Term::Predecessor(_, box Term::Zero(_)) => {
+                self.rule = "E-PredZero";
+                Ok(Some(Term::Zero(FileInfo::default())))
+            }
+            Term::Predecessor(_, box Term::Successor(_, box nv_next)) if is_numeric(nv_next) => {
+                self.rule = "E-PredSucc";
+                Ok(Some(nv_next.clone()))
+            }
+            Term::Predecessor(file_info, t1) => Ok(self
+                .eval_subterm(context, t1)?
+                .map(|t1_prime| Term::Predecessor(file_info.clone(), box t1_prime))),
 
-fn evaluate_binding(context: &mut Context, bind: &Binding) -> Binding {
-    match bind {
-        Binding::TermBind(box term) => {
-            let term = hydrate_vars(context, &term);
-            Binding::TermBind(box term)
+            Term::IsZero(_, box Term::Zero(_)) => {
+                self.rule = "E-IsZeroZero";
+                Ok(Some(Term::True(FileInfo::default())))
+            }
+            Term::IsZero(_, box Term::Successor(_, nv)) if is_numeric(nv) => {
+                self.rule = "E-IsZeroSucc";
+                Ok(Some(Term::False(FileInfo::default())))
+            }
+            Term::IsZero(file_info, t) => Ok(self
+                .eval_subterm(context, t)?
+                .map(|t_prime| Term::IsZero(file_info.clone(), box t_prime.clone()))),
+
+            Term::TimesFloat(_, box Term::Float(_, f1), box Term::Float(_, f2)) => {
+                self.rule = "E-TimesFloatFloat";
+                Ok(Some(Term::Float(FileInfo::default(), f1 * f2)))
+            }
+            Term::TimesFloat(file_info, v1, box t2) if is_value(v1) => Ok(self
+                .eval_subterm(context, t2)?
+                .map(|t2_prime| Term::TimesFloat(file_info.clone(), v1.clone(), box t2_prime))),
+            Term::TimesFloat(file_info, box t1, t2) => Ok(self
+                .eval_subterm(context, t1)?
+                .map(|t1_prime| Term::TimesFloat(file_info.clone(), box t1_prime, t2.clone()))),
+            _ => Ok(None),
         }
-        _ => bind.clone(),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::context::*;
     use crate::evaluate::*;
     use crate::parser::*;
-    use crate::syntax::*;
 
     #[test]
     fn test_base_conditional() {
-        let mut context = Context::default();
+        let context = Context::default();
         let if_true = Term::If(
             FileInfo::default(),
             box Term::True(FileInfo::default()),
@@ -206,24 +783,24 @@ mod tests {
         );
 
         assert_eq!(
-            evaluate_top(&mut context, &if_true),
+            evaluate_top(&mut MemoryIo::default(), &context, &if_true).unwrap(),
             Term::Float(FileInfo::default(), 1.0)
         )
     }
 
     #[test]
     fn test_let() {
-        let (parsed, mut context) = parse("let x = 1 in +x x;").expect("Parse error");
+        let (parsed, context) = parse("let x = 1 in +x x;").expect("Parse error");
 
         if let Command::Eval(_, term) = &parsed[0] {
-            let evaluated = evaluate_top(&mut context, term);
+            let evaluated = evaluate_top(&mut MemoryIo::default(), &context, term).unwrap();
             let expectation = Term::Application(
                 FileInfo::default(),
                 box Term::from_int(2, FileInfo::default()),
                 box Term::from_int(1, FileInfo::default()),
             );
 
-            assert_eq!(evaluated, expectation);
+            assert_eq!(evaluated.without_locations(), expectation);
         } else {
             panic!()
         }
@@ -231,19 +808,19 @@ mod tests {
 
     #[test]
     fn test_let_free() {
-        let (parsed, mut context) =
+        let (parsed, context) =
             parse("let y = true in let x = 1 in y x;").expect("Parse error");
 
         if let Command::Eval(_, term) = &parsed[0] {
-            let term = hydrate_vars(&mut context, term);
-            let evaluated = evaluate_top(&mut context, &term);
+            let term = hydrate_vars(&context, term);
+            let evaluated = evaluate_top(&mut MemoryIo::default(), &context, &term).unwrap();
             let expectation = Term::Application(
                 FileInfo::default(),
                 box Term::True(FileInfo::default()),
                 box Term::from_int(1, FileInfo::default()),
             );
 
-            assert_eq!(evaluated, expectation);
+            assert_eq!(evaluated.without_locations(), expectation);
         } else {
             panic!()
         }
@@ -251,18 +828,18 @@ mod tests {
 
     #[test]
     fn test_let_global() {
-        let (parsed, mut context) = parse("let y = true; let x = 1 in y x;").expect("Parse error");
+        let (parsed, context) = parse("let y = true; let x = 1 in y x;").expect("Parse error");
 
         if let Command::Eval(_, term) = &parsed[1] {
-            let term = hydrate_vars(&mut context, term);
-            let evaluated = evaluate_top(&mut context, &term);
+            let term = hydrate_vars(&context, term);
+            let evaluated = evaluate_top(&mut MemoryIo::default(), &context, &term).unwrap();
             let expectation = Term::Application(
                 FileInfo::default(),
                 box Term::True(FileInfo::default()),
                 box Term::from_int(1, FileInfo::default()),
             );
 
-            assert_eq!(evaluated, expectation);
+            assert_eq!(evaluated.without_locations(), expectation);
         } else {
             panic!()
         }
@@ -270,14 +847,14 @@ mod tests {
 
     #[test]
     fn test_lambda_evall() {
-        let (parsed, mut context) = parse("(lambda x. x) 1;").expect("Parse error");
+        let (parsed, context) = parse("(lambda x. x) 1;").expect("Parse error");
 
         if let Command::Eval(_, term) = &parsed[0] {
-            let term = hydrate_vars(&mut context, term);
-            let evaluated = evaluate_top(&mut context, &term);
+            let term = hydrate_vars(&context, term);
+            let evaluated = evaluate_top(&mut MemoryIo::default(), &context, &term).unwrap();
             let expectation = Term::from_int(1, FileInfo::default());
 
-            assert_eq!(evaluated, expectation);
+            assert_eq!(evaluated.without_locations(), expectation);
         } else {
             panic!()
         }
@@ -292,14 +869,14 @@ mod tests {
         realbool fls; 
         "#;
 
-        let (parsed, mut context) = parse(input).expect("Parse error");
+        let (parsed, context) = parse(input).expect("Parse error");
 
         if let Command::Eval(_, term) = &parsed[0] {
-            let term = hydrate_vars(&mut context, term);
-            let evaluated = evaluate_top(&mut context, &term);
+            let term = hydrate_vars(&context, term);
+            let evaluated = evaluate_top(&mut MemoryIo::default(), &context, &term).unwrap();
             let expectation = Term::False(FileInfo::default());
 
-            assert_eq!(evaluated, expectation);
+            assert_eq!(evaluated.without_locations(), expectation);
         } else {
             panic!()
         }
@@ -314,14 +891,14 @@ mod tests {
         realnat (scc czero); 
         "#;
 
-        let (parsed, mut context) = parse(input).expect("Parse error");
+        let (parsed, context) = parse(input).expect("Parse error");
 
         if let Command::Eval(_, term) = &parsed[0] {
-            let term = hydrate_vars(&mut context, term);
-            let evaluated = evaluate_top(&mut context, &term);
+            let term = hydrate_vars(&context, term);
+            let evaluated = evaluate_top(&mut MemoryIo::default(), &context, &term).unwrap();
             let expectation = Term::from_int(1, FileInfo::default());
 
-            assert_eq!(evaluated, expectation);
+            assert_eq!(evaluated.without_locations(), expectation);
         } else {
             panic!()
         }
@@ -339,14 +916,14 @@ mod tests {
         realnat (churchnat 10);
         "#;
 
-        let (parsed, mut context) = parse(input).expect("Parse error");
+        let (parsed, context) = parse(input).expect("Parse error");
 
         if let Command::Eval(_, term) = &parsed[0] {
-            let term = hydrate_vars(&mut context, term);
-            let evaluated = evaluate_top(&mut context, &term);
+            let term = hydrate_vars(&context, term);
+            let evaluated = evaluate_top(&mut MemoryIo::default(), &context, &term).unwrap();
             let expectation = Term::from_int(10, FileInfo::default());
 
-            assert_eq!(evaluated, expectation);
+            assert_eq!(evaluated.without_locations(), expectation);
         } else {
             panic!()
         }
@@ -381,16 +958,77 @@ mod tests {
         realnat (factorial (churchnat 4));
         "#;
 
-        let (parsed, mut context) = parse(input).expect("Parse error");
+        let (parsed, context) = parse(input).expect("Parse error");
 
         if let Command::Eval(_, term) = &parsed[0] {
-            let term = hydrate_vars(&mut context, term);
-            let evaluated = evaluate_top(&mut context, &term);
+            let term = hydrate_vars(&context, term);
+            let evaluated = evaluate_top(&mut MemoryIo::default(), &context, &term).unwrap();
             let expectation = Term::from_int(24, FileInfo::default());
 
-            assert_eq!(evaluated, expectation);
+            assert_eq!(evaluated.without_locations(), expectation);
         } else {
             panic!()
         }
     }
+
+    #[test]
+    fn test_records() {
+        let values = Evaluator::new(&mut crate::io::MemoryIo::default())
+            .eval_source(
+                "let r = { a = + 0, b = { c = iszero 0 } }; r.b.c; (λx. { y = x }) 2; r.a;",
+                Context::default(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            values.without_locations(),
+            [
+                Term::True(FileInfo::default()),
+                Term::Record(
+                    FileInfo::default(),
+                    vec![("y".into(), box Term::from_int(2, FileInfo::default()))]
+                ),
+                Term::from_int(1, FileInfo::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_patterns() {
+        let input = r#"
+        let pair = { fst = 1, snd = { x = true, y = 3 } };
+        let {fst = a, snd = {y = b}} = pair in if iszero a then 0 else b;
+        let k = 7 in let {fst = a, snd = b} = {fst = k, snd = 2} in λz. a;
+        (λq. let {fst = a, snd = b} = q in b) pair.snd.x;
+        "#;
+        let values = Evaluator::new(&mut crate::io::MemoryIo::default())
+            .eval_source(input, Context::default());
+
+        assert!(matches!(values, Err(EvalError::Runtime(_, ref e)) if e == "expected a record, found true"));
+
+        let input = input.lines().take(4).collect::<Vec<&str>>().join("\n");
+        let values = Evaluator::new(&mut crate::io::MemoryIo::default())
+            .eval_source(&input, Context::default())
+            .unwrap();
+
+        assert_eq!(values[0].without_locations(), Term::from_int(3, FileInfo::default()));
+        assert_eq!(format!("{}", values[1]), "λz. 7");
+    }
+
+    #[test]
+    fn test_missing_label() {
+        for input in ["{ a = 1 }.b;", "let {b = x} = { a = 1 } in x;"] {
+            let error = Evaluator::new(&mut crate::io::MemoryIo::default())
+                .eval_source(input, Context::default())
+                .unwrap_err();
+
+            match error {
+                EvalError::Runtime(info, message) => {
+                    assert_eq!(message, "no label `b` in { a = 1 }");
+                    assert_eq!(info.column(), 1);
+                }
+                _ => panic!(),
+            }
+        }
+    }
 }
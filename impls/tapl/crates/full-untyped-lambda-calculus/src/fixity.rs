@@ -0,0 +1,238 @@
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::fmt;
+
+/// The highest precedence a declaration may give, binding tightest.
+pub const MAX_PRECEDENCE: u32 = 9;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Assoc {
+    Left,
+    Right,
+    None,
+}
+
+/// How an infix operator groups, and the function it stands for.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Fixity {
+    pub assoc: Assoc,
+    #[serde(deserialize_with = "bounded_precedence")]
+    pub precedence: u32,
+    pub function: String,
+}
+
+impl Fixity {
+    /// Operators that were never declared group like Haskell's: to the
+    /// left, binding tighter than anything declared below 9.
+    pub fn undeclared(operator: &str) -> Self {
+        Fixity {
+            assoc: Assoc::Left,
+            precedence: MAX_PRECEDENCE,
+            function: operator.into(),
+        }
+    }
+}
+
+/// Reads a precedence, which a program written out elsewhere could still
+/// have set past `MAX_PRECEDENCE`.
+fn bounded_precedence<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    match u32::deserialize(deserializer)? {
+        precedence if precedence <= MAX_PRECEDENCE => Ok(precedence),
+        precedence => Err(de::Error::custom(format!(
+            "precedence {} is not between 0 and {}",
+            precedence, MAX_PRECEDENCE
+        ))),
+    }
+}
+
+/// Operator declarations in scope, newest first.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Fixities(Vec<(String, Fixity)>);
+
+impl Fixities {
+    pub fn declare(&mut self, operator: &str, fixity: Fixity) {
+        self.0.insert(0, (operator.into(), fixity))
+    }
+
+    pub fn get(&self, operator: &str) -> Option<&Fixity> {
+        self.0
+            .iter()
+            .find(|(declared, _)| declared == operator)
+            .map(|(_, fixity)| fixity)
+    }
+
+    pub fn resolve(&self, operator: &str) -> Fixity {
+        self.get(operator)
+            .cloned()
+            .unwrap_or_else(|| Fixity::undeclared(operator))
+    }
+
+    /// The operator that currently stands for `function`, if any. An
+    /// operator redeclared for another function no longer counts.
+    pub fn operator_for(&self, function: &str) -> Option<(&str, &Fixity)> {
+        self.0
+            .iter()
+            .filter(|(operator, fixity)| {
+                fixity.function == function && self.get(operator) == Some(fixity)
+            })
+            .map(|(operator, fixity)| (operator.as_str(), fixity))
+            .next()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Every declaration, newest first.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Fixity)> {
+        self.0.iter().map(|(operator, fixity)| (operator.as_str(), fixity))
+    }
+}
+
+impl fmt::Display for Assoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Assoc::Left => write!(f, "infixl"),
+            Assoc::Right => write!(f, "infixr"),
+            Assoc::None => write!(f, "infix"),
+        }
+    }
+}
+
+/// Groups `first op1 e1 op2 e2 ...` by precedence and associativity, calling
+/// `apply` to combine each operator with its operands. Fails with the
+/// offending operator when two operators of the same precedence cannot be
+/// grouped, such as chained non-associative ones.
+pub fn reassociate<T, O, F>(
+    first: T,
+    rest: Vec<(O, T)>,
+    fixity: impl Fn(&O) -> Fixity,
+    mut apply: F,
+) -> Result<T, (O, String)>
+where
+    F: FnMut(O, T, T) -> T,
+{
+    fn reduce<T, O>(operands: &mut Vec<T>, operators: &mut Vec<O>, apply: &mut impl FnMut(O, T, T) -> T) {
+        let rhs = operands.pop().unwrap();
+        let lhs = operands.pop().unwrap();
+        let operator = operators.pop().unwrap();
+
+        operands.push(apply(operator, lhs, rhs));
+    }
+
+    let mut operands = vec![first];
+    let mut operators: Vec<O> = vec![];
+
+    for (operator, operand) in rest {
+        let next = fixity(&operator);
+
+        while let Some(top) = operators.last().map(&fixity) {
+            match (top.precedence.cmp(&next.precedence), top.assoc, next.assoc) {
+                (std::cmp::Ordering::Greater, _, _) => {}
+                (std::cmp::Ordering::Equal, Assoc::Left, Assoc::Left) => {}
+                (std::cmp::Ordering::Equal, Assoc::Right, Assoc::Right) => break,
+                (std::cmp::Ordering::Equal, _, _) => {
+                    let message = format!(
+                        "cannot mix `{} {}` and `{} {}` operators without parentheses",
+                        top.assoc, top.precedence, next.assoc, next.precedence
+                    );
+                    return Err((operator, message));
+                }
+                (std::cmp::Ordering::Less, _, _) => break,
+            }
+            reduce(&mut operands, &mut operators, &mut apply);
+        }
+
+        operators.push(operator);
+        operands.push(operand);
+    }
+
+    while !operators.is_empty() {
+        reduce(&mut operands, &mut operators, &mut apply);
+    }
+
+    Ok(operands.pop().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixity::*;
+
+    fn fixities() -> Fixities {
+        let mut fixities = Fixities::default();
+        for (operator, assoc, precedence) in [
+            ("+", Assoc::Left, 6),
+            ("*", Assoc::Left, 7),
+            ("^", Assoc::Right, 8),
+            ("==", Assoc::None, 4),
+        ] {
+            fixities.declare(
+                operator,
+                Fixity {
+                    assoc,
+                    precedence,
+                    function: operator.into(),
+                },
+            );
+        }
+        fixities
+    }
+
+    fn group(input: &str) -> Result<String, String> {
+        let fixities = fixities();
+        let mut words = input.split_whitespace().map(String::from);
+        let first = words.next().unwrap();
+        let words: Vec<String> = words.collect();
+        let rest = words
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+
+        reassociate(
+            first,
+            rest,
+            |operator| fixities.resolve(operator),
+            |operator, lhs, rhs| format!("({} {} {})", lhs, operator, rhs),
+        )
+        .map_err(|(operator, _)| operator)
+    }
+
+    #[test]
+    fn test_reassociate() {
+        assert_eq!(group("a + b * c + d").unwrap(), "((a + (b * c)) + d)");
+        assert_eq!(group("a ^ b ^ c * d").unwrap(), "((a ^ (b ^ c)) * d)");
+        assert_eq!(group("a + b == c").unwrap(), "((a + b) == c)");
+        assert_eq!(group("a <> b <> c").unwrap(), "((a <> b) <> c)");
+        assert_eq!(group("a == b == c"), Err(String::from("==")));
+    }
+
+    #[test]
+    fn test_operator_for() {
+        let mut fixities = fixities();
+
+        assert_eq!(fixities.operator_for("*").map(|(op, _)| op), Some("*"));
+        fixities.declare(
+            "*",
+            Fixity {
+                assoc: Assoc::Left,
+                precedence: 7,
+                function: String::from("times"),
+            },
+        );
+        assert_eq!(fixities.operator_for("*"), None);
+        assert_eq!(fixities.operator_for("times").map(|(op, _)| op), Some("*"));
+    }
+
+    #[test]
+    fn test_precedence_bounds() {
+        let json = |precedence| {
+            format!(r#"{{"assoc": "Left", "precedence": {}, "function": "f"}}"#, precedence)
+        };
+
+        assert_eq!(serde_json::from_str::<Fixity>(&json(9)).unwrap().precedence, 9);
+        assert!(serde_json::from_str::<Fixity>(&json(10)).is_err());
+    }
+}
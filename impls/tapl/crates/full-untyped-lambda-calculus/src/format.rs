@@ -0,0 +1,184 @@
+use crate::diagnostics::Diagnostic;
+use crate::parser::comments::*;
+use crate::parser::parse_statements;
+use crate::pretty::Style;
+use crate::surface::Statement;
+use crate::syntax::WithoutLocations;
+
+/// A statement or comment to lay out, and the source it came from.
+struct Item {
+    text: String,
+    kind: Option<CommentKind>,
+    /// Where the item is taken to start when measuring the gap before it,
+    /// and where it ends.
+    span: (usize, usize),
+}
+
+/// Lays `source` out canonically: every statement pretty-printed in `style`,
+/// one blank line kept wherever statements were separated by any, and every
+/// comment kept. A comment on the line after a statement stays there; one
+/// inside a statement moves to the line before it. Fails rather than return
+/// a layout that would parse to different statements.
+pub fn format_source(
+    filename: &str,
+    source: &str,
+    style: &Style,
+) -> Result<String, Vec<Diagnostic>> {
+    let (statements, errors) = parse_statements(filename, source);
+    if !errors.is_empty() {
+        return Err(errors
+            .iter()
+            .map(|error| Diagnostic::from_parse_error(filename, source, error))
+            .collect());
+    }
+
+    let (blanked, comments, _) = strip_comments(source);
+    let mut comments = comments.into_iter().peekable();
+    let mut items = vec![];
+    for statement in &statements {
+        let (start, end) = statement.info().span();
+        let end = blanked[end..].find(';').map_or(end, |semicolon| end + semicolon + 1);
+
+        while let Some(comment) = comments.next_if(|comment| comment.span.0 < end) {
+            items.push(Item {
+                text: comment.text.trim_end().into(),
+                kind: Some(comment.kind),
+                span: (comment.span.0.min(start), comment.span.1.min(start)),
+            });
+        }
+        items.push(Item {
+            text: statement.pretty(style),
+            kind: None,
+            span: (start, end),
+        });
+    }
+    items.extend(comments.map(|comment| Item {
+        text: comment.text.trim_end().into(),
+        kind: Some(comment.kind),
+        span: comment.span,
+    }));
+
+    let mut out = String::new();
+    for (i, item) in items.iter().enumerate() {
+        if let Some(previous) = i.checked_sub(1).map(|i| &items[i]) {
+            let gap = source.get(previous.span.1..item.span.0).unwrap_or_default();
+            out.push_str(match gap.matches('\n').count() {
+                0 if item.kind.is_some() && previous.kind != Some(CommentKind::Line) => " ",
+                0 | 1 => "\n",
+                _ => "\n\n",
+            });
+        }
+        out.push_str(&item.text);
+    }
+    out.push('\n');
+
+    reparses_as(filename, &statements, &out).map(|_| out).map_err(|diagnostic| vec![diagnostic])
+}
+
+/// Checks that `formatted` parses to `statements` again, so that a printer
+/// bug cannot rewrite a file into a different program.
+fn reparses_as(
+    filename: &str,
+    statements: &[Statement],
+    formatted: &str,
+) -> Result<(), Diagnostic> {
+    let (reformatted, errors) = parse_statements(filename, formatted);
+    let changed = statements.iter().zip(&reformatted).find(|(statement, again)| {
+        statement.without_locations() != again.without_locations()
+    });
+    let diagnostic = Diagnostic::error("formatting would change what this file means")
+        .note("the file is left as it is");
+
+    match changed {
+        Some((statement, _)) => Err(diagnostic.at(statement.info().clone())),
+        None if !errors.is_empty() || reformatted.len() != statements.len() => Err(diagnostic),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::*;
+    use std::fs;
+
+    fn format(source: &str) -> String {
+        format_source("", source, &Style::default()).expect("format error")
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format("λb.b ;λt. λf. t ;"), "λb. b;\nλt. λf. t;\n");
+        assert_eq!(
+            format("-- id\nlet id x=x;  // trailing\n\n\n\n(+ 1) * x where x = 2 ;"),
+            "-- id\nlet id x = x; // trailing\n\n+ 1 * x where x = 2;\n"
+        );
+        assert_eq!(format("f /* inside */ x;\n/* end */"), "/* inside */\nf x;\n/* end */\n");
+        assert!(format_source("", "λx. ;", &Style::default()).is_err());
+        assert_eq!(
+            format("99999999999999 ;100000000000000000000.0;0.0000001 ; f 1.50 (-2.5);"),
+            "99999999999999;\n100000000000000000000.0;\n0.0000001;\nf 1.50 (-2.5);\n"
+        );
+
+        let nested = "let f = (x where x = 1), g y = {a = λx. x, b = y.a.b} in
+            let {l = y} = r in if iszero y then - (- y) else f (f (+ y));";
+
+        assert_eq!(
+            format(nested),
+            "let f = (x where x = 1), g y = { a = λx. x, b = y.a.b } in
+let { l = y } = r in
+if iszero y then - (- y) else f (f (+ y));\n"
+        );
+        assert_eq!(format(&format(nested)), format(nested));
+    }
+
+    #[test]
+    fn test_format_checks_reparse() {
+        let (statements, _) = parse_statements("", "f 1;\nlet x = 2;");
+
+        assert_eq!(reparses_as("", &statements, "f 1;\n\nlet x = 2;\n"), Ok(()));
+        for formatted in ["f 1;\nlet x = 3;\n", "f 1;\n", "f 1;\nlet x = ;\n"] {
+            let diagnostic = reparses_as("", &statements, formatted).unwrap_err();
+
+            assert_eq!(diagnostic.message, "formatting would change what this file means");
+        }
+        let diagnostic = reparses_as("", &statements, "f 1;\nlet x = 3;\n").unwrap_err();
+        assert_eq!(diagnostic.location.map(|info| info.line()), Some(2));
+    }
+
+    #[test]
+    fn test_format_lambda_files() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/lambda-files");
+        let mut sources = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+
+                (path.display().to_string(), fs::read_to_string(&path).unwrap())
+            })
+            .collect::<Vec<(String, String)>>();
+        // Literals the printer once rounded or rewrote, which only parse.
+        sources.push((
+            "literals".into(),
+            "99999999999999; 100000000000000000000.0; 0.0000001;\n\
+             timesfloat (-2.50) (λx. x) 007;"
+                .into(),
+        ));
+
+        for (path, source) in sources {
+            let formatted = format(&source);
+            let statements = |source: &str| {
+                let (statements, errors) = parse_statements("", source);
+                assert!(errors.is_empty(), "{}", path);
+
+                statements.without_locations()
+            };
+            let comments = |source: &str| -> Vec<String> {
+                strip_comments(source).1.into_iter().map(|comment| comment.text).collect()
+            };
+
+            assert_eq!(format(&formatted), formatted, "{} formats differently twice", path);
+            assert_eq!(statements(&formatted), statements(&source), "{}", path);
+            assert_eq!(comments(&formatted), comments(&source), "{}", path);
+        }
+    }
+}
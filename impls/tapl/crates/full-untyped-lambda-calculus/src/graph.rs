@@ -0,0 +1,605 @@
+use crate::context::*;
+use crate::evaluate::*;
+use crate::host::*;
+use crate::io::*;
+use crate::syntax::*;
+use std::rc::Rc;
+
+/// An experimental lazy backend.
+///
+/// A hydrated term is compiled once into a graph of `Code` nodes. Evaluation
+/// then allocates thunks for arguments and let bindings on a heap; a thunk is
+/// overwritten with its value the first time it is forced, so an argument
+/// used many times is evaluated at most once. This is call-by-need rather
+/// than optimal reduction: work under lambdas is still not shared.
+type CodeId = usize;
+type Addr = usize;
+
+#[derive(Debug, Clone)]
+enum Code {
+    Var(String, usize),
+    Lam(String, CodeId),
+    App(CodeId, CodeId),
+    Let(String, CodeId, CodeId),
+    If(CodeId, CodeId, CodeId),
+    Lit(Value),
+    Succ(CodeId),
+    Pred(CodeId),
+    IsZero(CodeId),
+    Record(Vec<(String, CodeId)>),
+    Proj(CodeId, String),
+    TimesFloat(CodeId, CodeId),
+    Host(HostFunction, Vec<CodeId>),
+}
+
+#[derive(Debug)]
+enum EnvNode {
+    Empty,
+    Bind(Addr, Env),
+}
+
+type Env = Rc<EnvNode>;
+
+fn lookup(env: &Env, idx: usize) -> Option<Addr> {
+    match (&**env, idx) {
+        (EnvNode::Bind(addr, _), 0) => Some(*addr),
+        (EnvNode::Bind(_, next), _) => lookup(next, idx - 1),
+        (EnvNode::Empty, _) => None,
+    }
+}
+
+fn extend(env: &Env, addr: Addr) -> Env {
+    Rc::new(EnvNode::Bind(addr, env.clone()))
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Closure(String, CodeId, Env),
+    Bool(bool),
+    Nat(i32),
+    Str(String),
+    Float(f32),
+    Record(Vec<(String, Addr)>),
+    Host(HostFunction, Vec<Addr>),
+}
+
+#[derive(Debug)]
+enum Cell {
+    Thunk(CodeId, Env),
+    Value(Value),
+    Blackhole,
+}
+
+enum Frame {
+    Arg(Addr),
+    Update(Addr),
+    If(CodeId, CodeId, Env),
+    Succ,
+    Pred,
+    IsZero,
+    Proj(String),
+    TimesLeft(CodeId, Env),
+    TimesRight(f32),
+}
+
+enum Control {
+    Eval(CodeId, Env),
+    Enter(Addr),
+    Return(Value),
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct GraphStats {
+    /// Closures entered with an argument.
+    pub beta: usize,
+    /// Thunks allocated for arguments, lets and record fields.
+    pub allocations: usize,
+    /// Thunks overwritten with their value.
+    pub updates: usize,
+    /// Transitions of the machine.
+    pub steps: usize,
+}
+
+pub struct Graph<'a> {
+    code: Vec<Code>,
+    heap: Vec<Cell>,
+    root: CodeId,
+    stats: GraphStats,
+    io: &'a mut dyn Io,
+}
+
+fn stuck(message: String) -> EvalError {
+    EvalError::EvalError(format!("graph reduction is stuck: {}", message))
+}
+
+impl<'a> Graph<'a> {
+    /// Translates a hydrated, closed term into a graph.
+    pub fn new(term: &Term, io: &'a mut dyn Io) -> Self {
+        let mut graph = Graph {
+            code: vec![],
+            heap: vec![],
+            root: 0,
+            stats: GraphStats::default(),
+            io,
+        };
+        graph.root = graph.compile(term);
+
+        graph
+    }
+
+    pub fn stats(&self) -> GraphStats {
+        self.stats
+    }
+
+    fn compile(&mut self, term: &Term) -> CodeId {
+        let code = match term {
+            Term::Var(_, Var { name, index, .. }) => Code::Var(name.clone(), *index as usize),
+            Term::Abstraction(_, name, box t1) => Code::Lam(name.clone(), self.compile(t1)),
+            Term::Application(_, box t1, box t2) => Code::App(self.compile(t1), self.compile(t2)),
+            Term::Let(_, name, box t1, box t2) => {
+                Code::Let(name.clone(), self.compile(t1), self.compile(t2))
+            }
+            Term::LetPattern(info, pattern, box t1, box t2) => {
+                return self.compile(&Term::lower_pattern_let(info, pattern, t1, t2))
+            }
+            Term::If(_, box t1, box t2, box t3) => {
+                Code::If(self.compile(t1), self.compile(t2), self.compile(t3))
+            }
+            Term::True(_) => Code::Lit(Value::Bool(true)),
+            Term::False(_) => Code::Lit(Value::Bool(false)),
+            Term::Zero(_) => Code::Lit(Value::Nat(0)),
+            Term::String(_, s) => Code::Lit(Value::Str(s.clone())),
+            Term::Float(_, flt) => Code::Lit(Value::Float(*flt)),
+            Term::Successor(_, box t1) => Code::Succ(self.compile(t1)),
+            Term::Predecessor(_, box t1) => Code::Pred(self.compile(t1)),
+            Term::IsZero(_, box t1) => Code::IsZero(self.compile(t1)),
+            Term::Record(_, fields) => Code::Record(
+                fields
+                    .iter()
+                    .map(|(name, box field)| (name.clone(), self.compile(field)))
+                    .collect(),
+            ),
+            Term::Projection(_, box t1, name) => Code::Proj(self.compile(t1), name.clone()),
+            Term::TimesFloat(_, box t1, box t2) => {
+                Code::TimesFloat(self.compile(t1), self.compile(t2))
+            }
+            Term::Host(_, host, args) => Code::Host(
+                host.clone(),
+                args.iter().map(|arg| self.compile(arg)).collect(),
+            ),
+        };
+
+        self.code.push(code);
+        self.code.len() - 1
+    }
+
+    fn alloc(&mut self, cell: Cell) -> Addr {
+        self.stats.allocations += 1;
+        self.heap.push(cell);
+        self.heap.len() - 1
+    }
+
+    /// Allocates a thunk for `code`, reusing the existing cell when the code
+    /// is just a variable so that sharing is preserved.
+    fn delay(&mut self, code: CodeId, env: &Env) -> Result<Addr, EvalError> {
+        match &self.code[code] {
+            Code::Var(name, idx) => {
+                lookup(env, *idx).ok_or_else(|| stuck(format!("{} is unbound", name)))
+            }
+            _ => Ok(self.alloc(Cell::Thunk(code, env.clone()))),
+        }
+    }
+
+    /// Reduces the root to weak head normal form, forcing numbers and
+    /// record fields so the result can be compared with `evaluate_top`.
+    pub fn evaluate(&mut self) -> Result<Term, EvalError> {
+        let value = self.run(Control::Eval(self.root, Rc::new(EnvNode::Empty)))?;
+
+        self.force_deep(&value)?;
+        self.read_back_value(&value, 0)
+    }
+
+    fn force_deep(&mut self, value: &Value) -> Result<(), EvalError> {
+        if let Value::Record(fields) = value {
+            for (_, addr) in fields.clone() {
+                let field = self.run(Control::Enter(addr))?;
+                self.force_deep(&field)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self, control: Control) -> Result<Value, EvalError> {
+        let mut control = control;
+        let mut stack: Vec<Frame> = vec![];
+
+        loop {
+            self.stats.steps += 1;
+            control = match control {
+                Control::Eval(code, env) => match self.code[code].clone() {
+                    Code::Var(name, idx) => Control::Enter(
+                        lookup(&env, idx).ok_or_else(|| stuck(format!("{} is unbound", name)))?,
+                    ),
+                    Code::Lam(name, body) => Control::Return(Value::Closure(name, body, env)),
+                    Code::App(t1, t2) => {
+                        stack.push(Frame::Arg(self.delay(t2, &env)?));
+                        Control::Eval(t1, env)
+                    }
+                    Code::Let(_, t1, t2) => {
+                        let addr = self.delay(t1, &env)?;
+                        Control::Eval(t2, extend(&env, addr))
+                    }
+                    Code::If(t1, t2, t3) => {
+                        stack.push(Frame::If(t2, t3, env.clone()));
+                        Control::Eval(t1, env)
+                    }
+                    Code::Lit(value) => Control::Return(value),
+                    Code::Succ(t1) => {
+                        stack.push(Frame::Succ);
+                        Control::Eval(t1, env)
+                    }
+                    Code::Pred(t1) => {
+                        stack.push(Frame::Pred);
+                        Control::Eval(t1, env)
+                    }
+                    Code::IsZero(t1) => {
+                        stack.push(Frame::IsZero);
+                        Control::Eval(t1, env)
+                    }
+                    Code::Record(fields) => {
+                        let mut addrs = vec![];
+                        for (name, field) in fields {
+                            addrs.push((name, self.delay(field, &env)?));
+                        }
+                        Control::Return(Value::Record(addrs))
+                    }
+                    Code::Proj(t1, name) => {
+                        stack.push(Frame::Proj(name));
+                        Control::Eval(t1, env)
+                    }
+                    Code::TimesFloat(t1, t2) => {
+                        stack.push(Frame::TimesLeft(t2, env.clone()));
+                        Control::Eval(t1, env)
+                    }
+                    Code::Host(host, args) => {
+                        let mut addrs = vec![];
+                        for arg in args {
+                            addrs.push(self.delay(arg, &env)?);
+                        }
+                        Control::Return(Value::Host(host, addrs))
+                    }
+                },
+                Control::Enter(addr) => {
+                    match std::mem::replace(&mut self.heap[addr], Cell::Blackhole) {
+                        Cell::Value(value) => {
+                            self.heap[addr] = Cell::Value(value.clone());
+                            Control::Return(value)
+                        }
+                        Cell::Thunk(code, env) => {
+                            stack.push(Frame::Update(addr));
+                            Control::Eval(code, env)
+                        }
+                        Cell::Blackhole => return Err(stuck(String::from("infinite loop"))),
+                    }
+                }
+                Control::Return(value) => match (stack.pop(), value) {
+                    (None, value) => return Ok(value),
+                    (Some(Frame::Update(addr)), value) => {
+                        self.stats.updates += 1;
+                        self.heap[addr] = Cell::Value(value.clone());
+                        Control::Return(value)
+                    }
+                    (Some(Frame::Arg(addr)), Value::Closure(_, body, env)) => {
+                        self.stats.beta += 1;
+                        Control::Eval(body, extend(&env, addr))
+                    }
+                    (Some(Frame::Arg(addr)), Value::Host(host, mut args)) => {
+                        args.push(addr);
+                        match args.len() == host.arity {
+                            true => Control::Return(self.call_host(&host, &args)?),
+                            false => Control::Return(Value::Host(host, args)),
+                        }
+                    }
+                    (Some(Frame::If(t2, _, env)), Value::Bool(true)) => Control::Eval(t2, env),
+                    (Some(Frame::If(_, t3, env)), Value::Bool(false)) => Control::Eval(t3, env),
+                    (Some(Frame::Succ), Value::Nat(n)) => Control::Return(Value::Nat(n + 1)),
+                    (Some(Frame::Pred), Value::Nat(n)) => {
+                        Control::Return(Value::Nat(std::cmp::max(n - 1, 0)))
+                    }
+                    (Some(Frame::IsZero), Value::Nat(n)) => Control::Return(Value::Bool(n == 0)),
+                    (Some(Frame::Proj(name)), Value::Record(fields)) => {
+                        match fields.iter().find(|(label, _)| *label == name) {
+                            Some((_, addr)) => Control::Enter(*addr),
+                            None => return Err(stuck(format!("no field {}", name))),
+                        }
+                    }
+                    (Some(Frame::TimesLeft(t2, env)), Value::Float(flt)) => {
+                        stack.push(Frame::TimesRight(flt));
+                        Control::Eval(t2, env)
+                    }
+                    (Some(Frame::TimesRight(l)), Value::Float(r)) => {
+                        Control::Return(Value::Float(l * r))
+                    }
+                    (Some(_), value) => {
+                        let term = self.read_back_value(&value, 0)?;
+                        return Err(stuck(format!("unexpected {}", term)));
+                    }
+                },
+            }
+        }
+    }
+
+    fn call_host(&mut self, host: &HostFunction, args: &[Addr]) -> Result<Value, EvalError> {
+        let mut terms = vec![];
+        for addr in args {
+            let value = self.run(Control::Enter(*addr))?;
+            self.force_deep(&value)?;
+            terms.push(self.read_back_value(&value, 0)?);
+        }
+
+        let result = host
+            .call(self.io, &terms)
+            .map_err(|e| EvalError::EvalError(format!("{}: {}", host.name, e)))?;
+        let code = self.compile(&result);
+
+        self.run(Control::Eval(code, Rc::new(EnvNode::Empty)))
+    }
+
+    fn read_back_value(&self, value: &Value, depth: usize) -> Result<Term, EvalError> {
+        let fi = FileInfo::default();
+
+        Ok(match value {
+            Value::Closure(name, body, env) => Term::Abstraction(
+                fi,
+                name.clone(),
+                box self.read_back_code(*body, env, depth + 1, 1)?,
+            ),
+            Value::Bool(true) => Term::True(fi),
+            Value::Bool(false) => Term::False(fi),
+            Value::Nat(n) => Term::from_int(*n, fi),
+            Value::Str(s) => Term::String(fi, s.clone()),
+            Value::Float(flt) => Term::Float(fi, *flt),
+            Value::Record(fields) => Term::Record(
+                fi,
+                fields
+                    .iter()
+                    .map(|(name, addr)| Ok((name.clone(), box self.read_back_addr(*addr, depth)?)))
+                    .collect::<Result<_, EvalError>>()?,
+            ),
+            Value::Host(host, args) => Term::Host(
+                fi,
+                host.clone(),
+                args.iter()
+                    .map(|addr| self.read_back_addr(*addr, depth))
+                    .collect::<Result<_, EvalError>>()?,
+            ),
+        })
+    }
+
+    fn read_back_addr(&self, addr: Addr, depth: usize) -> Result<Term, EvalError> {
+        match &self.heap[addr] {
+            Cell::Value(value) => self.read_back_value(value, depth),
+            Cell::Thunk(code, env) => self.read_back_code(*code, env, depth, 0),
+            Cell::Blackhole => Err(stuck(String::from("reading back a value under evaluation"))),
+        }
+    }
+
+    /// Reads `code` back into a term. `binders` counts the binders between
+    /// `env` and the code; variables beyond them are replaced by the term the
+    /// environment points at.
+    fn read_back_code(
+        &self,
+        code: CodeId,
+        env: &Env,
+        depth: usize,
+        binders: usize,
+    ) -> Result<Term, EvalError> {
+        let fi = FileInfo::default();
+        let back = |code: CodeId| self.read_back_code(code, env, depth, binders);
+        let under = |code: CodeId| self.read_back_code(code, env, depth + 1, binders + 1);
+
+        Ok(match &self.code[code] {
+            Code::Var(name, idx) if *idx < binders => {
+                Term::Var(fi, Var::new(name, *idx as i32, depth as i32))
+            }
+            Code::Var(name, idx) => {
+                let addr = lookup(env, idx - binders)
+                    .ok_or_else(|| stuck(format!("{} is unbound", name)))?;
+                self.read_back_addr(addr, depth - binders)?
+                    .shift(binders as i32)
+            }
+            Code::Lam(name, body) => Term::Abstraction(fi, name.clone(), box under(*body)?),
+            Code::App(t1, t2) => Term::Application(fi, box back(*t1)?, box back(*t2)?),
+            Code::Let(name, t1, t2) => {
+                Term::Let(fi, name.clone(), box back(*t1)?, box under(*t2)?)
+            }
+            Code::If(t1, t2, t3) => Term::If(fi, box back(*t1)?, box back(*t2)?, box back(*t3)?),
+            Code::Lit(value) => self.read_back_value(value, depth)?,
+            Code::Succ(t1) => Term::Successor(fi, box back(*t1)?),
+            Code::Pred(t1) => Term::Predecessor(fi, box back(*t1)?),
+            Code::IsZero(t1) => Term::IsZero(fi, box back(*t1)?),
+            Code::Record(fields) => Term::Record(
+                fi,
+                fields
+                    .iter()
+                    .map(|(name, field)| Ok((name.clone(), box back(*field)?)))
+                    .collect::<Result<_, EvalError>>()?,
+            ),
+            Code::Proj(t1, name) => Term::Projection(fi, box back(*t1)?, name.clone()),
+            Code::TimesFloat(t1, t2) => Term::TimesFloat(fi, box back(*t1)?, box back(*t2)?),
+            Code::Host(host, args) => Term::Host(
+                fi,
+                host.clone(),
+                args.iter()
+                    .map(|arg| back(*arg))
+                    .collect::<Result<_, EvalError>>()?,
+            ),
+        })
+    }
+}
+
+/// The outcome of evaluating one command with both backends.
+#[derive(Debug)]
+pub struct Comparison {
+    pub substitution: Term,
+    pub substitution_stats: EvalStats,
+    pub graph: Term,
+    pub graph_stats: GraphStats,
+    /// Whether graph reduction printed what substitution did.
+    pub output_agrees: bool,
+}
+
+pub fn evaluate_graph(
+    context: &Context,
+    term: &Term,
+    io: &mut dyn Io,
+) -> Result<(Term, GraphStats), EvalError> {
+    let mut graph = Graph::new(&close_over_globals(context, term), io);
+    let value = graph.evaluate()?;
+
+    Ok((value, graph.stats()))
+}
+
+impl<'a> Evaluator<'a> {
+    /// Evaluates a command by substitution and then by graph reduction.
+    /// Only substitution performs effects: graph reduction is fed the lines
+    /// it read, and what it prints is compared with what substitution did.
+    pub fn compare(&mut self, context: &Context, term: &Term) -> Result<Comparison, EvalError> {
+        let mut recording = RecordingIo::new(&mut *self.io);
+        let mut evaluator = Evaluator::new(&mut recording).with_limits(self.limits);
+        evaluator.memo = self.memo.take();
+        let substitution = evaluator.evaluate_command(context, term);
+        let substitution_stats = evaluator.stats();
+        self.memo = evaluator.memo.take();
+
+        let substitution = substitution?;
+        let mut replay = recording.replay();
+        let (graph, graph_stats) = evaluate_graph(context, term, &mut replay)?;
+
+        Ok(Comparison {
+            substitution,
+            substitution_stats,
+            graph,
+            graph_stats,
+            output_agrees: replay.output() == recording.output(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::*;
+    use crate::parser::*;
+    use crate::test_util::last_term;
+
+    fn compare_last(input: &str) -> Comparison {
+        let (term, context) = last_term(input);
+
+        Evaluator::new(&mut MemoryIo::default())
+            .compare(&context, &term)
+            .expect("Failed to evaluate")
+    }
+
+    #[test]
+    fn test_graph_agrees_on_test_files() {
+        for (input, expectation) in [
+            (include_str!("lambda-files/test1.f"), Term::from_int(120, FileInfo::default())),
+            (include_str!("lambda-files/test2.f"), Term::True(FileInfo::default())),
+            (include_str!("lambda-files/test4.f"), Term::False(FileInfo::default())),
+            (include_str!("lambda-files/test5.f"), Term::True(FileInfo::default())),
+        ] {
+            let comparison = compare_last(input);
+
+            assert_eq!(comparison.graph, expectation);
+            assert_eq!(comparison.graph, comparison.substitution.without_locations());
+        }
+    }
+
+    #[test]
+    fn test_graph_record_patterns() {
+        let comparison = compare_last(
+            "let k = 7 in let {a = x, b = {c = y}} = {a = k, b = {c = 2, d = 3}} in { s = x, t = + y };",
+        );
+
+        assert_eq!(comparison.graph, comparison.substitution.without_locations());
+        assert_eq!(format!("{}", comparison.graph), "{ s = 7, t = 3 }");
+    }
+
+    #[test]
+    fn test_graph_shares_arguments() {
+        let input = r#"
+        let czero = λs. λz. z;
+        let scc = λn. λs. λz. s (n s z);
+        let plus = λm. λn. λs. λz. m s (n s z);
+        let times = λm. λn. m (plus n) czero;
+        let realnat = λm. m (λx. + x) 0;
+        let three = scc (scc (scc czero));
+        realnat ((λx. times x x) (times three three));
+        "#;
+
+        let comparison = compare_last(input);
+
+        assert_eq!(comparison.graph, Term::from_int(81, FileInfo::default()));
+        assert_eq!(comparison.graph, comparison.substitution.without_locations());
+        assert!(comparison.graph_stats.updates > 0);
+        assert!(comparison.graph_stats.beta > 0);
+        assert!(comparison.substitution_stats.substitutions > 0);
+    }
+
+    #[test]
+    fn test_graph_effects_happen_once() {
+        let mut context = Context::default();
+        context.register_io();
+        let (parsed, context) =
+            parse_with_context("print (read_line {});", context).expect("Parse error");
+        let term = match &parsed[0] {
+            Command::Eval(_, term) => term,
+            _ => panic!(),
+        };
+        let mut io = MemoryIo::new(&["hello"]);
+        let comparison = Evaluator::new(&mut io)
+            .compare(&context, term)
+            .expect("Failed to evaluate");
+
+        assert_eq!(comparison.graph, comparison.substitution.without_locations());
+        assert!(comparison.output_agrees);
+        assert_eq!(io.output(), ["hello"]);
+    }
+
+    #[test]
+    fn test_graph_is_lazy() {
+        let (term, context) = last_term("(λx. 0) ((λx. x x) (λx. x x));");
+        let (value, _) =
+            evaluate_graph(&context, &term, &mut MemoryIo::default()).expect("Failed to evaluate");
+
+        assert_eq!(value, Term::from_int(0, FileInfo::default()));
+    }
+
+    #[test]
+    fn test_graph_reads_back_closures() {
+        let (term, context) = last_term("let k = λx. λy. x; k (λz. z);");
+        let (value, _) =
+            evaluate_graph(&context, &term, &mut MemoryIo::default()).expect("Failed to evaluate");
+
+        assert_eq!(format!("{}", value), "λy z. z");
+    }
+
+    #[test]
+    fn test_graph_host_functions() {
+        let mut context = Context::default();
+        context.register_io();
+        let (parsed, context) =
+            parse_with_context("(λx. λy. y) (print 1) (print 2);", context).expect("Parse error");
+        let mut io = MemoryIo::default();
+
+        if let Command::Eval(_, term) = &parsed[0] {
+            let (value, _) = evaluate_graph(&context, term, &mut io).expect("Failed to evaluate");
+
+            assert_eq!(value, Term::from_int(2, FileInfo::default()));
+            assert_eq!(io.output(), ["2"]);
+        } else {
+            panic!()
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::context::*;
+use crate::io::{self, Io};
+use crate::literals::MAX_LITERAL;
+use crate::syntax::*;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::sync::Arc;
+
+pub type HostResult = Result<Term, String>;
+
+type HostFn = dyn Fn(&mut dyn Io, &[Term]) -> HostResult + Send + Sync;
+
+/// A named Rust function that can be called from lambda terms.
+///
+/// Host functions are curried: applying one to fewer than `arity` values
+/// produces a partially applied `Term::Host`, and the function body runs
+/// once the last argument has been evaluated to a value.
+#[derive(Clone)]
+pub struct HostFunction {
+    pub name: String,
+    pub arity: usize,
+    pub effectful: bool,
+    func: Arc<HostFn>,
+}
+
+impl HostFunction {
+    fn build<F>(name: &str, arity: usize, effectful: bool, func: F) -> Self
+    where
+        F: Fn(&mut dyn Io, &[Term]) -> HostResult + Send + Sync + 'static,
+    {
+        HostFunction {
+            name: name.into(),
+            arity,
+            effectful,
+            func: Arc::new(func),
+        }
+    }
+
+    /// Fails if `arity` is zero, as a host function only runs once it is
+    /// applied to its last argument.
+    pub fn new<F>(name: &str, arity: usize, func: F) -> Result<Self, String>
+    where
+        F: Fn(&[Term]) -> HostResult + Send + Sync + 'static,
+    {
+        HostFunction::effectful(name, arity, move |_, args| func(args)).map(|host| HostFunction {
+            effectful: false,
+            ..host
+        })
+    }
+
+    /// Like `new`, but the function is also handed the evaluator's `Io`.
+    pub fn effectful<F>(name: &str, arity: usize, func: F) -> Result<Self, String>
+    where
+        F: Fn(&mut dyn Io, &[Term]) -> HostResult + Send + Sync + 'static,
+    {
+        match arity {
+            0 => Err(format!("host function {} must take an argument", name)),
+            _ => Ok(HostFunction::build(name, arity, true, func)),
+        }
+    }
+
+    pub fn effectful_unary<F>(name: &str, func: F) -> Self
+    where
+        F: Fn(&mut dyn Io, &Term) -> HostResult + Send + Sync + 'static,
+    {
+        HostFunction::build(name, 1, true, move |io, args| func(io, &args[0]))
+    }
+
+    pub fn unary<A, R, F>(name: &str, func: F) -> Self
+    where
+        A: FromTerm,
+        R: IntoTerm,
+        F: Fn(A) -> R + Send + Sync + 'static,
+    {
+        HostFunction::build(name, 1, false, move |_, args| {
+            func(A::from_term(&args[0])?).into_term()
+        })
+    }
+
+    pub fn binary<A, B, R, F>(name: &str, func: F) -> Self
+    where
+        A: FromTerm,
+        B: FromTerm,
+        R: IntoTerm,
+        F: Fn(A, B) -> R + Send + Sync + 'static,
+    {
+        HostFunction::build(name, 2, false, move |_, args| {
+            func(A::from_term(&args[0])?, B::from_term(&args[1])?).into_term()
+        })
+    }
+
+    pub fn call(&self, io: &mut dyn Io, args: &[Term]) -> HostResult {
+        (self.func)(io, args)
+    }
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HostFunction({}/{})", self.name, self.arity)
+    }
+}
+
+impl PartialEq for HostFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.arity == other.arity && Arc::ptr_eq(&self.func, &other.func)
+    }
+}
+
+/// Host functions are written as their name, and read back as the I/O
+/// primitive of that name; there is no way to serialize the Rust function.
+impl Serialize for HostFunction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+impl<'de> Deserialize<'de> for HostFunction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        io::primitive(&name)
+            .ok_or_else(|| de::Error::custom(format!("there is no host function `{}`", name)))
+    }
+}
+
+impl Context {
+    pub fn register_host(&mut self, host: HostFunction) {
+        self.append_binding(ContextMember {
+            name: host.name.clone(),
+            binding: Binding::TermBind(box Term::Host(FileInfo::default(), host, vec![])),
+        })
+    }
+}
+
+pub trait FromTerm: Sized {
+    fn from_term(term: &Term) -> Result<Self, String>;
+}
+
+/// Fails for values no term can hold.
+pub trait IntoTerm {
+    fn into_term(self) -> HostResult;
+}
+
+impl FromTerm for Term {
+    fn from_term(term: &Term) -> Result<Self, String> {
+        Ok(term.clone())
+    }
+}
+
+impl IntoTerm for Term {
+    fn into_term(self) -> HostResult {
+        Ok(self)
+    }
+}
+
+impl FromTerm for u32 {
+    fn from_term(term: &Term) -> Result<Self, String> {
+        term.into_int()
+            .map(|n| n as u32)
+            .ok_or(format!("expected a number, found {}", term))
+    }
+}
+
+/// Numbers are unary, so a host may only hand back as large a number as a
+/// literal could write.
+impl IntoTerm for u32 {
+    fn into_term(self) -> HostResult {
+        match i32::try_from(self) {
+            Ok(n) if n <= MAX_LITERAL => Ok(Term::from_int(n, FileInfo::default())),
+            _ => Err(format!(
+                "{} is too large to be a number; numbers go up to {}",
+                self, MAX_LITERAL
+            )),
+        }
+    }
+}
+
+impl FromTerm for bool {
+    fn from_term(term: &Term) -> Result<Self, String> {
+        match term {
+            Term::True(_) => Ok(true),
+            Term::False(_) => Ok(false),
+            _ => Err(format!("expected a boolean, found {}", term)),
+        }
+    }
+}
+
+impl IntoTerm for bool {
+    fn into_term(self) -> HostResult {
+        Ok(match self {
+            true => Term::True(FileInfo::default()),
+            false => Term::False(FileInfo::default()),
+        })
+    }
+}
+
+impl FromTerm for String {
+    fn from_term(term: &Term) -> Result<Self, String> {
+        match term {
+            Term::String(_, s) => Ok(s.clone()),
+            _ => Err(format!("expected a string, found {}", term)),
+        }
+    }
+}
+
+impl IntoTerm for String {
+    fn into_term(self) -> HostResult {
+        Ok(Term::String(FileInfo::default(), self))
+    }
+}
+
+impl FromTerm for f32 {
+    fn from_term(term: &Term) -> Result<Self, String> {
+        match term {
+            Term::Float(_, flt) => Ok(*flt),
+            _ => Err(format!("expected a float, found {}", term)),
+        }
+    }
+}
+
+impl IntoTerm for f32 {
+    fn into_term(self) -> HostResult {
+        Ok(Term::Float(FileInfo::default(), self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluate::*;
+    use crate::host::*;
+    use crate::io::MemoryIo;
+
+    fn host_context() -> Context {
+        let mut context = Context::default();
+
+        context.register_host(HostFunction::unary("double", |n: u32| n * 2));
+        context.register_host(HostFunction::binary("add", |m: u32, n: u32| m + n));
+        context.register_host(HostFunction::unary("shout", |s: String| {
+            s.to_uppercase()
+        }));
+        context.register_host(HostFunction::unary("huge", |n: u32| n + u32::MAX / 2));
+
+        context
+    }
+
+    #[test]
+    fn test_host_unary() {
+        let values = eval_source(&mut MemoryIo::default(), "double 3;", host_context())
+            .expect("Failed to evaluate");
+
+        assert_eq!(values, [Term::from_int(6, FileInfo::default())]);
+    }
+
+    #[test]
+    fn test_host_partial_application() {
+        let input = r#"
+        let addtwo = add 2;
+        (λf. f (double 1)) addtwo;
+        "#;
+
+        let values = eval_source(&mut MemoryIo::default(), input, host_context())
+            .expect("Failed to evaluate");
+
+        assert_eq!(values, [Term::from_int(4, FileInfo::default())]);
+    }
+
+    #[test]
+    fn test_host_string() {
+        let values = eval_source(&mut MemoryIo::default(), "shout \"hello\";", host_context())
+            .expect("Failed to evaluate");
+
+        assert_eq!(
+            values,
+            [Term::String(FileInfo::default(), "HELLO".into())]
+        );
+    }
+
+    #[test]
+    fn test_host_conversion_error() {
+        let result = eval_source(&mut MemoryIo::default(), "double true;", host_context());
+
+        assert!(matches!(result, Err(EvalError::Runtime(_, _))));
+    }
+
+    #[test]
+    fn test_host_overflow() {
+        let result = eval_source(&mut MemoryIo::default(), "huge 1;", host_context());
+
+        assert!(matches!(result, Err(EvalError::Runtime(_, e)) if e.contains("too large")));
+    }
+
+    #[test]
+    fn test_host_number_bound() {
+        assert!(1000u32.into_term().is_ok());
+        assert!(1001u32.into_term().is_err());
+    }
+
+    #[test]
+    fn test_host_arity() {
+        assert!(HostFunction::new("constant", 0, |_| Ok(Term::Zero(FileInfo::default()))).is_err());
+        assert_eq!(HostFunction::new("id", 1, |args| Ok(args[0].clone())).unwrap().arity, 1);
+    }
+
+    #[test]
+    fn test_host_is_bound_name() {
+        let context = host_context();
+
+        assert!(context.is_name_bound("double"));
+        assert!(context.is_name_bound("add"));
+    }
+}
@@ -0,0 +1,242 @@
+use crate::context::*;
+use crate::host::*;
+use crate::syntax::*;
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+/// The effects available to `print`, `read_line` and `read_nat`.
+pub trait Io {
+    fn write_line(&mut self, line: &str);
+    fn read_line(&mut self) -> Option<String>;
+}
+
+#[derive(Debug, Default)]
+pub struct StdIo;
+
+impl Io for StdIo {
+    fn write_line(&mut self, line: &str) {
+        println!("{}", line);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+
+        match std::io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(&['\r', '\n'][..]).into()),
+        }
+    }
+}
+
+/// Like `StdIo`, but writes to standard error, leaving standard output for
+/// a program being written out.
+#[derive(Debug, Default)]
+pub struct StderrIo;
+
+impl Io for StderrIo {
+    fn write_line(&mut self, line: &str) {
+        eprintln!("{}", line);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        StdIo.read_line()
+    }
+}
+
+/// Reads from a fixed list of lines and records everything written, so
+/// interactive programs can be tested deterministically.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct MemoryIo {
+    input: VecDeque<String>,
+    output: Vec<String>,
+}
+
+impl MemoryIo {
+    pub fn new(input: &[&str]) -> Self {
+        MemoryIo {
+            input: input.iter().map(|line| String::from(*line)).collect(),
+            output: vec![],
+        }
+    }
+
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+}
+
+impl Io for MemoryIo {
+    fn write_line(&mut self, line: &str) {
+        self.output.push(line.into())
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+}
+
+/// Passes everything on to another `Io`, keeping the lines read and written
+/// so a second evaluation can be run against the same input.
+pub struct RecordingIo<'a> {
+    io: &'a mut dyn Io,
+    record: MemoryIo,
+}
+
+impl<'a> RecordingIo<'a> {
+    pub fn new(io: &'a mut dyn Io) -> Self {
+        RecordingIo {
+            io,
+            record: MemoryIo::default(),
+        }
+    }
+
+    pub fn output(&self) -> &[String] {
+        self.record.output()
+    }
+
+    /// A `MemoryIo` that reads the lines read so far.
+    pub fn replay(&self) -> MemoryIo {
+        MemoryIo {
+            input: self.record.input.clone(),
+            output: vec![],
+        }
+    }
+}
+
+impl<'a> Io for RecordingIo<'a> {
+    fn write_line(&mut self, line: &str) {
+        self.record.write_line(line);
+        self.io.write_line(line)
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let line = self.io.read_line();
+        self.record.input.extend(line.clone());
+        line
+    }
+}
+
+fn read_line(io: &mut dyn Io) -> Result<String, String> {
+    io.read_line().ok_or(String::from("end of input"))
+}
+
+/// `print v` writes `v` and evaluates to it. `read_line` and `read_nat`
+/// ignore their argument, so they are called as `read_line {}`. `read_nat`
+/// refuses numbers larger than a literal could write.
+pub fn primitives() -> Vec<HostFunction> {
+    vec![
+        HostFunction::effectful_unary("print", |io, arg| {
+            match arg {
+                Term::String(_, s) => io.write_line(s),
+                term => io.write_line(&format!("{}", term)),
+            }
+            Ok(arg.clone())
+        }),
+        HostFunction::effectful_unary("read_line", |io, _| {
+            Ok(Term::String(FileInfo::default(), read_line(io)?))
+        }),
+        HostFunction::effectful_unary("read_nat", |io, _| {
+            let line = read_line(io)?;
+
+            line.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("expected a natural number, read {:?}", line))
+                .and_then(IntoTerm::into_term)
+        }),
+    ]
+}
+
+pub fn primitive(name: &str) -> Option<HostFunction> {
+    primitives().into_iter().find(|host| host.name == name)
+}
+
+impl Context {
+    pub fn register_io(&mut self) {
+        for host in primitives() {
+            self.register_host(host)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluate::*;
+    use crate::io::*;
+
+    fn eval_with_input(input: &str, lines: &[&str]) -> (Result<Vec<Term>, EvalError>, MemoryIo) {
+        let mut io = MemoryIo::new(lines);
+        let mut context = Context::default();
+        context.register_io();
+
+        let values = Evaluator::new(&mut io).eval_source(input, context);
+
+        (values, io)
+    }
+
+    #[test]
+    fn test_print() {
+        let (values, io) = eval_with_input("print 2;", &[]);
+
+        assert_eq!(values.unwrap().without_locations(), [Term::from_int(2, FileInfo::default())]);
+        assert_eq!(io.output(), ["2"]);
+    }
+
+    #[test]
+    fn test_read_line() {
+        let (values, io) = eval_with_input("print (read_line {});", &["hello"]);
+
+        assert_eq!(
+            values.unwrap(),
+            [Term::String(FileInfo::default(), "hello".into())]
+        );
+        assert_eq!(io.output(), ["hello"]);
+    }
+
+    #[test]
+    fn test_read_nat_countdown() {
+        let input = r#"
+        let Y = λf. (λx. f(λy. x x y)) (λx. f(λy. x x y));
+        let count = λfn. λn. if iszero n then 0 else (λu. fn (-n)) (print n);
+        Y count (read_nat {});
+        "#;
+
+        let (values, io) = eval_with_input(input, &["3"]);
+
+        assert_eq!(values.unwrap().without_locations(), [Term::from_int(0, FileInfo::default())]);
+        assert_eq!(io.output(), ["3", "2", "1"]);
+    }
+
+    #[test]
+    fn test_read_nat_invalid() {
+        let (values, _) = eval_with_input("read_nat {};", &["three"]);
+
+        assert!(matches!(values, Err(EvalError::Runtime(_, _))));
+    }
+
+    #[test]
+    fn test_read_nat_too_large() {
+        let (values, _) = eval_with_input("read_nat {};", &["4000000000"]);
+
+        assert!(matches!(values, Err(EvalError::Runtime(_, e)) if e.contains("too large")));
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut io = MemoryIo::new(&["a", "b"]);
+        let mut recording = RecordingIo::new(&mut io);
+        recording.write_line("x");
+        recording.read_line();
+        let mut replay = recording.replay();
+
+        assert_eq!(recording.output(), ["x"]);
+        assert_eq!(replay.read_line(), Some("a".into()));
+        assert_eq!(replay.read_line(), None);
+        assert_eq!(io.output(), ["x"]);
+    }
+
+    #[test]
+    fn test_read_past_end_of_input() {
+        let (values, _) = eval_with_input("read_line {};", &[]);
+
+        assert!(matches!(values, Err(EvalError::Runtime(_, _))));
+    }
+}
@@ -1,5 +1,5 @@
 let tru = λt. λf. t;
 let fls = λt. λf. f;
-let realbool = λb.b true false;
+let realbool = λb. b true false;
 
-realbool tru;
\ No newline at end of file
+realbool tru;
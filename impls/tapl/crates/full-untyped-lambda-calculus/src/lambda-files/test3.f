@@ -1,5 +1,4 @@
 let tru = λt. λf. t in
 let fls = λt. λf. f in
-let realbool = λb.b true false in
-
-realbool tru;
\ No newline at end of file
+let realbool = λb. b true false in
+realbool tru;
@@ -3,5 +3,5 @@ let fls = λt. λf. f in
 let pair = λf. λs. λb. b f s in
 let fst = λp. p tru in
 let snd = λp. p fls in
-let realbool = λb.b true false in
-realbool (fst (pair tru fls));
\ No newline at end of file
+let realbool = λb. b true false in
+realbool (fst (pair tru fls));
@@ -0,0 +1,7 @@
+-- Factorial of five on Church numerals, from the prelude; evaluates to 120.
+use "std";
+
+let fct fn n = if realeq n czero then cone else times n (fn (prd n));
+let factorial = Y fct;
+
+realnat (factorial (churchnat 5));
@@ -0,0 +1,35 @@
+#![feature(box_patterns)]
+#![feature(box_syntax)]
+#[macro_use]
+extern crate lalrpop_util;
+
+pub mod bigstep;
+pub mod blc;
+pub mod combinators;
+pub mod context;
+pub mod context_visitor;
+pub mod desugar;
+pub mod diagnostics;
+pub mod dot;
+pub mod evaluate;
+pub mod fixity;
+pub mod format;
+pub mod graph;
+pub mod host;
+pub mod io;
+pub mod limits;
+pub mod literals;
+pub mod memo;
+pub mod modules;
+pub mod parallel;
+pub mod parser;
+pub mod prelude;
+pub mod pretty;
+mod printer;
+pub mod resolve;
+pub mod serialize;
+pub mod sexp;
+pub mod surface;
+pub mod syntax;
+#[cfg(test)]
+mod test_util;
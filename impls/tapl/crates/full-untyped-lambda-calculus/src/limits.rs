@@ -0,0 +1,338 @@
+use crate::syntax::*;
+use std::mem;
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Limits {
+    pub max_term_size: Option<usize>,
+    pub max_binding_depth: Option<usize>,
+    pub max_memory: Option<usize>,
+}
+
+/// A limit that was exceeded, along with its configured maximum.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Limit {
+    TermSize(usize),
+    BindingDepth(usize),
+    Memory(usize),
+}
+
+impl Limit {
+    pub fn max(&self) -> usize {
+        match self {
+            Limit::TermSize(max) | Limit::BindingDepth(max) | Limit::Memory(max) => *max,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Metrics {
+    /// Number of nodes in the term.
+    pub size: usize,
+    /// Deepest nesting of binders (abstractions and lets).
+    pub binding_depth: usize,
+    /// Rough number of bytes the term occupies on the heap.
+    pub memory: usize,
+}
+
+impl Metrics {
+    pub fn of(term: &Term) -> Metrics {
+        fn walk(term: &Term, depth: usize, metrics: &mut Metrics) {
+            metrics.size += 1;
+            metrics.memory += mem::size_of::<Term>();
+            metrics.binding_depth = metrics.binding_depth.max(depth);
+
+            match term {
+                Term::String(_, s) => metrics.memory += s.len(),
+                Term::Var(_, var) => metrics.memory += var.name.len(),
+                Term::If(_, box t1, box t2, box t3) => {
+                    walk(t1, depth, metrics);
+                    walk(t2, depth, metrics);
+                    walk(t3, depth, metrics);
+                }
+                Term::Let(_, name, box t1, box t2) => {
+                    metrics.memory += name.len();
+                    walk(t1, depth, metrics);
+                    walk(t2, depth + 1, metrics);
+                }
+                Term::LetPattern(_, pattern, box t1, box t2) => {
+                    let binders = pattern.binders();
+                    metrics.memory += binders.iter().map(|name| name.len()).sum::<usize>();
+                    walk(t1, depth, metrics);
+                    walk(t2, depth + binders.len(), metrics);
+                }
+                Term::Record(_, fields) => {
+                    for (name, box field) in fields {
+                        metrics.memory += name.len();
+                        walk(field, depth, metrics);
+                    }
+                }
+                Term::Projection(_, box t1, name) => {
+                    metrics.memory += name.len();
+                    walk(t1, depth, metrics);
+                }
+                Term::Abstraction(_, name, box t1) => {
+                    metrics.memory += name.len();
+                    walk(t1, depth + 1, metrics);
+                }
+                Term::Application(_, box t1, box t2) | Term::TimesFloat(_, box t1, box t2) => {
+                    walk(t1, depth, metrics);
+                    walk(t2, depth, metrics);
+                }
+                Term::Successor(_, box t1) | Term::Predecessor(_, box t1) | Term::IsZero(_, box t1) => {
+                    walk(t1, depth, metrics)
+                }
+                Term::Host(_, _, args) => {
+                    for arg in args {
+                        walk(arg, depth, metrics);
+                    }
+                }
+                Term::True(_) | Term::False(_) | Term::Zero(_) | Term::Float(_, _) => {}
+            }
+        }
+
+        let mut metrics = Metrics::default();
+        walk(term, 0, &mut metrics);
+
+        metrics
+    }
+
+    /// Like `of`, but leaves out the first `globals` leading `let`s, which
+    /// `close_over_globals` wrapped the term in. Their bound terms and the
+    /// body are measured on their own, keeping the largest of each measure.
+    pub fn of_closed(term: &Term, globals: usize) -> Metrics {
+        match term {
+            Term::Let(_, _, box t1, box t2) if globals > 0 => {
+                Metrics::of(t1).max(Metrics::of_closed(t2, globals - 1))
+            }
+            term => Metrics::of(term),
+        }
+    }
+
+    fn max(self, other: Metrics) -> Metrics {
+        Metrics {
+            size: self.size.max(other.size),
+            binding_depth: self.binding_depth.max(other.binding_depth),
+            memory: self.memory.max(other.memory),
+        }
+    }
+}
+
+/// How many `let`s `term` starts with, one in the body of the other.
+pub fn leading_lets(term: &Term) -> usize {
+    match term {
+        Term::Let(_, _, _, box body) => 1 + leading_lets(body),
+        _ => 0,
+    }
+}
+
+impl Limits {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Limits::default()
+    }
+
+    /// Returns the first limit `term` exceeds, with the measured value.
+    pub fn check(&self, term: &Term) -> Option<(Limit, usize)> {
+        match self.is_unlimited() {
+            true => None,
+            false => self.exceeded(&Metrics::of(term)),
+        }
+    }
+
+    fn exceeded(&self, metrics: &Metrics) -> Option<(Limit, usize)> {
+        [
+            (self.max_term_size.map(Limit::TermSize), metrics.size),
+            (
+                self.max_binding_depth.map(Limit::BindingDepth),
+                metrics.binding_depth,
+            ),
+            (self.max_memory.map(Limit::Memory), metrics.memory),
+        ]
+        .into_iter()
+        .find_map(|(limit, actual)| match limit {
+            Some(limit) if actual > limit.max() => Some((limit, actual)),
+            _ => None,
+        })
+    }
+}
+
+/// Checks each term one evaluation steps through against `Limits`.
+///
+/// A single step can double a term or more, so every one is measured. Its
+/// first `globals` leading `let`s are measured as in `Metrics::of_closed`,
+/// for as long as they have not been reduced.
+pub struct Meter {
+    limits: Limits,
+    globals: usize,
+    lets: usize,
+}
+
+impl Meter {
+    pub fn new(limits: Limits, term: &Term, globals: usize) -> Meter {
+        Meter {
+            limits,
+            globals,
+            lets: leading_lets(term),
+        }
+    }
+
+    /// Called with the first term and then with each term it steps to.
+    pub fn check(&mut self, term: &Term) -> Option<(Limit, usize)> {
+        // Only the outermost `let` is reduced while any of the globals' are
+        // left, so each `let` lost is one of theirs.
+        let lets = leading_lets(term);
+        self.globals = self.globals.saturating_sub(self.lets.saturating_sub(lets));
+        self.lets = lets;
+
+        match self.limits.is_unlimited() {
+            true => None,
+            false => self.limits.exceeded(&Metrics::of_closed(term, self.globals)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::*;
+    use crate::evaluate::*;
+    use crate::io::*;
+    use crate::limits::*;
+    use crate::parser::*;
+
+    fn eval_limited(input: &str, limits: Limits) -> Result<Vec<Term>, EvalError> {
+        let mut io = MemoryIo::default();
+
+        Evaluator::new(&mut io)
+            .with_limits(limits)
+            .eval_source(input, Context::default())
+    }
+
+    #[test]
+    fn test_metrics() {
+        let (parsed, _) = parse("λx. λy. x (let z = y in z);").expect("Parse error");
+
+        if let Command::Eval(_, term) = &parsed[0] {
+            let metrics = Metrics::of(term);
+
+            assert_eq!(metrics.size, 7);
+            assert_eq!(metrics.binding_depth, 3);
+            assert!(metrics.memory >= 7 * std::mem::size_of::<Term>());
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn test_term_size_limit() {
+        let limits = Limits {
+            max_term_size: Some(1000),
+            ..Limits::default()
+        };
+
+        let result = eval_limited("(λx. x x x) (λx. x x x);", limits);
+
+        assert!(matches!(
+            result,
+            Err(EvalError::LimitExceeded(Limit::TermSize(1000), actual)) if actual > 1000
+        ));
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let limits = Limits {
+            max_memory: Some(64 * 1024),
+            ..Limits::default()
+        };
+
+        let result = eval_limited("(λx. x x x) (λx. x x x);", limits);
+
+        assert!(matches!(
+            result,
+            Err(EvalError::LimitExceeded(Limit::Memory(_), _))
+        ));
+    }
+
+    #[test]
+    fn test_doubling_term_stops_at_limit() {
+        let input = r#"
+        let Y = λf. (λx. f(λy. x x y)) (λx. f(λy. x x y));
+        let grow = Y (λg. λv. g {a = v, b = v});
+        grow 0;
+        "#;
+        let limits = Limits {
+            max_term_size: Some(1000),
+            ..Limits::default()
+        };
+
+        assert!(matches!(
+            eval_limited(input, limits),
+            Err(EvalError::LimitExceeded(Limit::TermSize(1000), actual)) if actual <= 2 * 1000 + 50
+        ));
+
+        let limits = Limits {
+            max_memory: Some(1_000_000),
+            ..Limits::default()
+        };
+
+        assert!(matches!(
+            eval_limited(input, limits),
+            Err(EvalError::LimitExceeded(Limit::Memory(_), actual)) if actual <= 2_004_096
+        ));
+    }
+
+    #[test]
+    fn test_binding_depth_limit() {
+        let limits = Limits {
+            max_binding_depth: Some(2),
+            ..Limits::default()
+        };
+
+        let result = eval_limited("λa. λb. λc. a;", limits);
+
+        assert!(matches!(
+            result,
+            Err(EvalError::LimitExceeded(Limit::BindingDepth(2), 3))
+        ));
+    }
+
+    #[test]
+    fn test_globals_are_measured_apart() {
+        let (parsed, context) =
+            parse("let a = λx. x; let b = a; λy. b (a y);").expect("Parse error");
+
+        if let Command::Eval(_, term) = &parsed[2] {
+            let term = close_over_globals(&context, term);
+            let metrics = Metrics::of_closed(&term, 2);
+
+            assert_eq!(leading_lets(&term), 2);
+            assert_eq!(metrics.size, 6);
+            assert_eq!(metrics.binding_depth, 1);
+        } else {
+            panic!()
+        }
+
+        let limits = Limits {
+            max_term_size: Some(12),
+            max_binding_depth: Some(1),
+            ..Limits::default()
+        };
+        let input = "let a = λx. x; let b = λx. x; let c = λx. x; a (b (c 1));";
+
+        assert_eq!(
+            eval_limited(input, limits).expect("Failed to evaluate").without_locations(),
+            [Term::from_int(1, FileInfo::default())]
+        );
+    }
+
+    #[test]
+    fn test_within_limits() {
+        let limits = Limits {
+            max_term_size: Some(100),
+            max_binding_depth: Some(10),
+            max_memory: Some(1024 * 1024),
+        };
+
+        let result = eval_limited("(λx. x) 3;", limits).expect("Failed to evaluate");
+
+        assert_eq!(result.without_locations(), [Term::from_int(3, FileInfo::default())]);
+    }
+}
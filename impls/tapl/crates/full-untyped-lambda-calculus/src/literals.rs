@@ -0,0 +1,81 @@
+use crate::syntax::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The largest number a literal may write. Every encoding is a chain as
+/// long as the number, which the recursive evaluators walk on the stack.
+pub const MAX_LITERAL: i32 = 1000;
+
+/// What an integer literal stands for, chosen by `#literals` or `--literals`.
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Literals {
+    /// The built-in numbers, `succ (succ 0)`.
+    #[default]
+    Native,
+    /// `succ (succ zero)`, for whatever `succ` and `zero` are bound to.
+    Unary,
+    /// Church numerals, `λs z. s (s z)`.
+    Church,
+}
+
+impl Literals {
+    pub fn term(self, n: i32, info: FileInfo) -> Term {
+        let var = |name: &str| Term::Var(info.clone(), Var::new(name, 0, 0));
+        let apply = |f: &str, t: Term| Term::Application(info.clone(), box var(f), box t);
+
+        match self {
+            Literals::Native => Term::from_int(n, info),
+            Literals::Unary => (0..n).fold(var("zero"), |t, _| apply("succ", t)),
+            Literals::Church => {
+                let body = (0..n).fold(var("z"), |t, _| apply("s", t));
+                let z = Term::Abstraction(info.clone(), "z".into(), box body);
+
+                Term::Abstraction(info, "s".into(), box z)
+            }
+        }
+    }
+}
+
+impl FromStr for Literals {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(Literals::Native),
+            "unary" => Ok(Literals::Unary),
+            "church" => Ok(Literals::Church),
+            _ => Err(format!(
+                "`{}` is not a kind of literal; expected native, unary or church",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Literals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literals::Native => write!(f, "native"),
+            Literals::Unary => write!(f, "unary"),
+            Literals::Church => write!(f, "church"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::literals::*;
+
+    #[test]
+    fn test_literals() {
+        let info = FileInfo::default();
+
+        assert_eq!(Literals::Native.term(3, info.clone()), Term::from_int(3, info.clone()));
+        assert_eq!(Literals::Unary.term(2, info.clone()).to_string(), "succ (succ zero)");
+        assert_eq!(Literals::Church.term(2, info.clone()).to_string(), "λs z. s (s z)");
+        assert_eq!(Literals::Church.term(0, info).to_string(), "λs z. z");
+        assert_eq!("church".parse(), Ok(Literals::Church));
+        assert!("roman".parse::<Literals>().is_err());
+    }
+}
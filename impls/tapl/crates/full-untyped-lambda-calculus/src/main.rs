@@ -1,36 +1,331 @@
 #![allow(warnings)]
-#![feature(box_patterns)]
-#![feature(box_syntax)]
-#[macro_use]
-extern crate lalrpop_util;
+use full_untyped_lambda_calculus::bigstep::DERIVE_STACK;
+use full_untyped_lambda_calculus::blc::BlcFormat;
+use full_untyped_lambda_calculus::diagnostics::use_colour;
+use full_untyped_lambda_calculus::dot::DotGraph;
+use full_untyped_lambda_calculus::evaluate::{self, EvalOptions};
+use full_untyped_lambda_calculus::format::format_source;
+use full_untyped_lambda_calculus::literals::Literals;
+use full_untyped_lambda_calculus::parallel::default_jobs;
+use full_untyped_lambda_calculus::pretty::Style;
+use full_untyped_lambda_calculus::serialize::{AstFormat, Stage};
 use std::env;
 use std::fs;
 use std::path::PathBuf;
+use std::thread;
 
-mod context;
-mod context_visitor;
-mod evaluate;
-mod parser;
-mod printer;
-mod syntax;
+const USAGE: &str = "usage: full-untyped-lambda-calculus [--parallel] [--jobs N] [--memo] [--graph] [--ski] [--derive] \
+[--from-blc | --from-blc-bytes] [--to-blc | --to-blc-bytes] [--from-json | --from-sexp] \
+[--to-json STAGE | --to-sexp STAGE] [--dot tree|reduction] [--dot-nodes N] \
+[--max-size N] [--max-depth N] [--max-memory BYTES] \
+[--path DIR]... [--prelude] [--literals native|unary|church] <file>
+       full-untyped-lambda-calculus fmt [--check] [--width N] [--indent N] <file>...";
+
+#[derive(Debug, Default)]
+struct Options {
+    file: String,
+    eval: EvalOptions,
+}
+
+/// Options for `fmt`, which lays out source files rather than running them.
+#[derive(Debug, Default)]
+struct FmtOptions {
+    files: Vec<String>,
+    /// Only report the files that are not formatted.
+    check: bool,
+    style: Style,
+}
+
+fn parse_number<I: Iterator<Item = String>>(flag: &str, args: &mut I) -> Result<usize, String> {
+    args.next()
+        .and_then(|n| n.parse::<usize>().ok())
+        .ok_or(format!("{} expects a number", flag))
+}
+
+fn parse_stage<I: Iterator<Item = String>>(args: &mut I) -> Result<Stage, String> {
+    args.next()
+        .ok_or("expected a stage: parsed, resolved or evaluated")?
+        .parse()
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut file = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--parallel" => {
+                options.eval.parallel = options.eval.parallel.or(Some(default_jobs()));
+            }
+            "--memo" => options.eval.memo = true,
+            "--graph" => options.eval.graph = true,
+            "--ski" => options.eval.combinators = true,
+            "--derive" => options.eval.derivation = true,
+            "--prelude" => options.eval.prelude = true,
+            "--from-blc" => options.eval.blc_input = Some(BlcFormat::Text),
+            "--from-blc-bytes" => options.eval.blc_input = Some(BlcFormat::Bytes),
+            "--to-blc" => options.eval.blc_output = Some(BlcFormat::Text),
+            "--to-blc-bytes" => options.eval.blc_output = Some(BlcFormat::Bytes),
+            "--from-json" => options.eval.ast_input = Some(AstFormat::Json),
+            "--from-sexp" => options.eval.ast_input = Some(AstFormat::Sexp),
+            "--to-json" => {
+                options.eval.ast_output = Some((AstFormat::Json, parse_stage(&mut args)?))
+            }
+            "--to-sexp" => {
+                options.eval.ast_output = Some((AstFormat::Sexp, parse_stage(&mut args)?))
+            }
+            "--dot" => {
+                options.eval.dot =
+                    Some(args.next().ok_or("--dot expects tree or reduction")?.parse()?)
+            }
+            "--dot-nodes" => options.eval.dot_nodes = Some(parse_number(&arg, &mut args)?),
+            "--jobs" => options.eval.parallel = Some(parse_number(&arg, &mut args)?),
+            "--max-size" => options.eval.limits.max_term_size = Some(parse_number(&arg, &mut args)?),
+            "--max-depth" => {
+                options.eval.limits.max_binding_depth = Some(parse_number(&arg, &mut args)?)
+            }
+            "--max-memory" => options.eval.limits.max_memory = Some(parse_number(&arg, &mut args)?),
+            "--path" => options
+                .eval
+                .search_path
+                .push(args.next().map(PathBuf::from).ok_or("--path expects a directory")?),
+            "--literals" => {
+                options.eval.literals = args
+                    .next()
+                    .ok_or("--literals expects native, unary or church")?
+                    .parse()?
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ => file = Some(arg),
+        }
+    }
+
+    options.file = file.ok_or(String::from(USAGE))?;
+
+    if !options.eval.limits.is_unlimited() {
+        let eval = &options.eval;
+        let unmetered = [
+            ("--graph", eval.graph),
+            ("--ski", eval.combinators),
+            ("--derive", eval.derivation),
+        ];
+
+        if let Some((flag, _)) = unmetered.iter().find(|(_, set)| *set) {
+            return Err(format!("{} does not apply --max-size, --max-depth or --max-memory", flag));
+        }
+    }
+    if options.eval.parallel.is_some() {
+        let eval = &options.eval;
+        let serial_only = [
+            ("--memo", eval.memo),
+            ("--graph", eval.graph),
+            ("--ski", eval.combinators),
+            ("--derive", eval.derivation),
+            ("--from-blc", eval.blc_input.is_some()),
+            ("--to-blc", eval.blc_output.is_some()),
+            ("--to-json or --to-sexp", eval.ast_output.is_some()),
+            ("--dot", eval.dot.is_some()),
+        ];
+
+        if let Some((flag, _)) = serial_only.iter().find(|(_, set)| *set) {
+            return Err(format!("{} cannot be used with --parallel", flag));
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_fmt_args<I: Iterator<Item = String>>(mut args: I) -> Result<FmtOptions, String> {
+    let mut options = FmtOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => options.check = true,
+            "--width" => options.style.width = parse_number(&arg, &mut args)?,
+            "--indent" => options.style.indent = parse_number(&arg, &mut args)?,
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ => options.files.push(arg),
+        }
+    }
+
+    match options.files.is_empty() {
+        true => Err(String::from(USAGE)),
+        false => Ok(options),
+    }
+}
+
+/// Formats each file in place, or with `--check` names those that would
+/// change. Fails if any would, or cannot be parsed.
+fn fmt(options: &FmtOptions) -> Result<(), ()> {
+    let mut result = Ok(());
+
+    for file in &options.files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                result = Err(());
+                continue;
+            }
+        };
+
+        match format_source(file, &source, &options.style) {
+            Ok(formatted) if formatted == source => {}
+            Ok(_) if options.check => {
+                println!("{} is not formatted", file);
+                result = Err(());
+            }
+            Ok(formatted) => {
+                if let Err(e) = fs::write(file, formatted) {
+                    eprintln!("{}: {}", file, e);
+                    result = Err(());
+                }
+            }
+            Err(diagnostics) => {
+                for diagnostic in diagnostics {
+                    eprint!("{}", diagnostic.render(&source, use_colour()));
+                }
+                result = Err(());
+            }
+        }
+    }
+
+    result
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let file = fs::canonicalize(PathBuf::from(&args[1]))
+    if env::args().nth(1).as_deref() == Some("fmt") {
+        let options = parse_fmt_args(env::args().skip(2)).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2)
+        });
+
+        std::process::exit(if fmt(&options).is_ok() { 0 } else { 1 })
+    }
+
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2)
+    });
+    let file = fs::canonicalize(PathBuf::from(&options.file))
         .unwrap()
         .into_os_string()
         .into_string()
         .expect("");
-    println!("Reading {}", file);
+    if options.eval.prints_results() {
+        println!("Reading {}", file);
+    }
 
-    evaluate::eval(&file).expect("Failed to evaluate");
+    let result = match options.eval.derivation {
+        true => thread::scope(|scope| {
+            thread::Builder::new()
+                .stack_size(DERIVE_STACK)
+                .spawn_scoped(scope, || evaluate::eval_with_options(&file, &options.eval))
+                .expect("failed to start a thread for --derive")
+                .join()
+                .unwrap()
+        }),
+        false => evaluate::eval_with_options(&file, &options.eval),
+    };
+
+    if let Err(error) = result {
+        let source = fs::read_to_string(&file).unwrap_or_default();
+
+        eprint!("{}", error.render(&source, use_colour()));
+        std::process::exit(1)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::*;
+
+    fn args(input: &[&str]) -> impl Iterator<Item = String> {
+        input
+            .iter()
+            .map(|arg| String::from(*arg))
+            .collect::<Vec<String>>()
+            .into_iter()
+    }
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(args(&["test.f"])).unwrap();
+
+        assert_eq!(options.file, "test.f");
+        assert_eq!(options.eval.parallel, None);
+        assert!(!options.eval.memo);
+
+        let options = parse_args(args(&["--jobs", "3", "test.f"])).unwrap();
+
+        assert_eq!(options.eval.parallel, Some(3));
+        assert!(!options.eval.graph);
+        assert!(parse_args(args(&["--memo", "test.f"])).unwrap().eval.memo);
+        assert!(parse_args(args(&["--jobs", "3", "--memo", "test.f"])).is_err());
+        assert!(parse_args(args(&["--parallel", "--dot", "tree", "test.f"])).is_err());
+        assert!(parse_args(args(&["--to-blc", "--parallel", "test.f"])).is_err());
+        assert!(parse_args(args(&["--graph", "test.f"])).unwrap().eval.graph);
+        assert!(parse_args(args(&["--ski", "test.f"])).unwrap().eval.combinators);
+        assert!(parse_args(args(&["--derive", "test.f"])).unwrap().eval.derivation);
+
+        let options = parse_args(args(&["--from-blc-bytes", "--to-blc", "t.blc"])).unwrap();
+
+        assert_eq!(options.eval.blc_input, Some(BlcFormat::Bytes));
+        assert_eq!(options.eval.blc_output, Some(BlcFormat::Text));
+        assert!(parse_args(args(&["--parallel"])).is_err());
+        assert!(parse_args(args(&["--jobs", "many", "test.f"])).is_err());
+        assert!(parse_args(args(&["--bogus", "test.f"])).is_err());
+
+        let options = parse_args(args(&["--max-size", "100", "--max-memory", "4096", "t.f"])).unwrap();
+
+        assert_eq!(options.eval.limits.max_term_size, Some(100));
+        assert_eq!(options.eval.limits.max_memory, Some(4096));
+        assert_eq!(options.eval.limits.max_binding_depth, None);
+        assert!(parse_args(args(&["--max-size", "100", "--derive", "t.f"])).is_err());
+        assert!(parse_args(args(&["--ski", "--max-depth", "9", "t.f"])).is_err());
+
+        let options = parse_args(args(&["--path", "lib", "--path", "/usr/lib/f", "t.f"])).unwrap();
+
+        assert_eq!(options.eval.search_path, [PathBuf::from("lib"), PathBuf::from("/usr/lib/f")]);
+        assert!(parse_args(args(&["t.f", "--path"])).is_err());
+        assert!(parse_args(args(&["--prelude", "t.f"])).unwrap().eval.prelude);
+
+        let options = parse_args(args(&["--literals", "church", "t.f"])).unwrap();
+
+        assert_eq!(options.eval.literals, Literals::Church);
+        assert!(parse_args(args(&["--literals", "roman", "t.f"])).is_err());
+
+        let options = parse_args(args(&["--from-sexp", "--to-json", "resolved", "t.s"])).unwrap();
+
+        assert_eq!(options.eval.ast_input, Some(AstFormat::Sexp));
+        assert_eq!(options.eval.ast_output, Some((AstFormat::Json, Stage::Resolved)));
+        assert!(parse_args(args(&["--to-sexp", "t.f"])).is_err());
+        assert_eq!(
+            parse_args(args(&["--dot", "reduction", "t.f"])).unwrap().eval.dot,
+            Some(DotGraph::Reduction)
+        );
+        assert!(parse_args(args(&["--dot", "t.f"])).is_err());
+        assert_eq!(
+            parse_args(args(&["--dot-nodes", "50", "t.f"])).unwrap().eval.dot_nodes,
+            Some(50)
+        );
+    }
+
+    #[test]
+    fn test_parse_fmt_args() {
+        let options = parse_fmt_args(args(&["--check", "--width", "60", "a.f", "b.f"])).unwrap();
+
+        assert!(options.check);
+        assert_eq!(options.style.width, 60);
+        assert_eq!(options.style.indent, 2);
+        assert_eq!(options.files, ["a.f", "b.f"]);
+        assert!(parse_fmt_args(args(&["--check"])).is_err());
+        assert!(parse_fmt_args(args(&["--width", "wide", "a.f"])).is_err());
+    }
 }
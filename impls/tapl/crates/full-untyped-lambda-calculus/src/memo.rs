@@ -0,0 +1,303 @@
+use crate::context::*;
+use crate::evaluate::*;
+use crate::syntax::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct MemoStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+}
+
+impl MemoStats {
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+/// Values of closed terms, keyed on their de Bruijn form so that
+/// α-equivalent terms share an entry.
+#[derive(Debug, Default, Clone)]
+pub struct Memo {
+    table: HashMap<String, Term>,
+    hits: usize,
+    misses: usize,
+}
+
+impl Memo {
+    pub fn stats(&self) -> MemoStats {
+        MemoStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.table.len(),
+        }
+    }
+}
+
+/// A key that is equal for two terms exactly when they are α-equivalent.
+/// Expects a term whose variables have been hydrated with de Bruijn indices.
+pub fn alpha_key(term: &Term) -> String {
+    fn walk(term: &Term, out: &mut String) {
+        match term {
+            Term::String(_, s) => write!(out, "{:?}", s).unwrap(),
+            Term::Var(_, var) => write!(out, "#{}", var.index).unwrap(),
+            Term::True(_) => out.push('T'),
+            Term::False(_) => out.push('F'),
+            Term::If(_, box t1, box t2, box t3) => {
+                out.push_str("(if ");
+                walk(t1, out);
+                out.push(' ');
+                walk(t2, out);
+                out.push(' ');
+                walk(t3, out);
+                out.push(')');
+            }
+            Term::Let(_, _, box t1, box t2) => {
+                out.push_str("(let ");
+                walk(t1, out);
+                out.push(' ');
+                walk(t2, out);
+                out.push(')');
+            }
+            Term::LetPattern(_, pattern, box t1, box t2) => {
+                fn shape(pattern: &Pattern, out: &mut String) {
+                    match pattern {
+                        Pattern::Var(_) => out.push('_'),
+                        Pattern::Record(fields) => {
+                            out.push('{');
+                            for (label, pattern) in fields {
+                                write!(out, "{}=", label).unwrap();
+                                shape(pattern, out);
+                                out.push(',');
+                            }
+                            out.push('}');
+                        }
+                    }
+                }
+
+                out.push_str("(let ");
+                shape(pattern, out);
+                out.push(' ');
+                walk(t1, out);
+                out.push(' ');
+                walk(t2, out);
+                out.push(')');
+            }
+            Term::Record(_, fields) => {
+                out.push('{');
+                for (name, box field) in fields {
+                    write!(out, "{}=", name).unwrap();
+                    walk(field, out);
+                    out.push(',');
+                }
+                out.push('}');
+            }
+            Term::Projection(_, box t1, name) => {
+                walk(t1, out);
+                write!(out, ".{}", name).unwrap();
+            }
+            Term::Abstraction(_, _, box t1) => {
+                out.push_str("(λ ");
+                walk(t1, out);
+                out.push(')');
+            }
+            Term::Application(_, box t1, box t2) => {
+                out.push('(');
+                walk(t1, out);
+                out.push(' ');
+                walk(t2, out);
+                out.push(')');
+            }
+            Term::Zero(_) => out.push('0'),
+            Term::Successor(_, box t1) => {
+                out.push('+');
+                walk(t1, out);
+            }
+            Term::Predecessor(_, box t1) => {
+                out.push('-');
+                walk(t1, out);
+            }
+            Term::IsZero(_, box t1) => {
+                out.push('?');
+                walk(t1, out);
+            }
+            Term::Float(_, flt) => write!(out, "{:?}", flt).unwrap(),
+            Term::TimesFloat(_, box t1, box t2) => {
+                out.push_str("(*f ");
+                walk(t1, out);
+                out.push(' ');
+                walk(t2, out);
+                out.push(')');
+            }
+            Term::Host(_, host, args) => {
+                write!(out, "(@{}", host.name).unwrap();
+                for arg in args {
+                    out.push(' ');
+                    walk(arg, out);
+                }
+                out.push(')');
+            }
+        }
+    }
+
+    let mut out = String::new();
+    walk(term, &mut out);
+
+    out
+}
+
+/// Whether `term` is closed and free of effects, so that its value can be
+/// reused wherever an α-equivalent term shows up.
+pub fn is_memoizable(term: &Term) -> bool {
+    fn walk(term: &Term, binders: i32) -> bool {
+        match term {
+            Term::Var(_, var) => var.index < binders,
+            Term::If(_, box t1, box t2, box t3) => {
+                walk(t1, binders) && walk(t2, binders) && walk(t3, binders)
+            }
+            Term::Let(_, _, box t1, box t2) => walk(t1, binders) && walk(t2, binders + 1),
+            Term::LetPattern(_, pattern, box t1, box t2) => {
+                walk(t1, binders) && walk(t2, binders + pattern.binders().len() as i32)
+            }
+            Term::Record(_, fields) => fields.iter().all(|(_, box t)| walk(t, binders)),
+            Term::Projection(_, box t1, _) => walk(t1, binders),
+            Term::Abstraction(_, _, box t1) => walk(t1, binders + 1),
+            Term::Application(_, box t1, box t2) | Term::TimesFloat(_, box t1, box t2) => {
+                walk(t1, binders) && walk(t2, binders)
+            }
+            Term::Successor(_, box t1) | Term::Predecessor(_, box t1) | Term::IsZero(_, box t1) => {
+                walk(t1, binders)
+            }
+            Term::Host(_, host, args) => {
+                !host.effectful && args.iter().all(|arg| walk(arg, binders))
+            }
+            Term::String(_, _)
+            | Term::True(_)
+            | Term::False(_)
+            | Term::Zero(_)
+            | Term::Float(_, _) => true,
+        }
+    }
+
+    walk(term, 0)
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn with_memo(mut self) -> Self {
+        self.memo = Some(Memo::default());
+        self
+    }
+
+    pub fn memo_stats(&self) -> Option<MemoStats> {
+        self.memo.as_ref().map(Memo::stats)
+    }
+
+    /// Evaluates `term` to its normal form, reusing the value of any
+    /// α-equivalent term evaluated before.
+    pub(crate) fn evaluate_memoized(
+        &mut self,
+        context: &Context,
+        term: &Term,
+    ) -> Result<Term, EvalError> {
+        let key = alpha_key(term);
+
+        if let Some(memo) = self.memo.as_mut() {
+            if let Some(value) = memo.table.get(&key) {
+                memo.hits += 1;
+                return Ok(value.clone());
+            }
+            memo.misses += 1;
+        }
+
+        let value = self.evaluate_top(context, term)?;
+
+        if let Some(memo) = self.memo.as_mut() {
+            memo.table.insert(key, value.clone());
+        }
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluate::*;
+    use crate::io::*;
+    use crate::memo::*;
+    use crate::parser::*;
+    use crate::test_util::hydrated;
+
+    #[test]
+    fn test_alpha_key() {
+        assert_eq!(
+            alpha_key(&hydrated("λx. λy. x y;")),
+            alpha_key(&hydrated("λa. λb. a b;"))
+        );
+        assert_ne!(
+            alpha_key(&hydrated("λx. λy. x y;")),
+            alpha_key(&hydrated("λx. λy. y x;"))
+        );
+    }
+
+    #[test]
+    fn test_is_memoizable() {
+        assert!(is_memoizable(&hydrated("(λx. x) (λy. y);")));
+        assert!(!is_memoizable(&hydrated("let x; λy. x;")));
+        assert!(!is_memoizable(&Term::Host(
+            FileInfo::default(),
+            crate::io::primitives().remove(0),
+            vec![]
+        )));
+    }
+
+    #[test]
+    fn test_memo_fibonacci() {
+        let input = include_str!("lambda-files/test7.f");
+        let (commands, context) = parse(input).expect("Parse error");
+        let terms: Vec<&Term> = commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::Eval(_, term) => Some(term),
+                _ => None,
+            })
+            .collect();
+
+        let mut io = MemoryIo::default();
+        let mut evaluator = Evaluator::new(&mut io).with_memo();
+        let values: Vec<Term> = terms
+            .iter()
+            .map(|term| evaluator.evaluate_command(&context, term).unwrap())
+            .collect();
+
+        assert_eq!(
+            values.without_locations(),
+            [1, 1, 2, 3, 5, 8, 13, 21].map(|n| Term::from_int(n, FileInfo::default()))
+        );
+
+        let stats = evaluator.memo_stats().unwrap();
+
+        assert!(stats.hits > 0);
+        assert!(stats.hit_rate() > 0.0);
+        assert!(stats.entries <= stats.misses);
+    }
+
+    #[test]
+    fn test_memo_keeps_effects() {
+        let mut context = Context::default();
+        context.register_io();
+        let mut io = MemoryIo::default();
+
+        let values = Evaluator::new(&mut io)
+            .with_memo()
+            .eval_source("(λx. λy. y) (print 1) (print 1);", context)
+            .expect("Failed to evaluate");
+
+        assert_eq!(values.without_locations(), [Term::from_int(1, FileInfo::default())]);
+        assert_eq!(io.output(), ["1", "1"]);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::context::*;
+use crate::desugar::DesugarError;
+use crate::diagnostics::Diagnostic;
+use crate::evaluate::hydrate_vars;
+use crate::fixity::Fixity;
+use crate::parser::parse_module;
+use crate::prelude;
+use crate::resolve::unbound;
+use crate::syntax::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The bindings and fixities a file declares, oldest first. A module's own
+/// evaluations are not run when it is imported.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub members: Vec<ContextMember>,
+    pub fixities: Vec<(String, Fixity)>,
+}
+
+/// Modules built into the interpreter, which take priority over files.
+const EMBEDDED: [(&str, &str); 1] = [("std", prelude::STD)];
+
+/// Loads the files `use` imports: relative to the importing file first,
+/// then from each directory of the search path. Each file is parsed once,
+/// in `base`, however many times it is imported.
+#[derive(Debug, Default)]
+pub struct Modules {
+    base: Context,
+    search_path: Vec<PathBuf>,
+    loaded: HashMap<PathBuf, Module>,
+    /// The files being parsed, outermost first, for finding cycles.
+    loading: Vec<PathBuf>,
+    /// Why the last import failed, kept so that a failure deep in a chain
+    /// of imports is reported with its original location.
+    failure: Option<Diagnostic>,
+    enabled: bool,
+}
+
+impl Modules {
+    pub fn new(base: &Context) -> Self {
+        Modules {
+            base: base.clone(),
+            enabled: true,
+            ..Modules::default()
+        }
+    }
+
+    /// Parses `use` without loading anything, so that no names are bound.
+    pub fn disabled() -> Self {
+        Modules::default()
+    }
+
+    pub fn with_search_path(mut self, search_path: &[PathBuf]) -> Self {
+        self.search_path = search_path.into();
+        self
+    }
+
+    pub(crate) fn enter(&mut self, filename: &str) {
+        self.loading
+            .push(fs::canonicalize(filename).unwrap_or_else(|_| PathBuf::from(filename)))
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.loading.pop();
+    }
+
+    /// Binds what `import` brings in at the end of `context`. Every binding
+    /// of the module is bound under its qualified name, `prefix.name`, and
+    /// the exposed ones again under their own name.
+    pub fn import(
+        &mut self,
+        info: &FileInfo,
+        import: &Import,
+        context: &mut Context,
+    ) -> Result<(), DesugarError> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        self.bind(info, import, context).map_err(|diagnostic| {
+            let elsewhere =
+                diagnostic.location.as_ref().map(FileInfo::filename) != Some(info.filename());
+            let message = match elsewhere {
+                true => format!("could not import \"{}\": {}", import.path, flatten(&diagnostic)),
+                false => diagnostic.message.clone(),
+            };
+
+            self.failure = Some(match elsewhere {
+                true => diagnostic.note(&format!("imported from {}", info)),
+                false => diagnostic,
+            });
+            DesugarError(info.clone(), message)
+        })
+    }
+
+    fn bind(
+        &mut self,
+        info: &FileInfo,
+        import: &Import,
+        context: &mut Context,
+    ) -> Result<(), Diagnostic> {
+        let path = self.resolve(info, &import.path)?;
+        let module = self.load(info, &path)?;
+        let prefix = import.alias.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .map_or(import.path.clone(), |stem| stem.to_string_lossy().into())
+        });
+        let own: Vec<String> = module.members.iter().map(|member| member.name.clone()).collect();
+        let exposed: Vec<String> = match &import.exposing {
+            Some(names) => names.clone(),
+            None => own.iter().fold(vec![], |mut names, name| {
+                if !names.contains(name) {
+                    names.push(name.clone())
+                }
+                names
+            }),
+        };
+
+        if let Some(missing) = exposed.iter().find(|name| !own.contains(name)) {
+            let message = format!("`{}` is not defined in {}", missing, path.display());
+
+            return Err(Diagnostic::error(&message).at(info.clone()));
+        }
+
+        let qualify = |name: &str| format!("{}.{}", prefix, name);
+
+        for ContextMember { name, binding } in &module.members {
+            let binding = match binding {
+                Binding::TermBind(box term) => {
+                    Binding::TermBind(box rename_free(term, &self.base, &own, &prefix))
+                }
+                Binding::NameBind => Binding::NameBind,
+            };
+
+            context.append_binding(ContextMember {
+                name: qualify(name),
+                binding,
+            });
+        }
+        for name in &exposed {
+            context.append_binding(ContextMember {
+                name: name.clone(),
+                binding: Binding::TermBind(box Term::Var(
+                    info.clone(),
+                    Var::new(&qualify(name), 0, 0),
+                )),
+            });
+        }
+        for (operator, fixity) in &module.fixities {
+            let hidden = own.contains(&fixity.function) && !exposed.contains(&fixity.function);
+            let function = match hidden {
+                true => qualify(&fixity.function),
+                false => fixity.function.clone(),
+            };
+
+            context.declare_fixity(operator, Fixity { function, ..fixity.clone() });
+        }
+
+        Ok(())
+    }
+
+    /// The file `path` names, as seen from the file `info` is in.
+    fn resolve(&self, info: &FileInfo, path: &str) -> Result<PathBuf, Diagnostic> {
+        if EMBEDDED.iter().any(|(name, _)| *name == path) {
+            return Ok(PathBuf::from(path));
+        }
+
+        let importer = Path::new(info.filename()).parent().unwrap_or(Path::new(""));
+        let directories: Vec<&Path> = [importer]
+            .into_iter()
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .collect();
+
+        directories
+            .iter()
+            .flat_map(|directory| {
+                let candidate = directory.join(path);
+                let with_extension = candidate.with_extension("f");
+
+                match candidate.extension() {
+                    Some(_) => vec![candidate],
+                    None => vec![candidate, with_extension],
+                }
+            })
+            .find(|candidate| candidate.is_file())
+            .and_then(|found| fs::canonicalize(found).ok())
+            .ok_or_else(|| {
+                let searched = directories
+                    .iter()
+                    .map(|directory| match directory.as_os_str().is_empty() {
+                        true => String::from("."),
+                        false => directory.display().to_string(),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                Diagnostic::error(&format!("cannot find \"{}\" (searched {})", path, searched))
+                    .at(info.clone())
+            })
+    }
+
+    fn load(&mut self, info: &FileInfo, path: &Path) -> Result<Module, Diagnostic> {
+        if let Some(module) = self.loaded.get(path) {
+            return Ok(module.clone());
+        }
+        if let Some(start) = self.loading.iter().position(|loading| loading == path) {
+            let cycle = self.loading[start..]
+                .iter()
+                .chain([path.to_path_buf()].iter())
+                .map(|path| path.display().to_string())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+
+            return Err(Diagnostic::error(&format!("import cycle {}", cycle)).at(info.clone()));
+        }
+
+        let filename = path.display().to_string();
+        let text = match EMBEDDED.iter().find(|(name, _)| Path::new(name) == path) {
+            Some((_, text)) => String::from(*text),
+            None => fs::read_to_string(path).map_err(|e| {
+                Diagnostic::error(&format!("could not read {}: {}", filename, e)).at(info.clone())
+            })?,
+        };
+
+        self.failure = None;
+        let (commands, context, errors) = parse_module(&filename, &text, self.base.clone(), self);
+        if let Some(error) = errors.first() {
+            return Err(self
+                .failure
+                .take()
+                .unwrap_or_else(|| Diagnostic::from_parse_error(&filename, &text, error)));
+        }
+        if let Some(diagnostic) = unbound(&commands, &context).into_iter().next() {
+            return Err(diagnostic);
+        }
+
+        let module = self.module_of(&context);
+        self.loaded.insert(path.to_path_buf(), module.clone());
+
+        Ok(module)
+    }
+
+    /// What `context` holds beyond `base`.
+    fn module_of(&self, context: &Context) -> Module {
+        let mut members: Vec<ContextMember> =
+            context.into_iter().take(context.len() - self.base.len()).cloned().collect();
+        let mut fixities: Vec<(String, Fixity)> = context
+            .fixities()
+            .iter()
+            .take(context.fixities().len() - self.base.fixities().len())
+            .map(|(operator, fixity)| (operator.into(), fixity.clone()))
+            .collect();
+
+        members.reverse();
+        fixities.reverse();
+
+        Module { members, fixities }
+    }
+}
+
+/// `term` with each free mention of one of `own`, bound after `base`,
+/// replaced by its qualified name.
+fn rename_free(term: &Term, base: &Context, own: &[String], prefix: &str) -> Term {
+    let names = own.iter().fold(base.names(), |context, name| context.add_name(name));
+    let own = own.len() as i32;
+
+    hydrate_vars(&names, term).visit(0, |(container_size, info, var)| {
+        let name = match var.index >= container_size && var.index - container_size < own {
+            true => format!("{}.{}", prefix, var.name),
+            false => var.name.clone(),
+        };
+
+        Term::Var(info.clone(), Var::new(&name, 0, 0))
+    })
+}
+
+/// A diagnostic as a single line, for reporting it where it was imported.
+fn flatten(diagnostic: &Diagnostic) -> String {
+    [match &diagnostic.location {
+        Some(location) => format!("{} at {}", diagnostic.message, location),
+        None => diagnostic.message.clone(),
+    }]
+    .into_iter()
+    .chain(diagnostic.notes.iter().cloned())
+    .collect::<Vec<String>>()
+    .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::*;
+    use crate::test_util::{values, TempDir};
+
+    fn directory(files: &[(&str, &str)]) -> TempDir {
+        let directory = TempDir::new();
+        for (file, text) in files {
+            directory.write(file, text);
+        }
+
+        directory
+    }
+
+    fn error(input: &str) -> String {
+        values(input).unwrap_err().diagnostics()[0].message.clone()
+    }
+
+    const BOOLEANS: &str = "let tru = λt f. t;
+let fls = λt f. f;
+let not b = b fls tru;
+let and b c = b c fls;
+infixr 3 && = and;
+";
+
+    #[test]
+    fn test_import() {
+        let dir = directory(&[("bool.f", BOOLEANS)]);
+        let lib = dir.path().join("bool.f").display().to_string();
+
+        assert_eq!(
+            values(&format!("use \"{}\";\nnot tru;\nbool.not fls;\ntru && not fls;", lib)).unwrap(),
+            ["λt f. f", "λt f. t", "λt f. t"]
+        );
+        assert_eq!(
+            values(&format!("use \"{}\" as b (not);\nnot b.tru;\nb.tru && b.fls;", lib)).unwrap(),
+            ["λt f. f", "λt f. f"]
+        );
+        assert_eq!(error(&format!("use \"{}\" as b;\nnot b.tru;", lib)), "`not` is not bound");
+        assert_eq!(
+            error(&format!("use \"{}\" (or);", lib)),
+            format!("`or` is not defined in {}", lib)
+        );
+    }
+
+    #[test]
+    fn test_nested_imports() {
+        let dir = directory(&[
+            ("a.f", "use \"b\";\nlet x = y;\n1;"),
+            ("b.f", "let y = λz. z;"),
+            ("c.f", "use \"d.f\";"),
+            ("d.f", "let w = 0;\nuse \"c.f\";"),
+            ("e.f", "use \"b.f\";\nlet v = w;"),
+        ]);
+        let path = |file: &str| dir.path().join(file).display().to_string();
+
+        assert_eq!(
+            values(&format!("use \"{}\";\nx;\na.b.y;", path("a.f"))).unwrap(),
+            ["λz. z", "λz. z"]
+        );
+        assert_eq!(
+            error(&format!("use \"{}\";", path("c.f"))),
+            format!(
+                "could not import \"{}\": import cycle {} -> {} -> {} at {}:2:1, \
+                 imported from {}:1:1",
+                path("c.f"),
+                path("c.f"),
+                path("d.f"),
+                path("c.f"),
+                path("d.f"),
+                path("c.f")
+            )
+        );
+        assert_eq!(
+            error(&format!("use \"{}\";", path("e.f"))),
+            format!("could not import \"{}\": `w` is not bound at {}:2:9", path("e.f"), path("e.f"))
+        );
+        assert_eq!(error("use \"missing\";"), "cannot find \"missing\" (searched .)");
+    }
+
+    #[test]
+    fn test_search_path() {
+        let dir = directory(&[("bool.f", BOOLEANS)]);
+        let mut modules =
+            Modules::new(&Context::default()).with_search_path(&[dir.path().to_path_buf()]);
+        let input = "use \"bool\" ();\nbool.not bool.tru;";
+        let (commands, context, errors) =
+            parse_module("", input, Context::default(), &mut modules);
+
+        assert!(errors.is_empty());
+        assert!(context.is_name_bound("bool.and") && !context.is_name_bound("and"));
+        assert_eq!(commands[0].to_string(), "use \"bool\" ();");
+    }
+}
@@ -0,0 +1,203 @@
+use crate::context::*;
+use crate::evaluate::*;
+use crate::io::*;
+use crate::syntax::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct CommandResult {
+    pub term: Term,
+    pub value: Result<Term, EvalError>,
+    pub output: Vec<String>,
+    pub elapsed: Duration,
+}
+
+/// The stack each worker gets: the main thread's on Linux, which serial
+/// evaluation runs on.
+const WORKER_STACK: usize = 8 << 20;
+
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// The first input primitive `term` reaches through the globals it uses.
+/// Commands get no input under `--parallel`, so such a term cannot run.
+pub fn reads_input(context: &Context, term: &Term) -> Option<String> {
+    let mut closed = close_over_globals(context, term);
+
+    while let Term::Let(_, _, box bound, box body) = closed {
+        match bound {
+            Term::Host(_, host, _) if ["read_line", "read_nat"].contains(&host.name.as_str()) => {
+                return Some(host.name)
+            }
+            _ => closed = body,
+        }
+    }
+
+    None
+}
+
+impl<'a> Evaluator<'a> {
+    /// Evaluates independent top-level terms on `jobs` threads.
+    ///
+    /// As in serial mode, each command evaluates only the global bindings it
+    /// mentions. Each command writes to its own buffer, returned in its
+    /// result so the caller can replay it next to the command's value.
+    /// Commands get no input, so `read_line` fails in parallel mode.
+    pub fn evaluate_parallel(
+        &mut self,
+        context: &Context,
+        terms: &[Term],
+        jobs: usize,
+    ) -> Vec<CommandResult> {
+        let next = AtomicUsize::new(0);
+        let slots: Vec<Mutex<Option<CommandResult>>> =
+            terms.iter().map(|_| Mutex::new(None)).collect();
+
+        thread::scope(|scope| {
+            for _ in 0..jobs.max(1).min(terms.len()) {
+                let worker = thread::Builder::new().stack_size(WORKER_STACK);
+
+                worker.spawn_scoped(scope, || loop {
+                    let idx = next.fetch_add(1, Ordering::SeqCst);
+                    let term = match terms.get(idx) {
+                        Some(term) => term,
+                        None => break,
+                    };
+                    let mut io = MemoryIo::default();
+                    let start = Instant::now();
+                    let value = Evaluator::new(&mut io)
+                        .with_limits(self.limits)
+                        .evaluate_command(context, term);
+
+                    *slots[idx].lock().unwrap() = Some(CommandResult {
+                        term: term.clone(),
+                        value,
+                        output: io.output().to_vec(),
+                        elapsed: start.elapsed(),
+                    });
+                })
+                .expect("failed to start a worker");
+            }
+        });
+
+        slots
+            .into_iter()
+            .map(|slot| slot.into_inner().unwrap().expect("command was not evaluated"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluate::*;
+    use crate::parallel::*;
+    use crate::parser::*;
+
+    fn eval_terms(commands: &[Command]) -> Vec<Term> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::Eval(_, term) => Some(term.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let input = r#"
+        let czero = λs. λz. z;
+        let scc = λn. λs. λz. s (n s z);
+        let plus = λm. λn. λs. λz. m s (n s z);
+        let times = λm. λn. m (plus n) czero;
+        let Y = λf. (λx. f(λy. x x y)) (λx. f(λy. x x y));
+        let cn = λfn. λn. if iszero n then czero else scc (fn (-n));
+        let churchnat = Y cn;
+        let realnat = λm. m (λx. + x) 0;
+        realnat (churchnat 3);
+        realnat (times (churchnat 2) (churchnat 3));
+        realnat (plus (churchnat 4) (churchnat 1));
+        realnat czero;
+        "#;
+
+        let (commands, context) = parse(input).expect("Parse error");
+        let terms = eval_terms(&commands);
+        let mut io = MemoryIo::default();
+        let results = Evaluator::new(&mut io)
+            .evaluate_parallel(&context, &terms, 4);
+
+        let serial: Vec<Term> = terms
+            .iter()
+            .map(|term| evaluate_command(&mut MemoryIo::default(), &context, term).unwrap())
+            .collect();
+        let parallel: Vec<Term> = results.into_iter().map(|r| r.value.unwrap()).collect();
+
+        assert_eq!(parallel.without_locations(), serial.without_locations());
+        assert_eq!(
+            parallel.without_locations(),
+            [3, 6, 5, 0].map(|n| Term::from_int(n, FileInfo::default()))
+        );
+    }
+
+    #[test]
+    fn test_parallel_preserves_output_order() {
+        let input = r#"
+        let Y = λf. (λx. f(λy. x x y)) (λx. f(λy. x x y));
+        let count = λfn. λn. if iszero n then 0 else (λu. fn (-n)) (print n);
+        Y count 5;
+        print 1;
+        Y count 2;
+        "#;
+
+        let mut context = Context::default();
+        context.register_io();
+        let (commands, context) = parse_with_context(input, context).expect("Parse error");
+        let mut io = MemoryIo::default();
+        let results = Evaluator::new(&mut io)
+            .evaluate_parallel(&context, &eval_terms(&commands), 3);
+
+        assert_eq!(results[0].output, ["5", "4", "3", "2", "1"]);
+        assert_eq!(results[1].output, ["1"]);
+        assert_eq!(results[2].output, ["2", "1"]);
+        assert!(io.output().is_empty());
+    }
+
+    #[test]
+    fn test_parallel_reads_input() {
+        let mut context = Context::default();
+        context.register_io();
+        let input = "let ask = λu. read_nat u; let shadow = λread_line. read_line; ask {}; shadow 1;";
+        let (commands, context) = parse_with_context(input, context).expect("Parse error");
+        let terms = eval_terms(&commands);
+
+        assert_eq!(reads_input(&context, &terms[0]), Some("read_nat".into()));
+        assert_eq!(reads_input(&context, &terms[1]), None);
+    }
+
+    #[test]
+    fn test_parallel_skips_unused_bindings() {
+        let input = r#"
+        let loop = (λx. x x) (λx. x x);
+        let shout = print 7;
+        let id = λx. x;
+        1;
+        id 2;
+        "#;
+
+        let mut context = Context::default();
+        context.register_io();
+        let (commands, context) = parse_with_context(input, context).expect("Parse error");
+        let results = Evaluator::new(&mut MemoryIo::default())
+            .evaluate_parallel(&context, &eval_terms(&commands), 2);
+
+        assert!(results.iter().all(|result| result.output.is_empty()));
+        assert_eq!(
+            results.into_iter().map(|r| r.value.unwrap()).collect::<Vec<_>>().without_locations(),
+            [1, 2].map(|n| Term::from_int(n, FileInfo::default()))
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use crate::syntax::Command;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CommentKind {
+    /// `-- ...` or `// ...`, up to the end of the line.
+    Line,
+    /// `/* ... */`, which may nest.
+    Block,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comment {
+    pub kind: CommentKind,
+    /// The full text of the comment, delimiters included.
+    pub text: String,
+    /// Byte offsets of the comment in the source.
+    pub span: (usize, usize),
+}
+
+/// A block comment still open at the end of input, and where it started.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct UnterminatedComment(pub usize);
+
+/// Finds the comments in `input` and returns a copy of it with every comment
+/// blanked out, along with the comments themselves.
+///
+/// LALRPOP's built-in lexer cannot skip nested comments, so this runs in front
+/// of it. Comment bytes are replaced with spaces and newlines are kept, so byte
+/// offsets and line numbers in the blanked copy match the original.
+///
+/// Comment markers inside strings are left alone. Anywhere else `--` starts a
+/// comment, even right before a term, so `pred (pred n)` is written `-(-n)`
+/// rather than `--n`.
+pub fn strip_comments(input: &str) -> (String, Vec<Comment>, Option<UnterminatedComment>) {
+    let bytes = input.as_bytes();
+    let mut out = bytes.to_vec();
+    let mut comments = vec![];
+    let mut unterminated = None;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let start = pos;
+        let kind = match &bytes[pos..] {
+            [b'-', b'-', ..] | [b'/', b'/', ..] => CommentKind::Line,
+            [b'/', b'*', ..] => CommentKind::Block,
+            [b'"', rest @ ..] => {
+                pos += match rest.iter().position(|byte| *byte == b'"') {
+                    Some(end) => end + 2,
+                    None => bytes.len() - pos,
+                };
+                continue;
+            }
+            _ => {
+                pos += 1;
+                continue;
+            }
+        };
+
+        match kind {
+            CommentKind::Line => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            }
+            CommentKind::Block => {
+                let mut depth = 0;
+                loop {
+                    match &bytes[pos..] {
+                        [b'/', b'*', ..] => {
+                            depth += 1;
+                            pos += 2;
+                        }
+                        [b'*', b'/', ..] => {
+                            depth -= 1;
+                            pos += 2;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        [_, ..] => pos += 1,
+                        [] => {
+                            unterminated = Some(UnterminatedComment(start));
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        for byte in &mut out[start..pos] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+        comments.push(Comment {
+            kind,
+            text: input[start..pos].into(),
+            span: (start, pos),
+        });
+    }
+
+    let blanked = String::from_utf8(out).expect("comments are blanked on character boundaries");
+
+    (blanked, comments, unterminated)
+}
+
+/// Groups comments with the command that follows them. A comment inside a
+/// command goes with that command; comments after the last command are
+/// returned separately.
+pub fn attach_comments(
+    commands: &[Command],
+    comments: &[Comment],
+) -> (Vec<Vec<Comment>>, Vec<Comment>) {
+    let mut attached: Vec<Vec<Comment>> = commands.iter().map(|_| vec![]).collect();
+    let mut trailing = vec![];
+
+    for comment in comments {
+        match commands
+            .iter()
+            .position(|command| command.info().span().1 > comment.span.0)
+        {
+            Some(idx) => attached[idx].push(comment.clone()),
+            None => trailing.push(comment.clone()),
+        }
+    }
+
+    (attached, trailing)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::comments::*;
+    use crate::parser::*;
+
+    #[test]
+    fn test_strip_comments() {
+        let input = "-- intro\nλx. x; // identity\n/* a /* nested */ λ */ 1;";
+        let (blanked, comments, unterminated) = strip_comments(input);
+
+        assert_eq!(blanked.len(), input.len());
+        assert_eq!(blanked.lines().count(), 3);
+        assert_eq!(
+            blanked.split_whitespace().collect::<Vec<&str>>(),
+            ["λx.", "x;", "1;"]
+        );
+        assert_eq!(
+            comments.iter().map(|c| c.text.as_str()).collect::<Vec<&str>>(),
+            ["-- intro", "// identity", "/* a /* nested */ λ */"]
+        );
+        assert_eq!(comments[2].kind, CommentKind::Block);
+        assert_eq!(&input[comments[1].span.0..comments[1].span.1], "// identity");
+        assert_eq!(unterminated, None);
+    }
+
+    #[test]
+    fn test_comment_markers_in_strings() {
+        for input in ["f \"/* hi */\";", "\"x // y\";", "\"-- a\" -- b"] {
+            let (blanked, comments, unterminated) = strip_comments(input);
+
+            assert_eq!(blanked.trim_end(), input.split(" -- ").next().unwrap());
+            assert!(comments.iter().all(|c| c.text == "-- b"));
+            assert_eq!(unterminated, None);
+        }
+
+        let (commands, _) = parse("\"x // y\";").expect("Failed to parse");
+
+        assert!(matches!(&commands[0], Command::Eval(_, Term::String(_, s)) if s == "x // y"));
+    }
+
+    #[test]
+    fn test_double_minus_is_a_comment() {
+        let (commands, _) = parse("let n = 2;\n--n;\n-(-n);").expect("Failed to parse");
+
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(&commands[1], Command::Eval(_, Term::Predecessor(..))));
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let (_, comments, unterminated) = strip_comments("1; /* /* */");
+
+        assert_eq!(unterminated, Some(UnterminatedComment(3)));
+        assert_eq!(comments[0].span, (3, 11));
+    }
+
+    #[test]
+    fn test_attach_comments() {
+        let input = "-- one\nlet x = 1;\n// two\nλy. /* inside */ y;\n-- end\n";
+        let (commands, _, errors) = parse_recovering("", input, Context::default());
+        let (_, comments, _) = strip_comments(input);
+        let (attached, trailing) = attach_comments(&commands, &comments);
+
+        assert!(errors.is_empty());
+        assert_eq!(attached[0][0].text, "-- one");
+        assert_eq!(
+            attached[1].iter().map(|c| c.text.as_str()).collect::<Vec<&str>>(),
+            ["// two", "/* inside */"]
+        );
+        assert_eq!(trailing[0].text, "-- end");
+    }
+}
@@ -0,0 +1,87 @@
+use crate::syntax::FileInfo;
+
+/// Byte offsets of the start of every line, for turning the offsets LALRPOP
+/// reports into lines and columns.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let starts = [0]
+            .into_iter()
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        LineIndex { starts }
+    }
+
+    /// The 1-based line and column of `offset`, counting columns in
+    /// characters.
+    pub fn position(&self, source: &str, offset: usize) -> (u32, u32) {
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let start = self.starts[line];
+        let column = source[start..offset.min(source.len())].chars().count();
+
+        (line as u32 + 1, column as u32 + 1)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// The text of 1-based `line`, without its line ending.
+    pub fn line<'s>(&self, source: &'s str, line: u32) -> &'s str {
+        let start = self.starts[line as usize - 1];
+        let end = self.starts.get(line as usize).copied().unwrap_or(source.len());
+
+        source[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+/// A source file being parsed.
+#[derive(Debug, Clone)]
+pub struct Source<'s> {
+    pub filename: String,
+    pub text: &'s str,
+    pub index: LineIndex,
+}
+
+impl<'s> Source<'s> {
+    pub fn new(filename: &str, text: &'s str) -> Self {
+        Source {
+            filename: filename.into(),
+            text,
+            index: LineIndex::new(text),
+        }
+    }
+
+    pub fn info(&self, start: usize, end: usize) -> FileInfo {
+        let (line, column) = self.index.position(self.text, start);
+
+        FileInfo::new(&self.filename, line, column).with_span(start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::location::*;
+
+    #[test]
+    fn test_line_index() {
+        let text = "let x = 1;\n\nλy. y;\n";
+        let index = LineIndex::new(text);
+
+        assert_eq!(index.position(text, 0), (1, 1));
+        assert_eq!(index.position(text, 4), (1, 5));
+        assert_eq!(index.position(text, 11), (2, 1));
+        assert_eq!(index.position(text, 12), (3, 1));
+        assert_eq!(index.position(text, 14), (3, 2));
+        assert_eq!(index.line(text, 3), "λy. y;");
+        assert_eq!(index.line_count(), 4);
+    }
+}
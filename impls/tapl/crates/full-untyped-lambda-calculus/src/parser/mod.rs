@@ -1,16 +1,176 @@
-lalrpop_mod!(pub parser, "/parser/parser.rs"); // synthesized by LALRPOP
+lalrpop_mod!(#[allow(clippy::all)] pub parser, "/parser/parser.rs"); // synthesized by LALRPOP
+pub mod comments;
+pub mod location;
 
+use crate::desugar::DesugarError;
+use crate::modules::Modules;
+use crate::resolve::resolve;
+use crate::surface::Statement;
 use lalrpop_util::lexer::Token;
 use lalrpop_util::ParseError;
 
 pub use crate::context::Context;
 pub use crate::syntax::*;
 
-pub fn parse(input: &str) -> Result<(Vec<Command>, Context), ParseError<usize, Token<'_>, &str>> {
-    let mut context = Context::default();
-    let result = parser::TopLevelParser::new().parse(&mut context, input)?;
+pub type SyntaxError<'i> = ParseError<usize, Token<'i>, DesugarError>;
 
-    Ok((result, context))
+/// Words that lex as keywords rather than names.
+pub const KEYWORDS: [&str; 16] = [
+    "let", "in", "if", "then", "else", "true", "false", "iszero", "timesfloat", "lambda", "use",
+    "as", "where", "infixl", "infixr", "infix",
+];
+
+/// The characters operators are made of, as in `OperatorSymbol`.
+pub const OPERATOR_CHARS: &str = "!$%&*+-/<=>?@^|~:";
+
+pub fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+pub fn parse(input: &str) -> Result<(Vec<Command>, Context), SyntaxError<'_>> {
+    parse_with_context(input, Context::default())
+}
+
+pub fn parse_with_context(
+    input: &str,
+    context: Context,
+) -> Result<(Vec<Command>, Context), SyntaxError<'_>> {
+    parse_file("", input, context)
+}
+
+/// Parses `input`, recording `filename` in the `FileInfo` of every term.
+/// Fails with the first syntax error.
+pub fn parse_file<'i>(
+    filename: &str,
+    input: &'i str,
+    context: Context,
+) -> Result<(Vec<Command>, Context), SyntaxError<'i>> {
+    match parse_recovering(filename, input, context) {
+        (commands, context, errors) if errors.is_empty() => Ok((commands, context)),
+        (_, _, mut errors) => Err(errors.remove(0)),
+    }
+}
+
+/// Parses `input`, skipping to the next `;` after a syntax error. Returns
+/// the commands that did parse along with every error, in source order.
+/// Imports are not loaded.
+pub fn parse_recovering<'i>(
+    filename: &str,
+    input: &'i str,
+    context: Context,
+) -> (Vec<Command>, Context, Vec<SyntaxError<'i>>) {
+    parse_module(filename, input, context, &mut Modules::disabled())
+}
+
+/// Like `parse_recovering`, binding what each `use` imports through
+/// `modules` before resolving the commands after it.
+pub fn parse_module<'i>(
+    filename: &str,
+    input: &'i str,
+    mut context: Context,
+    modules: &mut Modules,
+) -> (Vec<Command>, Context, Vec<SyntaxError<'i>>) {
+    let (statements, mut errors) = parse_statements(filename, input);
+
+    modules.enter(filename);
+    let (commands, resolve_errors) = resolve(statements, &mut context, modules);
+    modules.leave();
+
+    errors.extend(resolve_errors.into_iter().map(|error| ParseError::User { error }));
+    errors.sort_by_key(offset);
+
+    (commands, context, errors)
+}
+
+/// Parses `input` into statements without resolving anything, skipping to
+/// the next `;` after a syntax error.
+pub fn parse_statements<'i>(
+    filename: &str,
+    input: &'i str,
+) -> (Vec<Statement>, Vec<SyntaxError<'i>>) {
+    let source = location::Source::new(filename, input);
+    let (blanked, comments, unterminated) = comments::strip_comments(input);
+    let mut recovered = vec![];
+    let result = parser::TopLevelParser::new().parse(&source, &mut recovered, &blanked);
+    let mut errors: Vec<SyntaxError<'i>> = recovered
+        .into_iter()
+        .map(|e| reborrow_error(e.error, input))
+        .collect();
+
+    let statements = match result {
+        Ok(statements) => statements,
+        Err(error) => {
+            errors.push(reborrow_error(error, input));
+            vec![]
+        }
+    };
+
+    errors.extend(comments.iter().filter_map(|comment| comment_in_operator(&source, comment)));
+    if let Some(comments::UnterminatedComment(start)) = unterminated {
+        errors.push(ParseError::UnrecognizedEOF {
+            location: start,
+            expected: vec![String::from("\"*/\"")],
+        });
+    }
+
+    (statements, errors)
+}
+
+/// An error for a comment starting right after an operator character, as
+/// in `<--`, which would otherwise silently cut the operator short.
+fn comment_in_operator<'i>(
+    source: &location::Source,
+    comment: &comments::Comment,
+) -> Option<SyntaxError<'i>> {
+    let is_operator = |c: char| OPERATOR_CHARS.contains(c);
+    let (start, _) = comment.span;
+    let before = source.text[..start].trim_end_matches(is_operator).len();
+    if before == start {
+        return None;
+    }
+
+    let rest = &source.text[start + 2..];
+    let end = source.text.len() - rest.trim_start_matches(is_operator).len();
+    let message = format!(
+        "`{}` cannot be an operator, as `{}` starts a comment",
+        &source.text[before..end],
+        &comment.text[..2]
+    );
+
+    Some(ParseError::User {
+        error: DesugarError(source.info(before, end), message),
+    })
+}
+
+/// Where in the input `error` is, for putting errors in source order.
+fn offset(error: &SyntaxError) -> usize {
+    match error {
+        ParseError::InvalidToken { location } | ParseError::UnrecognizedEOF { location, .. } => {
+            *location
+        }
+        ParseError::UnrecognizedToken { token: (l, _, _), .. }
+        | ParseError::ExtraToken { token: (l, _, _) } => *l,
+        ParseError::User { error: DesugarError(info, _) } => info.span().0,
+    }
+}
+
+/// Points the tokens of an error from parsing the comment-free copy of
+/// `input` back at `input` itself; the two agree outside comments.
+fn reborrow_error<'i>(error: ParseError<usize, Token<'_>, DesugarError>, input: &'i str) -> SyntaxError<'i> {
+    let token = |(l, Token(n, _), r): (usize, Token, usize)| (l, Token(n, &input[l..r]), r);
+
+    match error {
+        ParseError::InvalidToken { location } => ParseError::InvalidToken { location },
+        ParseError::UnrecognizedEOF { location, expected } => {
+            ParseError::UnrecognizedEOF { location, expected }
+        }
+        ParseError::UnrecognizedToken { token: t, expected } => ParseError::UnrecognizedToken {
+            token: token(t),
+            expected,
+        },
+        ParseError::ExtraToken { token: t } => ParseError::ExtraToken { token: token(t) },
+        ParseError::User { error } => ParseError::User { error },
+    }
 }
 
 #[cfg(test)]
@@ -32,7 +192,10 @@ mod tests {
     fn test_parser_import() {
         let (commands, _) = parser::parse("use \"std\";").expect("");
 
-        assert_eq!(commands, [Command::Import("std".into())]);
+        assert_eq!(
+            commands.without_locations(),
+            [Command::Import(FileInfo::default(), "std".into())]
+        );
     }
 
     #[test]
@@ -46,10 +209,10 @@ mod tests {
         .expect("");
 
         assert_eq!(
-            commands,
+            commands.without_locations(),
             [
-                Command::Import("std".into()),
-                Command::Import("bazinga".into())
+                Command::Import(FileInfo::default(), "std".into()),
+                Command::Import(FileInfo::default(), "bazinga".into())
             ]
         );
     }
@@ -59,7 +222,7 @@ mod tests {
         let (commands, context) = parser::parse("let x;").expect("");
 
         assert_eq!(
-            commands,
+            commands.without_locations(),
             [Command::Bind(
                 FileInfo::default(),
                 "x".into(),
@@ -71,10 +234,10 @@ mod tests {
 
     #[test]
     fn test_bind_term() {
-        let (commands, context) = parser::parse("let x = if true then false else true;").expect("");
+        let (commands, _context) = parser::parse("let x = if true then false else true;").expect("");
 
         assert_eq!(
-            commands,
+            commands.without_locations(),
             [Command::Bind(
                 FileInfo::default(),
                 "x".into(),
@@ -88,6 +251,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_identifiers() {
+        let (commands, _) = parser::parse("x1; is_zero; f''; _; λβ. β; letter; lambda_; inner;").unwrap();
+        let names: Vec<String> = commands
+            .iter()
+            .map(|command| match command {
+                Command::Eval(_, Term::Var(_, var)) => var.name.clone(),
+                Command::Eval(_, Term::Abstraction(_, name, _)) => name.clone(),
+                _ => panic!(),
+            })
+            .collect();
+
+        assert_eq!(names, ["x1", "is_zero", "f''", "_", "β", "letter", "lambda_", "inner"]);
+        assert!(parser::parse("let in = 1;").is_err());
+    }
+
+    #[test]
+    fn test_comment_in_operator() {
+        let input = "let f = λx y. x;\ninfixl 5 <-- = f;\n1 +// one\n;";
+        let (_, _, errors) = parser::parse_recovering("", input, Context::default());
+        let messages: Vec<String> = errors
+            .iter()
+            .filter_map(|error| match error {
+                ParseError::User { error } => Some(error.1.clone()),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            messages,
+            [
+                "`<--` cannot be an operator, as `--` starts a comment",
+                "`+//` cannot be an operator, as `//` starts a comment",
+            ]
+        );
+        assert!(parser::parse("infixl 5 <+> = f; ---- banner\n1; /** doc */").is_ok());
+    }
+
+    #[test]
+    fn test_strings() {
+        let (commands, _) = parser::parse("\"hello, world\"; \"\"; \"read_line {}\";").unwrap();
+        let strings: Vec<String> = commands
+            .iter()
+            .map(|command| match command {
+                Command::Eval(_, Term::String(_, s)) => s.clone(),
+                _ => panic!(),
+            })
+            .collect();
+
+        assert_eq!(strings, ["hello, world", "", "read_line {}"]);
+        assert!(parser::parse("\"unterminated;").is_err());
+    }
+
     #[test]
     fn test_lambda() {
         let expectation = [Command::Eval(
@@ -100,12 +316,12 @@ mod tests {
         )];
 
         let input = r#"λ x.x;"#;
-        let (commands, context) = parser::parse(input).unwrap();
-        assert_eq!(commands, expectation);
+        let (commands, _context) = parser::parse(input).unwrap();
+        assert_eq!(commands.without_locations(), expectation);
 
         let input = r#"lambda x.x;"#;
-        let (commands, context) = parser::parse(input).unwrap();
-        assert_eq!(commands, expectation);
+        let (commands, _context) = parser::parse(input).unwrap();
+        assert_eq!(commands.without_locations(), expectation);
     }
 
     #[test]
@@ -128,8 +344,8 @@ mod tests {
         )];
 
         let input = r#"λ x. λ y. y x;"#;
-        let (commands, context) = parser::parse(input).unwrap();
-        assert_eq!(commands, expectation);
+        let (commands, _context) = parser::parse(input).unwrap();
+        assert_eq!(commands.without_locations(), expectation);
     }
 
     #[test]
@@ -147,7 +363,7 @@ mod tests {
         let (commands, _) = parser::parse(input).expect("");
 
         assert_eq!(
-            commands,
+            commands.without_locations(),
             [
                 Command::Bind(
                     FileInfo::default(),
@@ -197,6 +413,101 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_file_info() {
+        let input = "let x = 1;\n\n  λy. iszero y;";
+        let (commands, _) = parser::parse_file("test.f", input, Context::default()).unwrap();
+
+        let (info, term) = match &commands[1] {
+            Command::Eval(info, term) => (info, term),
+            _ => panic!(),
+        };
+        assert_eq!((info.line(), info.column()), (3, 3));
+        assert_eq!(format!("{}", info), "test.f:3:3");
+        assert_eq!(&input[info.span().0..info.span().1], "λy. iszero y");
+
+        match term {
+            Term::Abstraction(_, _, box Term::IsZero(info, box Term::Var(var_info, _))) => {
+                assert_eq!(format!("{}", info), "test.f:3:7");
+                assert_eq!(&input[var_info.span().0..var_info.span().1], "y");
+                assert_eq!(var_info.column(), 14);
+            }
+            _ => panic!(),
+        }
+
+        match &commands[0] {
+            Command::Bind(info, _, Binding::TermBind(box one)) => {
+                assert_eq!(format!("{}", info), "test.f:1:1");
+                assert!(matches!(one, Term::Successor(one_info, _) if one_info.column() == 9));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let input = "let x = 1;\nλy. );\nlet z = (;\ntrue;";
+        let (commands, context, errors) = parser::parse_recovering("", input, Context::default());
+
+        assert_eq!(
+            commands.without_locations(),
+            [
+                Command::Bind(
+                    FileInfo::default(),
+                    "x".into(),
+                    Binding::TermBind(Box::new(Term::from_int(1, FileInfo::default())))
+                ),
+                Command::Eval(FileInfo::default(), Term::True(FileInfo::default()))
+            ]
+        );
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            errors[0],
+            ParseError::UnrecognizedToken { token: (16, Token(_, ")"), _), .. }
+        ));
+        assert!(matches!(
+            errors[1],
+            ParseError::UnrecognizedToken { token: (28, Token(_, ";"), _), .. }
+        ));
+        assert!(context.is_name_bound("x"));
+        assert!(parser::parse(input).is_err());
+    }
+
+    #[test]
+    fn test_error_recovery_at_end_of_input() {
+        let input = "let x = 1;\nx;\nx";
+        let (commands, context, errors) = parser::parse_recovering("", input, Context::default());
+
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(commands[1], Command::Eval(..)));
+        assert!(matches!(errors[..], [ParseError::UnrecognizedEOF { location: 15, .. }]));
+        assert!(context.is_name_bound("x"));
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = r#"
+        -- Church booleans
+        let tru = λt. λf. t; // true
+        /* false, /* nested */ and
+           spread over lines */
+        let fls = λt. λf. f;
+        tru -- trailing
+          fls;
+        "#;
+        let (commands, _) = parser::parse(input).expect("Failed to parse");
+
+        assert_eq!(commands.len(), 3);
+        assert!(matches!(&commands[2], Command::Eval(info, _) if info.line() == 7));
+
+        let (_, _, errors) = parser::parse_recovering("", "λx. x; /* open", Context::default());
+
+        assert!(matches!(
+            &errors[..],
+            [ParseError::UnrecognizedEOF { location: 8, expected }] if expected == &["\"*/\""]
+        ));
+    }
+
     #[test]
     fn test_record() {
         let input = r#"
@@ -209,7 +520,7 @@ mod tests {
         let (commands, _) = parser::parse(input).expect("Failed to parse");
 
         assert_eq!(
-            commands,
+            commands.without_locations(),
             [Command::Bind(
                 FileInfo::default(),
                 "x".into(),
@@ -229,4 +540,28 @@ mod tests {
             )]
         )
     }
+
+    #[test]
+    fn test_negative_floats() {
+        let parse = |input: &str| {
+            let (commands, _) = parser::parse(input).expect("Failed to parse");
+
+            commands.without_locations()
+        };
+        let x = || Box::new(Term::Var(FileInfo::default(), Var::new("x", 0, 0)));
+
+        assert_eq!(parse("let x; x -1.0;"), parse("let x; x - 1.0;"));
+        assert_ne!(parse("let x; x ( - 1.0);"), parse("let x; x (-1.0);"));
+        assert_eq!(
+            parse("let x; x (-1.0);")[1],
+            Command::Eval(
+                FileInfo::default(),
+                Term::Application(
+                    FileInfo::default(),
+                    x(),
+                    Box::new(Term::Float(FileInfo::default(), -1.0))
+                )
+            )
+        );
+    }
 }
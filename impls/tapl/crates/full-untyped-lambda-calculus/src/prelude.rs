@@ -0,0 +1,116 @@
+/// The source of the `std` module, which `use "std"` loads without looking
+/// for a file.
+pub const STD: &str = include_str!("prelude/std.f");
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util;
+
+    fn values(input: &str) -> Vec<String> {
+        test_util::values(&format!("use \"std\";\n{}", input)).unwrap()
+    }
+
+    #[test]
+    fn test_booleans() {
+        assert_eq!(
+            values("realbool tru; realbool fls; test tru 1 2; test fls 1 2;"),
+            ["true", "false", "1", "2"]
+        );
+        assert_eq!(
+            values("realbool (and tru fls); realbool (or fls tru); realbool (not tru);"),
+            ["false", "true", "false"]
+        );
+    }
+
+    #[test]
+    fn test_pairs() {
+        assert_eq!(values("fst (pair 1 2); snd (pair 1 2);"), ["1", "2"]);
+    }
+
+    #[test]
+    fn test_church_numerals() {
+        assert_eq!(
+            values(
+                "realnat czero; realnat cone; realnat ctwo; realnat (scc ctwo);
+                 realnat (plus ctwo ctwo); realnat (times ctwo (scc ctwo));
+                 realnat (power ctwo (scc ctwo));"
+            ),
+            ["0", "1", "2", "3", "4", "6", "8"]
+        );
+        assert_eq!(
+            values(
+                "realnat (prd ctwo); realnat (prd czero); realnat (minus (churchnat 5) ctwo);
+                 realbool (iszro czero); realbool (iszro cone);"
+            ),
+            ["1", "0", "3", "true", "false"]
+        );
+        assert_eq!(
+            values(
+                "realbool (leq cone ctwo); realbool (leq ctwo cone);
+                 realeq ctwo ctwo; realeq cone ctwo;"
+            ),
+            ["true", "false", "true", "false"]
+        );
+        assert_eq!(values("realbool (equal (churchnat 2) ctwo);"), ["true"]);
+    }
+
+    #[test]
+    fn test_scott_numerals() {
+        assert_eq!(
+            values(
+                "realscott szero; realscott (ssucc (ssucc szero)); realscott (spred (scottnat 3));
+                 realbool (siszero szero); realbool (siszero (scottnat 1));"
+            ),
+            ["0", "2", "2", "true", "false"]
+        );
+    }
+
+    #[test]
+    fn test_fixed_points() {
+        let double = "λf n. if iszero n then 0 else + (+ (f (- n)))";
+
+        assert_eq!(values(&format!("Y ({}) 3; fix ({}) 0;", double, double)), ["6", "0"]);
+    }
+
+    #[test]
+    fn test_lists() {
+        let list = "(cons 1 (cons 2 (cons 3 nil)))";
+
+        assert_eq!(
+            values(&format!(
+                "head {l}; head (tail {l}); realnat (length {l}); realbool (isnil nil);
+                 realbool (isnil {l}); foldr (λh t. + t) 0 {l};",
+                l = list
+            )),
+            ["1", "2", "3", "true", "false", "3"]
+        );
+        assert_eq!(
+            values(&format!(
+                "reallist (λx. x) (map (λx. + x) {l});
+                 reallist realnat (append (cons cone nil) nil);",
+                l = list
+            )),
+            [
+                "{ head = 2, tail = { head = 3, tail = { head = 4, tail = {} } } }",
+                "{ head = 1, tail = {} }"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(
+            values("realbool (churchbool false); realnat (churchnat 4); realscott (scottnat 4);"),
+            ["false", "4", "4"]
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(
+            values("#literals church; realnat (times 3 4); realeq (plus 2 2) 4;"),
+            ["12", "true"]
+        );
+        assert_eq!(values("#literals unary; realnat (power 2 3);"), ["8"]);
+    }
+}
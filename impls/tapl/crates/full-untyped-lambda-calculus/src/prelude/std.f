@@ -0,0 +1,66 @@
+-- The standard prelude: Church encodings from TAPL §5.2 and a few more,
+-- loaded by `use "std";` or `--prelude`.
+
+-- Booleans
+let tru t f = t;
+let fls t f = f;
+let test b t f = b t f;
+let and b c = b c fls;
+let or b c = b tru c;
+let not b = b fls tru;
+
+-- Pairs
+let pair f s b = b f s;
+let fst p = p tru;
+let snd p = p fls;
+
+-- Church numerals
+let czero s z = z;
+let scc n s z = s (n s z);
+let cone = scc czero;
+let ctwo = scc cone;
+let plus m n s z = m s (n s z);
+let times m n = m (plus n) czero;
+let power m n = n (times m) cone;
+let iszro m = m (λx. fls) tru;
+let zz = pair czero czero;
+let ss p = pair (snd p) (plus cone (snd p));
+let prd m = fst (m ss zz);
+let minus m n = n prd m;
+let leq m n = iszro (minus m n);
+let equal m n = and (leq m n) (leq n m);
+
+-- What `2` means after `#literals unary`
+let zero = czero;
+let succ = scc;
+
+-- Scott numerals, whose predecessor takes one step
+let szero s z = z;
+let ssucc n s z = s n;
+let spred n = n (λp. p) szero;
+let siszero n = n (λp. fls) tru;
+
+-- Fixed points, for call-by-value
+let Y f = (λx. f (λy. x x y)) (λx. f (λy. x x y));
+let fix = Y;
+
+-- Lists, as their right folds
+let nil c n = n;
+let cons h t c n = c h (t c n);
+let isnil l = l (λh t. fls) tru;
+let head l = l (λh t. h) fls;
+let tail l = fst (l (λh p. pair (snd p) (cons h (snd p))) (pair nil nil));
+let map f l = l (λh t. cons (f h) t) nil;
+let foldr f z l = l f z;
+let length l = l (λh t. scc t) czero;
+let append l m = l cons m;
+
+-- Conversions to and from the built-in values
+let realbool b = b true false;
+let churchbool b = if b then tru else fls;
+let realnat m = m (λx. + x) 0;
+let churchnat = Y (λf n. if iszero n then czero else scc (f (- n)));
+let realeq m n = realbool (equal m n);
+let scottnat = Y (λf n. if iszero n then szero else ssucc (f (- n)));
+let realscott = Y (λf n. n (λp. + (f p)) 0);
+let reallist f l = l (λh t. { head = f h, tail = t }) {};
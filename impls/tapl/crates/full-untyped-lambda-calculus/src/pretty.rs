@@ -0,0 +1,185 @@
+use std::ops::Add;
+
+/// A document in the style of Wadler's "A prettier printer": text with
+/// places it may break, laid out as flat as the width allows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Doc {
+    Nil,
+    Text(String),
+    /// A space when its group fits on the line, otherwise a newline followed
+    /// by the current indentation.
+    Line,
+    Concat(Box<Doc>, Box<Doc>),
+    Nest(usize, Box<Doc>),
+    /// Laid out flat if all of it fits in the rest of the line.
+    Group(Box<Doc>),
+}
+
+/// How wide and how deeply indented printed terms are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub width: usize,
+    pub indent: usize,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            width: 80,
+            indent: 2,
+        }
+    }
+}
+
+impl Style {
+    /// Everything on one line.
+    pub fn flat() -> Self {
+        Style {
+            width: usize::MAX,
+            ..Style::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+pub fn text(s: impl Into<String>) -> Doc {
+    Doc::Text(s.into())
+}
+
+pub fn line() -> Doc {
+    Doc::Line
+}
+
+pub fn nest(indent: usize, doc: Doc) -> Doc {
+    Doc::Nest(indent, box doc)
+}
+
+pub fn group(doc: Doc) -> Doc {
+    Doc::Group(box doc)
+}
+
+/// `docs` with `separator` between each of them.
+pub fn join(docs: impl IntoIterator<Item = Doc>, separator: Doc) -> Doc {
+    docs.into_iter().enumerate().fold(Doc::Nil, |joined, (i, doc)| match i {
+        0 => doc,
+        _ => joined + separator.clone() + doc,
+    })
+}
+
+impl Add for Doc {
+    type Output = Doc;
+
+    fn add(self, other: Doc) -> Doc {
+        match (self, other) {
+            (Doc::Nil, doc) | (doc, Doc::Nil) => doc,
+            (left, right) => Doc::Concat(box left, box right),
+        }
+    }
+}
+
+impl Doc {
+    pub fn render(&self, width: usize) -> String {
+        let mut out = String::new();
+        let mut column = 0;
+        let mut stack = vec![(0, Mode::Break, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Nil => {}
+                Doc::Text(s) => {
+                    out.push_str(s);
+                    column += s.chars().count();
+                }
+                Doc::Line if mode == Mode::Flat => {
+                    out.push(' ');
+                    column += 1;
+                }
+                Doc::Line => {
+                    out.push('\n');
+                    out.push_str(&" ".repeat(indent));
+                    column = indent;
+                }
+                Doc::Concat(box left, box right) => {
+                    stack.push((indent, mode, right));
+                    stack.push((indent, mode, left));
+                }
+                Doc::Nest(i, box doc) => stack.push((indent + i, mode, doc)),
+                Doc::Group(box doc) => {
+                    let room = width.saturating_sub(column);
+                    let flat = mode == Mode::Flat || fits(room, doc, &stack);
+                    let mode = if flat { Mode::Flat } else { Mode::Break };
+                    stack.push((indent, mode, doc));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Whether `doc` laid out flat, and whatever follows it up to the next
+/// line break, takes at most `width` columns.
+fn fits(width: usize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut width = width;
+    let mut pending = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (mode, doc) = match pending.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Nil => {}
+            Doc::Text(s) => match width.checked_sub(s.chars().count()) {
+                Some(left) => width = left,
+                None => return false,
+            },
+            Doc::Line if mode == Mode::Break => return true,
+            Doc::Line => match width.checked_sub(1) {
+                Some(left) => width = left,
+                None => return false,
+            },
+            Doc::Concat(box left, box right) => {
+                pending.push((mode, right));
+                pending.push((mode, left));
+            }
+            Doc::Nest(_, box doc) => pending.push((mode, doc)),
+            Doc::Group(box doc) => pending.push((mode, doc)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pretty::*;
+
+    #[test]
+    fn test_render() {
+        let call = |name: &str, args: &[&str]| {
+            let args = args.iter().fold(Doc::Nil, |doc, arg| doc + line() + text(*arg));
+
+            group(text(name) + nest(2, args))
+        };
+        let doc = call("f", &["a", "b"]) + text(";");
+
+        assert_eq!(doc.render(80), "f a b;");
+        assert_eq!(doc.render(6), "f a b;");
+        assert_eq!(doc.render(5), "f\n  a\n  b;");
+
+        let items = join(vec![text("x"), call("g", &["y"])], text(",") + line());
+        let list = group(text("[") + items + text("]"));
+
+        assert_eq!(list.render(10), "[x, g y]");
+        assert_eq!(list.render(5), "[x,\ng y]");
+    }
+}
@@ -1,51 +1,551 @@
 use crate::context::*;
+use crate::evaluate::is_numeric;
+use crate::fixity::{Assoc, Fixities, Fixity};
+use crate::pretty::*;
+use crate::surface::*;
 use crate::syntax::*;
 use std::fmt;
 
-impl fmt::Display for Term {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self {
-            Term::String(_, s) => write!(f, "{}", s),
-            Term::Var(_, Var { name, .. }) => write!(f, "{}", name),
-            Term::True(_) => write!(f, "true"),
-            Term::False(_) => write!(f, "false"),
-            Term::If(_, box t1, box t2, box t3) => {
-                write!(f, "if {} then {} else {}", t1, t2, t3)
+/// A term printed with the operators in its fixities written infix.
+pub struct WithFixities<'a>(&'a Term, &'a Fixities);
+
+impl Term {
+    pub fn with_fixities<'a>(&'a self, fixities: &'a Fixities) -> WithFixities<'a> {
+        WithFixities(self, fixities)
+    }
+
+    /// The term laid out in `style`, with the operators in `fixities`
+    /// written infix, in a form that parses back to the same term.
+    pub fn pretty(&self, fixities: &Fixities, style: &Style) -> String {
+        Printer::new(fixities, style).doc(self, Prec::Expr).render(style.width)
+    }
+}
+
+impl Command {
+    /// The command as it would be written in a file, see `Term::pretty`.
+    pub fn pretty(&self, fixities: &Fixities, style: &Style) -> String {
+        Printer::new(fixities, style).command(self).render(style.width)
+    }
+}
+
+impl Statement {
+    /// The statement laid out in `style` just as it was written, down to
+    /// its literals, infix operators and `where` clauses.
+    pub fn pretty(&self, style: &Style) -> String {
+        let fixities = Fixities::default();
+
+        Printer::new(&fixities, style).statement(self).render(style.width)
+    }
+}
+
+/// How loosely a term binds, loosest first. A term can stand unbracketed
+/// wherever the grammar expects its own kind or a looser one.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+enum Prec {
+    /// `t where x = a`, which only stands at the top of a statement.
+    Where,
+    /// Abstractions, `let` and `if`, which extend as far right as they can.
+    Expr,
+    Infix,
+    /// Applications and the built-in prefix functions.
+    App,
+    /// Names, literals, records and projections.
+    Atom,
+}
+
+struct Printer<'a> {
+    fixities: &'a Fixities,
+    indent: usize,
+    /// Names bound by enclosing abstractions and lets, which shadow the
+    /// functions operators stand for.
+    bound: Vec<String>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(fixities: &'a Fixities, style: &Style) -> Self {
+        Printer {
+            fixities,
+            indent: style.indent,
+            bound: vec![],
+        }
+    }
+
+    fn under(&mut self, names: &[&str], term: &Term) -> Doc {
+        self.bound.extend(names.iter().map(|name| String::from(*name)));
+        let doc = self.doc(term, Prec::Expr);
+        self.bound.truncate(self.bound.len() - names.len());
+
+        doc
+    }
+
+    /// `term`, bracketed if it binds more loosely than `prec` asks for.
+    fn doc(&mut self, term: &Term, prec: Prec) -> Doc {
+        let (own, doc) = self.layout(term);
+
+        match own < prec {
+            true => text("(") + doc + text(")"),
+            false => doc,
+        }
+    }
+
+    /// The operator written for `function`: the one declared for it, or the
+    /// symbol itself if that was never declared, as it then stands for
+    /// itself.
+    fn operator(&self, function: &Term) -> Option<(String, Fixity)> {
+        match function {
+            Term::Var(_, Var { name, .. }) if !self.bound.contains(name) => {
+                match self.fixities.operator_for(name) {
+                    Some((operator, fixity)) => Some((operator.into(), fixity.clone())),
+                    None if is_operator(name) && self.fixities.get(name).is_none() => {
+                        Some((name.clone(), Fixity::undeclared(name)))
+                    }
+                    None => None,
+                }
             }
-            Term::Let(_, name, box t1, box t2) => {
-                write!(f, "let {} = {} in \n{}", name, t1, t2)
+            _ => None,
+        }
+    }
+
+    fn infix<'t>(&self, term: &'t Term) -> Option<(String, Fixity, &'t Term, &'t Term)> {
+        match term {
+            Term::Application(_, box Term::Application(_, box function, box lhs), box rhs) => {
+                let (operator, fixity) = self.operator(function)?;
+
+                Some((operator, fixity, lhs, rhs))
             }
-            Term::Projection(_, box t1, n) => {
-                write!(f, "{}.{}", t1, n)
+            _ => None,
+        }
+    }
+
+    /// An operand of `fixity`, which another operator's application only
+    /// groups with unbracketed if it binds tighter, or as tightly and
+    /// associates toward `side`.
+    fn operand(&mut self, term: &Term, fixity: &Fixity, side: Assoc) -> Doc {
+        match self.infix(term) {
+            Some((_, inner, _, _)) => {
+                let groups = inner.precedence > fixity.precedence
+                    || inner.precedence == fixity.precedence
+                        && inner.assoc == side
+                        && fixity.assoc == side;
+
+                self.doc(term, if groups { Prec::Infix } else { Prec::App })
             }
+            None => self.doc(term, Prec::App),
+        }
+    }
+
+    fn apply(&mut self, function: Doc, args: Vec<&Term>) -> Doc {
+        let args = args
+            .into_iter()
+            .fold(Doc::Nil, |doc, arg| doc + line() + self.doc(arg, Prec::Atom));
+
+        group(function + nest(self.indent, args))
+    }
+
+    /// `let x = t1 in let y = t2 in t3`, all on one line or each binding on
+    /// its own.
+    fn let_chain(&mut self, term: &Term) -> Doc {
+        let bound = self.bound.len();
+        let mut bindings = Doc::Nil;
+        let mut body = term;
+        loop {
+            let (lhs, binders, t1, t2) = match body {
+                Term::Let(_, name, box t1, box t2) => (name.clone(), vec![name.as_str()], t1, t2),
+                Term::LetPattern(_, pattern, box t1, box t2) => {
+                    (pattern.to_string(), pattern.binders(), t1, t2)
+                }
+                _ => break,
+            };
+            let t1 = self.doc(t1, Prec::Expr);
+            let binding = text(format!("let {} =", lhs)) + nest(self.indent, line() + t1);
+
+            bindings = bindings + group(binding) + text(" in") + line();
+            self.bound.extend(binders.into_iter().map(String::from));
+            body = t2;
+        }
+        let body = self.doc(body, Prec::Expr);
+        self.bound.truncate(bound);
+
+        group(bindings + body)
+    }
+
+    fn layout(&mut self, term: &Term) -> (Prec, Doc) {
+        let indent = self.indent;
+
+        match term {
+            Term::String(_, s) => (Prec::Atom, text(format!("\"{}\"", s))),
+            Term::Var(_, Var { name, .. }) => (Prec::Atom, text(name)),
+            Term::True(_) => (Prec::Atom, text("true")),
+            Term::False(_) => (Prec::Atom, text("false")),
+            Term::Zero(_) => (Prec::Atom, text("0")),
+            Term::Successor(..) if is_numeric(term) => {
+                (Prec::Atom, text(term.into_int().unwrap().to_string()))
+            }
+            Term::Float(_, flt) => float(*flt),
+            Term::Record(_, fields) if fields.is_empty() => (Prec::Atom, text("{}")),
             Term::Record(_, fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, box term)| {
+                        text(format!("{} = ", name)) + self.doc(term, Prec::Expr)
+                    })
+                    .collect::<Vec<Doc>>();
+                let fields = nest(indent, line() + join(fields, text(",") + line()));
+
+                (Prec::Atom, group(text("{") + fields + line() + text("}")))
+            }
+            Term::Projection(_, box t1, label) => {
+                (Prec::Atom, self.doc(t1, Prec::Atom) + text(format!(".{}", label)))
+            }
+            Term::Abstraction(..) => {
+                let mut params = vec![];
+                let mut body = term;
+                while let Term::Abstraction(_, name, box t) = body {
+                    params.push(name.as_str());
+                    body = t;
+                }
+                let head = text(format!("λ{}.", params.join(" ")));
+
+                (Prec::Expr, group(head + nest(indent, line() + self.under(&params, body))))
+            }
+            Term::If(_, box t1, box t2, box t3) => {
+                let branches = line()
+                    + text("then ")
+                    + self.doc(t2, Prec::Expr)
+                    + line()
+                    + text("else ")
+                    + self.doc(t3, Prec::Expr);
+
+                (
+                    Prec::Expr,
+                    group(text("if ") + self.doc(t1, Prec::Expr) + nest(indent, branches)),
+                )
+            }
+            Term::Let(..) | Term::LetPattern(..) => (Prec::Expr, self.let_chain(term)),
+            Term::Application(..) if self.infix(term).is_some() => {
+                let (operator, fixity, lhs, rhs) = self.infix(term).unwrap();
+                let lhs = self.operand(lhs, &fixity, Assoc::Left);
+                let rhs = self.operand(rhs, &fixity, Assoc::Right);
+
+                (
+                    Prec::Infix,
+                    group(lhs + text(format!(" {}", operator)) + nest(indent, line() + rhs)),
+                )
+            }
+            Term::Application(..) => {
+                let mut args = vec![];
+                let mut function = term;
+                while let Term::Application(_, box t1, box t2) = function {
+                    if self.infix(function).is_some() {
+                        break;
+                    }
+                    args.push(t2);
+                    function = t1;
+                }
+                args.reverse();
+                let function = self.doc(function, Prec::App);
+
+                (Prec::App, self.apply(function, args))
+            }
+            Term::Successor(_, box t1) => (Prec::App, text("+ ") + self.doc(t1, Prec::Atom)),
+            Term::Predecessor(_, box t1) => (Prec::App, text("- ") + self.doc(t1, Prec::Atom)),
+            Term::IsZero(_, box t1) => (Prec::App, text("iszero ") + self.doc(t1, Prec::Atom)),
+            Term::Host(_, host, args) if args.is_empty() => (Prec::Atom, text(&host.name)),
+            Term::Host(_, host, args) => {
+                (Prec::App, self.apply(text(&host.name), args.iter().collect()))
+            }
+            Term::TimesFloat(_, box t1, box t2) => {
+                (Prec::App, self.apply(text("timesfloat"), vec![t1, t2]))
+            }
+        }
+    }
+
+    fn command(&mut self, command: &Command) -> Doc {
+        let indent = self.indent;
+
+        match command {
+            Command::Bind(_, name, Binding::TermBind(box term)) => {
+                let mut params = vec![];
+                let mut body = term;
+                while let Term::Abstraction(_, param, box t) = body {
+                    params.push(param.as_str());
+                    body = t;
+                }
+                let head = [name.as_str()].iter().chain(&params).cloned().collect::<Vec<&str>>();
+                let head = text(format!("let {} =", head.join(" ")));
+
+                group(head + nest(indent, line() + self.under(&params, body))) + text(";")
+            }
+            Command::Bind(_, name, Binding::NameBind) => text(format!("let {}/;", name)),
+            Command::Eval(_, term) => self.doc(term, Prec::Expr) + text(";"),
+            Command::Import(..) | Command::Fixity(..) | Command::Pragma(..) => {
+                text(command.to_string())
+            }
+        }
+    }
+}
+
+/// Surface syntax, which keeps what desugaring loses: operators as they were
+/// written, multi-argument abstractions, literals and `where`.
+impl<'a> Printer<'a> {
+    fn surface(&mut self, surface: &Surface, prec: Prec) -> Doc {
+        let (own, doc) = self.surface_layout(surface);
+
+        match own < prec {
+            true => text("(") + doc + text(")"),
+            false => doc,
+        }
+    }
+
+    /// `name params = body`, with the body bracketed unless it binds at
+    /// least as tightly as `prec`.
+    fn definition(&mut self, definition: &Definition, prec: Prec) -> Doc {
+        let head = [&definition.name].into_iter().chain(&definition.params);
+        let head = text(format!("{} =", head.cloned().collect::<Vec<String>>().join(" ")));
+
+        group(head + nest(self.indent, line() + self.surface(&definition.body, prec)))
+    }
+
+    fn definitions(&mut self, definitions: &[Definition]) -> Doc {
+        let definitions = definitions
+            .iter()
+            .map(|definition| self.definition(definition, Prec::Expr))
+            .collect::<Vec<Doc>>();
+
+        join(definitions, text(",") + line())
+    }
+
+    /// See `let_chain`.
+    fn surface_let_chain(&mut self, surface: &Surface) -> Doc {
+        let mut bindings = Doc::Nil;
+        let mut body = surface;
+        loop {
+            let binding = match body {
+                Surface::Let(_, definitions, box s) => {
+                    body = s;
+                    text("let ") + self.definitions(definitions)
+                }
+                Surface::LetPattern(_, pattern, box s1, box s2) => {
+                    body = s2;
+                    let s1 = self.surface(s1, Prec::Expr);
+
+                    text(format!("let {} =", pattern)) + nest(self.indent, line() + s1)
+                }
+                _ => break,
+            };
+
+            bindings = bindings + group(binding) + text(" in") + line();
+        }
+
+        group(bindings + self.surface(body, Prec::Expr))
+    }
+
+    fn surface_layout(&mut self, surface: &Surface) -> (Prec, Doc) {
+        let indent = self.indent;
+
+        match surface {
+            Surface::String(_, s) => (Prec::Atom, text(format!("\"{}\"", s))),
+            Surface::Var(_, name) => (Prec::Atom, text(name)),
+            Surface::True(_) => (Prec::Atom, text("true")),
+            Surface::False(_) => (Prec::Atom, text("false")),
+            Surface::Float(_, digits) if digits.starts_with('-') => {
+                (Prec::Atom, text(format!("({})", digits)))
+            }
+            Surface::Nat(_, digits) | Surface::Float(_, digits) => (Prec::Atom, text(digits)),
+            Surface::Record(_, fields) if fields.is_empty() => (Prec::Atom, text("{}")),
+            Surface::Record(_, fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, surface)| {
+                        text(format!("{} = ", name)) + self.surface(surface, Prec::Expr)
+                    })
+                    .collect::<Vec<Doc>>();
+                let fields = nest(indent, line() + join(fields, text(",") + line()));
+
+                (Prec::Atom, group(text("{") + fields + line() + text("}")))
+            }
+            Surface::Projection(_, box s1, label) => {
+                (Prec::Atom, self.surface(s1, Prec::Atom) + text(format!(".{}", label)))
+            }
+            Surface::Abstraction(_, params, box body) => {
+                let head = text(format!("λ{}.", params.join(" ")));
+
+                (Prec::Expr, group(head + nest(indent, line() + self.surface(body, Prec::Expr))))
+            }
+            Surface::If(_, box s1, box s2, box s3) => {
+                let branches = line()
+                    + text("then ")
+                    + self.surface(s2, Prec::Expr)
+                    + line()
+                    + text("else ")
+                    + self.surface(s3, Prec::Expr);
+
+                (
+                    Prec::Expr,
+                    group(text("if ") + self.surface(s1, Prec::Expr) + nest(indent, branches)),
+                )
+            }
+            Surface::Let(..) | Surface::LetPattern(..) => {
+                (Prec::Expr, self.surface_let_chain(surface))
+            }
+            Surface::Where(_, box body, definitions) => {
+                let body = self.surface(body, Prec::Expr);
+                let clause = text("where ") + self.definitions(definitions);
+
+                (Prec::Where, group(body + nest(indent, line() + clause)))
+            }
+            Surface::Infix(_, box first, rest) => {
+                let rest = rest.iter().fold(Doc::Nil, |doc, (Operator(_, operator), operand)| {
+                    doc + text(format!(" {}", operator))
+                        + nest(indent, line() + self.surface(operand, Prec::App))
+                });
+
+                (Prec::Infix, group(self.surface(first, Prec::App) + rest))
+            }
+            Surface::Application(..) => {
+                let mut args = vec![];
+                let mut function = surface;
+                while let Surface::Application(_, box s1, box s2) = function {
+                    args.push(s2);
+                    function = s1;
+                }
+                let function = self.surface(function, Prec::App);
+                let args = args
+                    .into_iter()
+                    .rev()
+                    .fold(Doc::Nil, |doc, arg| doc + line() + self.surface(arg, Prec::Atom));
+
+                (Prec::App, group(function + nest(indent, args)))
+            }
+            Surface::Successor(_, box s1) => (Prec::App, text("+ ") + self.surface(s1, Prec::Atom)),
+            Surface::Predecessor(_, box s1) => {
+                (Prec::App, text("- ") + self.surface(s1, Prec::Atom))
+            }
+            Surface::IsZero(_, box s1) => {
+                (Prec::App, text("iszero ") + self.surface(s1, Prec::Atom))
+            }
+            Surface::TimesFloat(_, box s1, box s2) => {
+                let args = line() + self.surface(s1, Prec::Atom) + line();
+
+                (
+                    Prec::App,
+                    group(text("timesfloat") + nest(indent, args + self.surface(s2, Prec::Atom))),
+                )
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Doc {
+        match statement {
+            Statement::Import(_, import) => text(format!("use {};", import)),
+            Statement::Eval(_, surface) => self.surface(surface, Prec::Where) + text(";"),
+            Statement::Declare(_, name) => text(format!("let {}/;", name)),
+            Statement::Define(_, definition) => {
+                text("let ") + self.definition(definition, Prec::Where) + text(";")
+            }
+            Statement::Fixity(_, operator, fixity) => text(format!(
+                "{} {} {} = {};",
+                fixity.assoc, fixity.precedence, operator, fixity.function
+            )),
+            Statement::Pragma(_, name, value) => text(format!("#{} {};", name, value)),
+        }
+    }
+}
+
+/// A float as a literal that reads back as the same float, a negative one in
+/// parentheses. Infinities and NaN have no literal, so they are written as the
+/// products that overflow to them.
+fn float(flt: f32) -> (Prec, Doc) {
+    const LARGEST: &str = "340282350000000000000000000000000000000.0";
+
+    match flt {
+        _ if flt.is_nan() => {
+            (Prec::App, text(format!("timesfloat 0.0 (timesfloat {} 2.0)", LARGEST)))
+        }
+        _ if flt.is_infinite() => {
+            let largest = match flt < 0.0 {
+                true => format!("(-{})", LARGEST),
+                false => LARGEST.into(),
+            };
+
+            (Prec::App, text(format!("timesfloat {} 2.0", largest)))
+        }
+        _ => {
+            // `Display` never uses an exponent, but leaves off `.0`.
+            let digits = match flt.to_string() {
+                digits if digits.contains('.') => digits,
+                digits => format!("{}.0", digits),
+            };
+
+            match flt.is_sign_negative() {
+                true => (Prec::Atom, text(format!("({})", digits))),
+                false => (Prec::Atom, text(digits)),
+            }
+        }
+    }
+}
+
+fn is_operator(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| "!$%&*+-/<=>?@^|~:".contains(c))
+}
+
+impl<'a> fmt::Display for WithFixities<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.pretty(self.1, &Style::flat()))
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.with_fixities(&Fixities::default()))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Var(name) => write!(f, "{}", name),
+            Pattern::Record(fields) => {
                 let csv = fields
                     .iter()
-                    .map(|(name, term)| format!("{} = {}", name, term))
+                    .map(|(label, pattern)| format!("{} = {}", label, pattern))
                     .collect::<Vec<String>>()
                     .join(", ");
                 write!(f, "{{ {} }}", csv)
             }
-            Term::Abstraction(_, name, box t2) => write!(f, "λ{}. {}", name, t2),
-            Term::Application(_, t1, t2) => write!(f, "({} {})", t1, t2),
-            Term::Zero(_) | Term::Successor(_, _) | Term::Predecessor(_, _) => write!(
-                f,
-                "{}",
-                self.into_int()
-                    .map_or(String::from("NaN"), |x| x.to_string())
-            ),
-            Term::IsZero(_, t1) => write!(f, "iszero {}", t1),
-            Term::Float(_, flt) => write!(f, "{}", flt),
-            _ => unimplemented!(),
         }
     }
 }
 
+impl fmt::Display for Import {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"", self.path)?;
+        if let Some(alias) = &self.alias {
+            write!(f, " as {}", alias)?;
+        }
+        match &self.exposing {
+            Some(names) if !(names.is_empty() && self.alias.is_some()) => {
+                write!(f, " ({})", names.join(", "))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `x = t` or, for a name bound to nothing, `x/` as in TAPL.
 impl fmt::Display for Binding {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
-            Binding::NameBind => write!(f, ""),
-            Binding::TermBind(t) => write!(f, "{}", t),
+            Binding::NameBind => write!(f, "/"),
+            Binding::TermBind(t) => write!(f, " = {}", t),
+        }
+    }
+}
+
+impl fmt::Display for Pragma {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pragma::Literals(literals) => write!(f, "#literals {}", literals),
         }
     }
 }
@@ -53,18 +553,40 @@ impl fmt::Display for Binding {
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
-            Command::Bind(_, name, binding) => write!(f, "const {} = {};", name, binding),
-            Command::Eval(_, term) => write!(f, "{};", term),
-            Command::Import(import) => write!(f, "use {};", import),
+            Command::Bind(..) | Command::Eval(..) => {
+                write!(f, "{}", self.pretty(&Fixities::default(), &Style::flat()))
+            }
+            Command::Import(_, import) => write!(f, "use {};", import),
+            Command::Pragma(_, pragma) => write!(f, "{};", pragma),
+            Command::Fixity(_, operator, fixity) => write!(
+                f,
+                "{} {} {} = {};",
+                fixity.assoc, fixity.precedence, operator, fixity.function
+            ),
+        }
+    }
+}
+
+impl fmt::Display for FileInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.filename(), self.is_known()) {
+            (_, false) => write!(f, "<unknown>"),
+            ("", true) => write!(f, "{}:{}", self.line(), self.column()),
+            (filename, true) => write!(f, "{}:{}:{}", filename, self.line(), self.column()),
         }
     }
 }
 
 impl fmt::Display for Context {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Context [\n")?;
+        writeln!(f, "Context [")?;
         for ContextMember { name, binding } in self.into_iter() {
-            write!(f, "\t{} = {}\n", name, binding)?;
+            match binding {
+                Binding::TermBind(box term) => {
+                    writeln!(f, "\t{} = {}", name, term.with_fixities(self.fixities()))?
+                }
+                Binding::NameBind => writeln!(f, "\t{}/", name)?,
+            }
         }
         write!(f, "]")
     }
@@ -72,9 +594,13 @@ impl fmt::Display for Context {
 
 #[cfg(test)]
 mod tests {
+    use crate::evaluate::{hydrate_vars, Evaluator};
+    use crate::host::HostFunction;
+    use crate::io::MemoryIo;
+    use crate::literals::Literals;
+    use crate::memo::alpha_key;
     use crate::parser::parse;
     use crate::printer::*;
-    use crate::syntax::*;
 
     #[test]
     fn test_print_simple() {
@@ -82,6 +608,12 @@ mod tests {
         let (parsed, _) = parse(id).expect("parse error");
 
         assert_eq!(format!("{}", parsed[0]), id);
+
+        let input = "let x/; let k x y = x; let f = k (λx. x) x.l;";
+        let (parsed, _) = parse(input).expect("parse error");
+        let printed: Vec<String> = parsed.iter().map(|command| command.to_string()).collect();
+
+        assert_eq!(printed, ["let x/;", "let k x y = x;", "let f = k (λx. x) x.l;"]);
     }
 
     #[test]
@@ -91,13 +623,289 @@ mod tests {
         let g = λx. x in
         Y g;
         "#;
+        let (parsed, _) = parse(input).expect("parse error");
+        let pretty = |width| {
+            parsed[0].pretty(&Fixities::default(), &Style { width, indent: 2 })
+        };
 
-        let expectation = r#"let Y = λf. (λx. (f λy. ((x x) y)) λx. (f λy. ((x x) y))) in 
-let g = λx. x in 
-(Y g);"#;
+        assert_eq!(
+            pretty(80),
+            "let Y = λf. (λx. f (λy. x x y)) (λx. f (λy. x x y)) in let g = λx. x in Y g;"
+        );
+        assert_eq!(
+            pretty(40),
+            "let Y =
+  λf.
+    (λx. f (λy. x x y))
+      (λx. f (λy. x x y)) in
+let g = λx. x in
+Y g;"
+        );
+    }
 
+    #[test]
+    fn test_print_reparses() {
+        let input = r#"
+        (λx. x) (λy. y);
+        f (let x = 1 in x) (if true then g else h);
+        iszero (+ x) (- 2) (+ (f 1));
+        (λf'. f'.first) { first = "a string", second_1 = 1.0 };
+        let α = λx1. x1 in α;
+        f (let {a = x, b = {c = y'}} = r in y') (λx. x);
+        "#;
         let (parsed, _) = parse(input).expect("parse error");
+        let printed = parsed
+            .iter()
+            .map(|command| format!("{}", command))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let (reparsed, _) = parse(&printed).expect("printed output should reparse");
+
+        assert_eq!(reparsed.without_locations(), parsed.without_locations());
+    }
+
+    #[test]
+    fn test_print_floats() {
+        let float = |flt: f32| Term::Float(FileInfo::default(), flt).to_string();
+
+        assert_eq!(float(1.0), "1.0");
+        assert_eq!(float(1e20), "100000000000000000000.0");
+        assert_eq!(float(1e-7), "0.0000001");
+        assert_eq!(float(-2.5), "(-2.5)");
+        assert_eq!(float(-3.0), "(-3.0)");
+
+        let mut io = MemoryIo::default();
+        let mut evaluator = Evaluator::new(&mut io);
+        let overflowed = [f32::INFINITY, f32::NEG_INFINITY, f32::NAN];
+        for flt in [1e20, 1e-7, -2.5, -3.0, -0.0, f32::MAX].into_iter().chain(overflowed) {
+            let printed = format!("{};", float(flt));
+            let values = evaluator.eval_source(&printed, Context::default()).unwrap();
+
+            match &values[..] {
+                [Term::Float(_, again)] if flt.is_nan() => assert!(again.is_nan()),
+                [Term::Float(_, again)] => {
+                    assert_eq!(again.to_bits(), flt.to_bits(), "{}", printed)
+                }
+                _ => panic!("{} is not a float", printed),
+            }
+        }
+
+        let (parsed, _) = parse("timesfloat 2.0 (timesfloat x 0.5) y;").expect("parse error");
+        assert_eq!(parsed[0].to_string(), "timesfloat 2.0 (timesfloat x 0.5) y;");
+    }
+
+    #[test]
+    fn test_print_infix() {
+        let input = r#"
+        infixl 6 + = plus;
+        infixl 7 * = times;
+        infix 4 == = eq;
+        infixr 5 :: = cons;
+        a + b * c == (λx. x) d + e;
+        λplus. a + b;
+        (a + b) + c; a + (b + c); (x :: y) :: z; x :: y :: z; (+ a) * - (f b);
+        "#;
+        let (parsed, context) = parse(input).expect("parse error");
+        let printed: Vec<String> = parsed
+            .iter()
+            .filter_map(|command| match command {
+                Command::Eval(_, term) => Some(format!("{}", term.with_fixities(context.fixities()))),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            printed,
+            [
+                "a + b * c == (λx. x) d + e",
+                "λplus. plus a b",
+                "a + b + c",
+                "a + (b + c)",
+                "(x :: y) :: z",
+                "x :: y :: z",
+                "+ a * - (f b)",
+            ]
+        );
+        assert_eq!(format!("{}", parsed[0]), "infixl 6 + = plus;");
+    }
+
+    /// A xorshift generator, so that a failing case comes back every run.
+    struct Gen(u64);
+
+    impl Gen {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+            choices[self.below(choices.len())]
+        }
+
+        fn term(&mut self, depth: usize) -> Term {
+            let info = FileInfo::default();
+            let var = |name: &str| Term::Var(FileInfo::default(), Var::new(name, 0, 0));
+
+            if depth == 0 {
+                return match self.below(6) {
+                    0 => Term::from_int(self.below(4) as i32, info),
+                    1 => Term::True(info),
+                    2 => Term::String(info, "s".into()),
+                    3 => {
+                        let floats = [1.5, 0.0, -2.5, 1e20, 1e-7, f32::MAX, f32::MIN_POSITIVE];
+
+                        Term::Float(info, floats[self.below(floats.len())])
+                    }
+                    _ => var(self.pick(&["x", "y", "a", "plus", "cons", "eq", "times"])),
+                };
+            }
+
+            let d = depth - 1;
+            match self.below(14) {
+                0 | 1 => {
+                    let name = self.pick(&["x", "y", "plus"]);
 
-        assert_eq!(format!("{}", parsed[0]), expectation);
+                    Term::Abstraction(info, name.into(), box self.term(d))
+                }
+                2 | 3 => Term::Application(info, box self.term(d), box self.term(d)),
+                4 => {
+                    let operator = var(self.pick(&["plus", "cons", "eq", "times"]));
+                    let lhs = Term::Application(info.clone(), box operator, box self.term(d));
+
+                    Term::Application(info, box lhs, box self.term(d))
+                }
+                5 => Term::If(info, box self.term(d), box self.term(d), box self.term(d)),
+                6 => {
+                    let name = self.pick(&["x", "y", "times"]);
+
+                    Term::Let(info, name.into(), box self.term(d), box self.term(d))
+                }
+                7 => {
+                    let pattern = Pattern::Record(vec![
+                        ("l".into(), Pattern::Var("x".into())),
+                        ("m".into(), Pattern::Record(vec![("l".into(), Pattern::Var("y".into()))])),
+                    ]);
+
+                    Term::LetPattern(info, pattern, box self.term(d), box self.term(d))
+                }
+                8 => {
+                    let l = box self.term(d);
+                    let m = box self.term(d);
+
+                    Term::Record(info, vec![("l".into(), l), ("m".into(), m)])
+                }
+                9 => Term::Projection(info, box self.term(d), "l".into()),
+                10 => Term::Successor(info, box self.term(d)),
+                11 => Term::Predecessor(info, box self.term(d)),
+                12 if self.below(2) == 0 => Term::IsZero(info, box self.term(d)),
+                12 => Term::TimesFloat(info, box self.term(d), box self.term(d)),
+                _ => {
+                    let host = HostFunction::binary("mix", |a: u32, b: u32| a + b);
+                    let args = (0..self.below(2)).map(|_| self.term(d)).collect();
+
+                    Term::Host(info, host, args)
+                }
+            }
+        }
+
+        fn command(&mut self) -> Command {
+            let info = FileInfo::default();
+            let depth = self.below(5);
+
+            match self.below(10) {
+                0..=3 => Command::Eval(info, self.term(depth)),
+                4..=6 => Command::Bind(info, "f".into(), Binding::TermBind(box self.term(depth))),
+                7 => Command::Bind(info, self.pick(&["f", "g"]).into(), Binding::NameBind),
+                // Declared again as they already are, so that every command
+                // reads the same whichever of them comes first.
+                8 => {
+                    let (operator, assoc, precedence, function) = match self.below(2) {
+                        0 => ("+", Assoc::Left, 6, "plus"),
+                        _ => ("::", Assoc::Right, 5, "cons"),
+                    };
+                    let function = function.into();
+
+                    Command::Fixity(info, operator.into(), Fixity { assoc, precedence, function })
+                }
+                _ => match self.below(2) {
+                    0 => Command::Pragma(info, Pragma::Literals(Literals::Native)),
+                    // `as` with nothing exposed reads back as exposing nothing.
+                    _ => Command::Import(
+                        info,
+                        Import {
+                            path: "std".into(),
+                            alias: Some("s".into()),
+                            exposing: Some(vec![]),
+                        },
+                    ),
+                },
+            }
+        }
+    }
+
+    /// `term` with every host function replaced by its name applied to its
+    /// arguments, which is how it reads back once printed.
+    fn named_hosts(term: &Term) -> Term {
+        let fi = FileInfo::default();
+        let go = |t: &Term| box named_hosts(t);
+
+        match term {
+            Term::Host(_, host, args) => args.iter().fold(
+                Term::Var(fi.clone(), Var::new(&host.name, 0, 0)),
+                |function, arg| Term::Application(fi.clone(), box function, go(arg)),
+            ),
+            Term::If(_, t1, t2, t3) => Term::If(fi, go(t1), go(t2), go(t3)),
+            Term::Let(_, name, t1, t2) => Term::Let(fi, name.clone(), go(t1), go(t2)),
+            Term::LetPattern(_, pattern, t1, t2) => {
+                Term::LetPattern(fi, pattern.clone(), go(t1), go(t2))
+            }
+            Term::Record(_, fields) => {
+                Term::Record(fi, fields.iter().map(|(l, t)| (l.clone(), go(t))).collect())
+            }
+            Term::Projection(_, t1, label) => Term::Projection(fi, go(t1), label.clone()),
+            Term::Abstraction(_, name, t1) => Term::Abstraction(fi, name.clone(), go(t1)),
+            Term::Application(_, t1, t2) => Term::Application(fi, go(t1), go(t2)),
+            Term::Successor(_, t1) => Term::Successor(fi, go(t1)),
+            Term::Predecessor(_, t1) => Term::Predecessor(fi, go(t1)),
+            Term::IsZero(_, t1) => Term::IsZero(fi, go(t1)),
+            Term::TimesFloat(_, t1, t2) => Term::TimesFloat(fi, go(t1), go(t2)),
+            _ => term.clone(),
+        }
+    }
+
+    #[test]
+    fn test_print_round_trips() {
+        let declarations = "infixl 6 + = plus; infixl 7 * = times; infixr 5 :: = cons;
+            infix 4 == = eq; let a/; let plus/; let times/; let cons/; let eq/; let f/;
+            let mix/;";
+        let (mut commands, context) = parse(declarations).expect("parse error");
+        let mut gen = Gen(0x9e3779b97f4a7c15);
+        for _ in 0..400 {
+            commands.push(gen.command());
+        }
+
+        for width in [10, 40, 80] {
+            let style = Style { width, indent: 2 };
+            let printed = commands
+                .iter()
+                .map(|command| command.pretty(context.fixities(), &style))
+                .collect::<Vec<String>>()
+                .join("\n");
+            let (reparsed, context) = parse(&printed).expect("printed output should reparse");
+            let names = context.names();
+            let key = |command: &Command| match command {
+                Command::Eval(_, term) | Command::Bind(_, _, Binding::TermBind(box term)) => {
+                    alpha_key(&hydrate_vars(&names, &named_hosts(term)))
+                }
+                _ => format!("{:?}", command.without_locations()),
+            };
+
+            assert_eq!(reparsed.len(), commands.len());
+            for (command, again) in commands.iter().zip(&reparsed) {
+                assert_eq!(key(command), key(again), "{}", command);
+            }
+        }
     }
 }
@@ -0,0 +1,191 @@
+use crate::context::*;
+use crate::desugar::*;
+use crate::diagnostics::Diagnostic;
+use crate::modules::Modules;
+use crate::surface::Statement;
+use crate::syntax::*;
+
+/// Desugars `statements` in order, loading imports, declaring fixities and
+/// binding names in `context` as it goes, so that each statement sees the
+/// ones before it. A statement that fails is skipped and the rest are still
+/// resolved.
+pub fn resolve(
+    statements: Vec<Statement>,
+    context: &mut Context,
+    modules: &mut Modules,
+) -> (Vec<Command>, Vec<DesugarError>) {
+    let mut commands = vec![];
+    let mut errors = vec![];
+
+    for statement in statements {
+        let command = match statement {
+            Statement::Import(info, import) => modules
+                .import(&info, &import, context)
+                .map(|_| Command::Import(info, import)),
+            Statement::Eval(info, surface) => {
+                desugar(&surface, context).map(|term| Command::Eval(info, term))
+            }
+            Statement::Declare(info, name) => {
+                context.append_name(&name);
+
+                Ok(Command::Bind(info, name, Binding::NameBind))
+            }
+            Statement::Define(info, definition) => {
+                desugar_definition(&definition, context).map(|term| {
+                    let binding = Binding::TermBind(box term);
+
+                    context.append_binding(ContextMember {
+                        name: definition.name.clone(),
+                        binding: binding.clone(),
+                    });
+
+                    Command::Bind(info, definition.name, binding)
+                })
+            }
+            Statement::Fixity(info, operator, fixity) => {
+                context.declare_fixity(&operator, fixity.clone());
+
+                Ok(Command::Fixity(info, operator, fixity))
+            }
+            Statement::Pragma(info, name, value) => match name.as_str() {
+                "literals" => value
+                    .parse()
+                    .map(|literals| {
+                        context.set_literals(literals);
+
+                        Command::Pragma(info.clone(), Pragma::Literals(literals))
+                    })
+                    .map_err(|message| DesugarError(info, message)),
+                _ => Err(DesugarError(info, format!("there is no `#{}` pragma", name))),
+            },
+        };
+
+        match command {
+            Ok(command) => commands.push(command),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    (commands, errors)
+}
+
+/// Every use of a name that is neither bound around it nor in `context`, in
+/// source order, suggesting a similar name that is.
+pub fn unbound(commands: &[Command], context: &Context) -> Vec<Diagnostic> {
+    let globals: Vec<&str> = context.into_iter().map(|member| member.name.as_str()).collect();
+    let mut diagnostics = vec![];
+
+    for command in commands {
+        let term = match command {
+            Command::Eval(_, term) | Command::Bind(_, _, Binding::TermBind(box term)) => term,
+            _ => continue,
+        };
+
+        term.visit_free(&mut |name, info, bound| {
+            if globals.contains(&name) {
+                return;
+            }
+
+            let diagnostic =
+                Diagnostic::error(&format!("`{}` is not bound", name)).at(info.clone());
+            let candidates = bound.iter().rev().chain(globals.iter());
+
+            diagnostics.push(match suggest(name, candidates) {
+                Some(similar) => diagnostic.note(&format!("did you mean `{}`?", similar)),
+                None => diagnostic,
+            })
+        });
+    }
+
+    diagnostics
+}
+
+/// The closest of `candidates` to `name`, if it is close enough to be a
+/// likely typo: a third of the name's length in edits, at most.
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a &'a str>) -> Option<&'a str> {
+    let allowed = name.chars().count() / 3;
+
+    candidates
+        .map(|candidate| (distance(name, candidate), *candidate))
+        .filter(|(edits, _)| *edits <= allowed)
+        .min_by_key(|(edits, _)| *edits)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The edits that turn `a` into `b`, counting each inserted, deleted or
+/// replaced character and each swap of neighbouring ones.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    (0..=a.len()).for_each(|i| d[i][0] = i);
+    (0..=b.len()).for_each(|j| d[0][j] = j);
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::*;
+    use crate::resolve::*;
+
+    fn unbound_in(input: &str) -> Vec<(String, u32, Vec<String>)> {
+        let (commands, context) =
+            parse_file("test.f", input, Context::default()).expect("parse error");
+
+        unbound(&commands, &context)
+            .into_iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.message,
+                    diagnostic.location.unwrap().column(),
+                    diagnostic.notes,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unbound() {
+        assert_eq!(
+            unbound_in("let plus = λm n. m;\nλnumber. plsu numbr x;"),
+            [
+                ("`plsu` is not bound".into(), 10, vec!["did you mean `plus`?".into()]),
+                ("`numbr` is not bound".into(), 15, vec!["did you mean `number`?".into()]),
+                ("`x` is not bound".into(), 21, vec![]),
+            ]
+        );
+        assert!(unbound_in("let f x = g x;\nlet g = f;\nλy. f y;").is_empty());
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("plsu", "plus"), 1);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("λx", "λx"), 0);
+    }
+
+    #[test]
+    fn test_resolve_in_order() {
+        let input = "a + b;\ninfixl 6 + = plus;\na + b;\nlet x = 1;";
+        let (statements, _) = parse_statements("", input);
+        let mut context = Context::default();
+        let (commands, errors) = resolve(statements, &mut context, &mut Modules::disabled());
+
+        assert!(errors.is_empty());
+        assert_eq!(commands[0].to_string(), "a + b;");
+        assert_eq!(commands[2].to_string(), "plus a b;");
+        assert!(context.is_name_bound("x"));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::pretty::Style;
+use crate::sexp;
+use crate::syntax::Command;
+use std::str::FromStr;
+
+/// How a program is written by `--to-json` or `--to-sexp`, and read back by
+/// `--from-json` or `--from-sexp`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AstFormat {
+    /// Every command and term as serde lays it out, locations included.
+    Json,
+    /// `(eval (app (var f 0) 1))`, without locations.
+    Sexp,
+}
+
+/// How far along a program is when it is written out.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Stage {
+    /// As parsed, with variables naming their binders.
+    Parsed,
+    /// With each variable's de Bruijn index filled in.
+    Resolved,
+    /// With every evaluated term replaced by its value.
+    Evaluated,
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parsed" => Ok(Stage::Parsed),
+            "resolved" => Ok(Stage::Resolved),
+            "evaluated" => Ok(Stage::Evaluated),
+            _ => Err(format!(
+                "`{}` is not a stage; expected parsed, resolved or evaluated",
+                s
+            )),
+        }
+    }
+}
+
+pub fn write_commands(commands: &[Command], format: AstFormat) -> String {
+    match format {
+        AstFormat::Json => {
+            serde_json::to_string_pretty(commands).expect("commands are serializable") + "\n"
+        }
+        AstFormat::Sexp => sexp::write_program(commands, &Style::default()),
+    }
+}
+
+/// Reads commands written by `write_commands`. S-expressions have no
+/// locations, so their commands are located in `filename`.
+pub fn read_commands(
+    filename: &str,
+    input: &str,
+    format: AstFormat,
+) -> Result<Vec<Command>, String> {
+    match format {
+        AstFormat::Json => serde_json::from_str(input).map_err(|e| e.to_string()),
+        AstFormat::Sexp => sexp::read_program(filename, input).map_err(|e| format!("{:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse;
+    use crate::serialize::*;
+    use crate::syntax::*;
+
+    #[test]
+    fn test_json() {
+        let (mut commands, _) = parse(
+            "let x/;\n#literals church;\nlet {a = p} = {a = \"q\"} in if true then p.b else 0.5;",
+        )
+        .unwrap();
+        let import = Import {
+            path: "std".into(),
+            alias: Some("s".into()),
+            exposing: Some(vec!["plus".into()]),
+        };
+        commands.insert(0, Command::Import(FileInfo::default(), import));
+        let json = write_commands(&commands, AstFormat::Json);
+
+        assert_eq!(read_commands("", &json, AstFormat::Json), Ok(commands.clone()));
+        assert_eq!(
+            read_commands("", &write_commands(&commands, AstFormat::Sexp), AstFormat::Sexp),
+            Ok(commands.without_locations())
+        );
+
+        let print = crate::io::primitive("print").unwrap();
+        let host = Term::Host(FileInfo::default(), print, vec![Term::True(FileInfo::default())]);
+        let json = serde_json::to_string(&host).unwrap();
+
+        assert_eq!(serde_json::from_str::<Term>(&json).unwrap().to_string(), host.to_string());
+        assert!(serde_json::from_str::<Term>(&json.replace("print", "launch")).is_err());
+        assert!(read_commands("", "[{\"Eval\": 1}]", AstFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_stage() {
+        assert_eq!("resolved".parse(), Ok(Stage::Resolved));
+        assert!("typed".parse::<Stage>().is_err());
+    }
+}
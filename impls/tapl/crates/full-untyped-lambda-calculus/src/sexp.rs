@@ -0,0 +1,477 @@
+use crate::evaluate::is_numeric;
+use crate::fixity::{Assoc, Fixity, MAX_PRECEDENCE};
+use crate::io;
+use crate::pretty::*;
+use crate::syntax::*;
+use std::fmt;
+
+/// An S-expression: a symbol or number, a quoted string, or a list.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Sexp {
+    Atom(String),
+    String(String),
+    List(Vec<Sexp>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SexpError {
+    UnexpectedEnd,
+    /// A `)` with no `(` before it, at this byte offset.
+    UnexpectedClose(usize),
+    /// A string starting at this byte offset that is never closed.
+    UnterminatedString(usize),
+    /// An expression that is not the command or term expected where it is.
+    Malformed(String),
+}
+
+fn atom(s: impl Into<String>) -> Sexp {
+    Sexp::Atom(s.into())
+}
+
+fn list(head: &str, items: impl IntoIterator<Item = Sexp>) -> Sexp {
+    Sexp::List(std::iter::once(atom(head)).chain(items).collect())
+}
+
+impl Sexp {
+    fn doc(&self, indent: usize) -> Doc {
+        match self {
+            Sexp::List(items) => {
+                let items = join(items.iter().map(|item| item.doc(indent)), line());
+
+                group(text("(") + nest(indent, items) + text(")"))
+            }
+            sexp => text(sexp.to_string()),
+        }
+    }
+
+    /// Lists that do not fit in `style`'s width are broken one item a line.
+    pub fn pretty(&self, style: &Style) -> String {
+        self.doc(style.indent).render(style.width)
+    }
+}
+
+impl fmt::Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sexp::Atom(s) => write!(f, "{}", s),
+            Sexp::String(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Sexp::List(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Reads every S-expression in `input`. `;` starts a comment that runs to
+/// the end of the line.
+pub fn parse_sexps(input: &str) -> Result<Vec<Sexp>, SexpError> {
+    let mut chars = input.char_indices().peekable();
+    let mut stack: Vec<Vec<Sexp>> = vec![vec![]];
+
+    while let Some((offset, c)) = chars.next() {
+        let sexp = match c {
+            ';' => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '(' => {
+                stack.push(vec![]);
+                continue;
+            }
+            ')' if stack.len() == 1 => return Err(SexpError::UnexpectedClose(offset)),
+            ')' => Sexp::List(stack.pop().unwrap()),
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(SexpError::UnterminatedString(offset)),
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            None => return Err(SexpError::UnterminatedString(offset)),
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, 't')) => s.push('\t'),
+                            Some((_, c)) => s.push(c),
+                        },
+                        Some((_, c)) => s.push(c),
+                    }
+                }
+                Sexp::String(s)
+            }
+            c => {
+                let mut s = String::from(c);
+                while let Some((_, c)) = chars
+                    .next_if(|(_, c)| !c.is_whitespace() && !"()\";".contains(*c))
+                {
+                    s.push(c);
+                }
+                Sexp::Atom(s)
+            }
+        };
+
+        stack.last_mut().unwrap().push(sexp);
+    }
+
+    match stack.len() {
+        1 => Ok(stack.pop().unwrap()),
+        _ => Err(SexpError::UnexpectedEnd),
+    }
+}
+
+pub fn from_term(term: &Term) -> Sexp {
+    match term {
+        term if is_numeric(term) => atom(term.into_int().unwrap().to_string()),
+        Term::String(_, s) => Sexp::String(s.clone()),
+        Term::Var(_, var) => list("var", [atom(&var.name), atom(var.index.to_string())]),
+        Term::True(_) => atom("true"),
+        Term::False(_) => atom("false"),
+        Term::If(_, box t1, box t2, box t3) => {
+            list("if", [from_term(t1), from_term(t2), from_term(t3)])
+        }
+        Term::Let(_, x, box t1, box t2) => list("let", [atom(x), from_term(t1), from_term(t2)]),
+        Term::LetPattern(_, pattern, box t1, box t2) => {
+            list("let-pattern", [from_pattern(pattern), from_term(t1), from_term(t2)])
+        }
+        Term::Record(_, fields) => list(
+            "record",
+            fields
+                .iter()
+                .map(|(label, box t)| Sexp::List(vec![atom(label), from_term(t)])),
+        ),
+        Term::Projection(_, box t, label) => list("proj", [from_term(t), atom(label)]),
+        Term::Abstraction(_, x, box t) => list("lambda", [atom(x), from_term(t)]),
+        Term::Application(_, box t1, box t2) => list("app", [from_term(t1), from_term(t2)]),
+        Term::Zero(_) => atom("0"),
+        Term::Successor(_, box t) => list("succ", [from_term(t)]),
+        Term::Predecessor(_, box t) => list("pred", [from_term(t)]),
+        Term::IsZero(_, box t) => list("iszero", [from_term(t)]),
+        Term::Float(_, n) => atom(format!("{:?}", n)),
+        Term::TimesFloat(_, box t1, box t2) => {
+            list("timesfloat", [from_term(t1), from_term(t2)])
+        }
+        Term::Host(_, host, args) => {
+            list("host", std::iter::once(atom(&host.name)).chain(args.iter().map(from_term)))
+        }
+    }
+}
+
+fn from_pattern(pattern: &Pattern) -> Sexp {
+    match pattern {
+        Pattern::Var(x) => atom(x),
+        Pattern::Record(fields) => list(
+            "record",
+            fields
+                .iter()
+                .map(|(label, p)| Sexp::List(vec![atom(label), from_pattern(p)])),
+        ),
+    }
+}
+
+pub fn from_command(command: &Command) -> Sexp {
+    match command {
+        Command::Import(_, import) => {
+            let alias = import.alias.iter().map(|alias| list("as", [atom(alias)]));
+            let exposing = import
+                .exposing
+                .iter()
+                .map(|names| list("exposing", names.iter().map(atom)));
+
+            let path = Sexp::String(import.path.clone());
+
+            list("import", std::iter::once(path).chain(alias).chain(exposing))
+        }
+        Command::Eval(_, term) => list("eval", [from_term(term)]),
+        Command::Bind(_, name, Binding::NameBind) => list("bind", [atom(name)]),
+        Command::Bind(_, name, Binding::TermBind(box term)) => {
+            list("bind", [atom(name), from_term(term)])
+        }
+        Command::Fixity(_, operator, fixity) => list(
+            "fixity",
+            [
+                atom(operator),
+                atom(fixity.assoc.to_string()),
+                atom(fixity.precedence.to_string()),
+                atom(&fixity.function),
+            ],
+        ),
+        Command::Pragma(_, Pragma::Literals(literals)) => {
+            list("pragma", [atom("literals"), atom(literals.to_string())])
+        }
+    }
+}
+
+/// Reads a term written by `from_term`, giving every part of it `info`.
+/// Variables are read with the index written and a container size of zero.
+pub fn to_term(sexp: &Sexp, info: &FileInfo) -> Result<Term, SexpError> {
+    let malformed = || SexpError::Malformed(sexp.to_string());
+    let term = |sexp| to_term(sexp, info).map(Box::new);
+
+    Ok(match sexp {
+        Sexp::String(s) => Term::String(info.clone(), s.clone()),
+        Sexp::Atom(a) if a == "true" => Term::True(info.clone()),
+        Sexp::Atom(a) if a == "false" => Term::False(info.clone()),
+        Sexp::Atom(a) => match (a.parse::<i32>(), a.parse::<f32>()) {
+            (Ok(n), _) if n >= 0 => Term::from_int(n, info.clone()),
+            (_, Ok(n)) => Term::Float(info.clone(), n),
+            _ => return Err(malformed()),
+        },
+        Sexp::List(items) => match items.as_slice() {
+            [Sexp::Atom(head), rest @ ..] => match (head.as_str(), rest) {
+                ("var", [Sexp::Atom(name), Sexp::Atom(index)]) => {
+                    let index = index.parse().map_err(|_| malformed())?;
+
+                    Term::Var(info.clone(), Var::new(name, index, 0))
+                }
+                ("if", [t1, t2, t3]) => Term::If(info.clone(), term(t1)?, term(t2)?, term(t3)?),
+                ("let", [Sexp::Atom(x), t1, t2]) => {
+                    Term::Let(info.clone(), x.clone(), term(t1)?, term(t2)?)
+                }
+                ("let-pattern", [pattern, t1, t2]) => {
+                    Term::LetPattern(info.clone(), to_pattern(pattern)?, term(t1)?, term(t2)?)
+                }
+                ("record", fields) => Term::Record(
+                    info.clone(),
+                    fields
+                        .iter()
+                        .map(|field| match field {
+                            Sexp::List(field) => match field.as_slice() {
+                                [Sexp::Atom(label), t] => Ok((label.clone(), term(t)?)),
+                                _ => Err(malformed()),
+                            },
+                            _ => Err(malformed()),
+                        })
+                        .collect::<Result<_, _>>()?,
+                ),
+                ("proj", [t, Sexp::Atom(label)]) => {
+                    Term::Projection(info.clone(), term(t)?, label.clone())
+                }
+                ("lambda", [Sexp::Atom(x), t]) => {
+                    Term::Abstraction(info.clone(), x.clone(), term(t)?)
+                }
+                ("app", [t1, t2]) => Term::Application(info.clone(), term(t1)?, term(t2)?),
+                ("succ", [t]) => Term::Successor(info.clone(), term(t)?),
+                ("pred", [t]) => Term::Predecessor(info.clone(), term(t)?),
+                ("iszero", [t]) => Term::IsZero(info.clone(), term(t)?),
+                ("timesfloat", [t1, t2]) => Term::TimesFloat(info.clone(), term(t1)?, term(t2)?),
+                ("host", [Sexp::Atom(name), args @ ..]) => Term::Host(
+                    info.clone(),
+                    io::primitive(name).ok_or_else(|| {
+                        SexpError::Malformed(format!("there is no host function `{}`", name))
+                    })?,
+                    args.iter().map(|arg| to_term(arg, info)).collect::<Result<_, _>>()?,
+                ),
+                _ => return Err(malformed()),
+            },
+            _ => return Err(malformed()),
+        },
+    })
+}
+
+fn to_pattern(sexp: &Sexp) -> Result<Pattern, SexpError> {
+    let malformed = || SexpError::Malformed(sexp.to_string());
+
+    match sexp {
+        Sexp::Atom(x) => Ok(Pattern::Var(x.clone())),
+        Sexp::List(items) => match items.as_slice() {
+            [Sexp::Atom(head), fields @ ..] if head == "record" => fields
+                .iter()
+                .map(|field| match field {
+                    Sexp::List(field) => match field.as_slice() {
+                        [Sexp::Atom(label), p] => Ok((label.clone(), to_pattern(p)?)),
+                        _ => Err(malformed()),
+                    },
+                    _ => Err(malformed()),
+                })
+                .collect::<Result<_, _>>()
+                .map(Pattern::Record),
+            _ => Err(malformed()),
+        },
+        _ => Err(malformed()),
+    }
+}
+
+pub fn to_command(sexp: &Sexp, info: &FileInfo) -> Result<Command, SexpError> {
+    let malformed = || SexpError::Malformed(sexp.to_string());
+    let names = |sexps: &[Sexp]| -> Result<Vec<String>, SexpError> {
+        sexps
+            .iter()
+            .map(|sexp| match sexp {
+                Sexp::Atom(name) => Ok(name.clone()),
+                _ => Err(malformed()),
+            })
+            .collect()
+    };
+
+    let items = match sexp {
+        Sexp::List(items) => items.as_slice(),
+        _ => return Err(malformed()),
+    };
+
+    Ok(match items {
+        [Sexp::Atom(head), Sexp::String(path), options @ ..] if head == "import" => {
+            let mut import = Import::from(path.as_str());
+            for option in options {
+                match option {
+                    Sexp::List(option) => match option.as_slice() {
+                        [Sexp::Atom(head), Sexp::Atom(alias)] if head == "as" => {
+                            import.alias = Some(alias.clone())
+                        }
+                        [Sexp::Atom(head), exposed @ ..] if head == "exposing" => {
+                            import.exposing = Some(names(exposed)?)
+                        }
+                        _ => return Err(malformed()),
+                    },
+                    _ => return Err(malformed()),
+                }
+            }
+
+            Command::Import(info.clone(), import)
+        }
+        [Sexp::Atom(head), t] if head == "eval" => Command::Eval(info.clone(), to_term(t, info)?),
+        [Sexp::Atom(head), Sexp::Atom(name)] if head == "bind" => {
+            Command::Bind(info.clone(), name.clone(), Binding::NameBind)
+        }
+        [Sexp::Atom(head), Sexp::Atom(name), t] if head == "bind" => Command::Bind(
+            info.clone(),
+            name.clone(),
+            Binding::TermBind(box to_term(t, info)?),
+        ),
+        [
+            Sexp::Atom(head),
+            Sexp::Atom(operator),
+            Sexp::Atom(assoc),
+            Sexp::Atom(precedence),
+            Sexp::Atom(function),
+        ] if head == "fixity" => {
+            let assoc = match assoc.as_str() {
+                "infixl" => Assoc::Left,
+                "infixr" => Assoc::Right,
+                "infix" => Assoc::None,
+                _ => return Err(malformed()),
+            };
+            let fixity = Fixity {
+                assoc,
+                precedence: match precedence.parse() {
+                    Ok(precedence) if precedence <= MAX_PRECEDENCE => precedence,
+                    _ => return Err(malformed()),
+                },
+                function: function.clone(),
+            };
+
+            Command::Fixity(info.clone(), operator.clone(), fixity)
+        }
+        [Sexp::Atom(head), Sexp::Atom(name), Sexp::Atom(value)]
+            if head == "pragma" && name == "literals" =>
+        {
+            let literals = value.parse().map_err(SexpError::Malformed)?;
+
+            Command::Pragma(info.clone(), Pragma::Literals(literals))
+        }
+        _ => return Err(malformed()),
+    })
+}
+
+/// One command a line, or more where a command is wider than `style`.
+pub fn write_program(commands: &[Command], style: &Style) -> String {
+    commands
+        .iter()
+        .map(|command| from_command(command).pretty(style) + "\n")
+        .collect()
+}
+
+/// Reads the commands in `input`, each located in `filename` so that its
+/// imports are found relative to it.
+pub fn read_program(filename: &str, input: &str) -> Result<Vec<Command>, SexpError> {
+    let info = FileInfo::new(filename, 0, 0);
+
+    parse_sexps(input)?
+        .iter()
+        .map(|sexp| to_command(sexp, &info))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluate::hydrate_vars;
+    use crate::parser::*;
+    use crate::sexp::*;
+
+    #[test]
+    fn test_parse_sexps() {
+        assert_eq!(
+            parse_sexps("(a \"b \\\"c\\\"\" ; comment\n (1.5)) d").unwrap(),
+            [
+                Sexp::List(vec![
+                    atom("a"),
+                    Sexp::String("b \"c\"".into()),
+                    Sexp::List(vec![atom("1.5")])
+                ]),
+                atom("d")
+            ]
+        );
+        assert_eq!(parse_sexps("(a"), Err(SexpError::UnexpectedEnd));
+        assert_eq!(parse_sexps("a)"), Err(SexpError::UnexpectedClose(1)));
+        assert_eq!(parse_sexps("(\"a)"), Err(SexpError::UnterminatedString(1)));
+
+        let sexp = Sexp::String("say \"hi\"\n".into());
+
+        assert_eq!(parse_sexps(&sexp.to_string()).unwrap(), [sexp]);
+    }
+
+    #[test]
+    fn test_write_program() {
+        let (commands, context) = parse(
+            "let id = λx. x;\ninfixr 5 ++ = append;\n\
+             (λf y. f (id y)) 3 \"s\" {a = 1.5, b = false};",
+        )
+        .unwrap();
+        let parsed = commands.clone();
+        let commands: Vec<Command> = commands
+            .into_iter()
+            .map(|command| match command {
+                Command::Eval(info, term) => Command::Eval(info, hydrate_vars(&context, &term)),
+                command => command,
+            })
+            .collect();
+
+        assert_eq!(
+            write_program(&commands, &Style::default()),
+            "(bind id (lambda x (var x 0)))
+(fixity ++ infixr 5 append)
+(eval
+  (app
+    (app
+      (app (lambda f (lambda y (app (var f 1) (app (var id 2) (var y 0))))) 3)
+      \"s\")
+    (record (a 1.5) (b false))))\n"
+        );
+        let written = write_program(&commands, &Style::flat());
+
+        assert_eq!(write_program(&read_program("", &written).unwrap(), &Style::flat()), written);
+        assert_eq!(
+            read_program("", &write_program(&parsed, &Style::flat())),
+            Ok(parsed.without_locations())
+        );
+        assert!(matches!(read_program("", "(eval (lambda))"), Err(SexpError::Malformed(_))));
+        assert!(matches!(read_program("", "(eval (host nope))"), Err(SexpError::Malformed(_))));
+    }
+}
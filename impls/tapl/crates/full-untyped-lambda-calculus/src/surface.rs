@@ -0,0 +1,178 @@
+use crate::fixity::Fixity;
+use crate::syntax::{FileInfo, Import, Pattern, WithoutLocations};
+
+/// A top-level command as parsed. `resolve` desugars it once the names and
+/// fixities declared before it are known.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Statement {
+    Import(FileInfo, Import),
+    Eval(FileInfo, Surface),
+    /// `let x/`, which binds a name to nothing.
+    Declare(FileInfo, String),
+    /// `let f x = t`, with any `where` already folded into the body.
+    Define(FileInfo, Definition),
+    Fixity(FileInfo, String, Fixity),
+    /// `#name value`, checked when it is resolved.
+    Pragma(FileInfo, String, String),
+}
+
+/// The term syntax as written, before `desugar` lowers it to a `Term`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Surface {
+    String(FileInfo, String),
+    Var(FileInfo, String),
+    True(FileInfo),
+    False(FileInfo),
+    /// The digits as written, which may be too many for any literal.
+    Nat(FileInfo, String),
+    /// The digits as written, which `fmt` keeps however they round.
+    Float(FileInfo, String),
+    If(FileInfo, Box<Surface>, Box<Surface>, Box<Surface>),
+    /// `let x = a, f y = b in t`, binding left to right.
+    Let(FileInfo, Vec<Definition>, Box<Surface>),
+    /// `let {x = a, y = {z = b}} = t1 in t2`
+    LetPattern(FileInfo, Pattern, Box<Surface>, Box<Surface>),
+    /// `t where x = a, f y = b`, the same as a `let` around `t`.
+    Where(FileInfo, Box<Surface>, Vec<Definition>),
+    /// `λx y z. t`
+    Abstraction(FileInfo, Vec<String>, Box<Surface>),
+    Application(FileInfo, Box<Surface>, Box<Surface>),
+    /// `a + b * c`, kept flat until `desugar` groups it by fixity.
+    Infix(FileInfo, Box<Surface>, Vec<(Operator, Surface)>),
+    Record(FileInfo, Vec<(String, Surface)>),
+    Projection(FileInfo, Box<Surface>, String),
+    Successor(FileInfo, Box<Surface>),
+    Predecessor(FileInfo, Box<Surface>),
+    IsZero(FileInfo, Box<Surface>),
+    TimesFloat(FileInfo, Box<Surface>, Box<Surface>),
+}
+
+/// An infix operator and where it was used.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Operator(pub FileInfo, pub String);
+
+/// `name params = body`, which binds `name` to `λparams. body`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Definition {
+    pub info: FileInfo,
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Surface,
+}
+
+impl Statement {
+    pub fn info(&self) -> &FileInfo {
+        match self {
+            Statement::Import(info, _)
+            | Statement::Eval(info, _)
+            | Statement::Declare(info, _)
+            | Statement::Define(info, _)
+            | Statement::Fixity(info, _, _)
+            | Statement::Pragma(info, _, _) => info,
+        }
+    }
+}
+
+impl Surface {
+    pub fn info(&self) -> &FileInfo {
+        match self {
+            Surface::String(info, _)
+            | Surface::Var(info, _)
+            | Surface::True(info)
+            | Surface::False(info)
+            | Surface::Nat(info, _)
+            | Surface::Float(info, _)
+            | Surface::If(info, _, _, _)
+            | Surface::Let(info, _, _)
+            | Surface::LetPattern(info, _, _, _)
+            | Surface::Where(info, _, _)
+            | Surface::Abstraction(info, _, _)
+            | Surface::Application(info, _, _)
+            | Surface::Infix(info, _, _)
+            | Surface::Record(info, _)
+            | Surface::Projection(info, _, _)
+            | Surface::Successor(info, _)
+            | Surface::Predecessor(info, _)
+            | Surface::IsZero(info, _)
+            | Surface::TimesFloat(info, _, _) => info,
+        }
+    }
+}
+
+impl WithoutLocations for Statement {
+    fn without_locations(&self) -> Statement {
+        let fi = FileInfo::default();
+
+        match self {
+            Statement::Import(_, import) => Statement::Import(fi, import.clone()),
+            Statement::Eval(_, surface) => Statement::Eval(fi, surface.without_locations()),
+            Statement::Declare(_, name) => Statement::Declare(fi, name.clone()),
+            Statement::Define(_, definition) => {
+                Statement::Define(fi, definition.without_locations())
+            }
+            Statement::Fixity(_, operator, fixity) => {
+                Statement::Fixity(fi, operator.clone(), fixity.clone())
+            }
+            Statement::Pragma(_, name, value) => Statement::Pragma(fi, name.clone(), value.clone()),
+        }
+    }
+}
+
+impl WithoutLocations for Definition {
+    fn without_locations(&self) -> Definition {
+        Definition {
+            info: FileInfo::default(),
+            name: self.name.clone(),
+            params: self.params.clone(),
+            body: self.body.without_locations(),
+        }
+    }
+}
+
+impl WithoutLocations for Surface {
+    fn without_locations(&self) -> Surface {
+        let fi = FileInfo::default();
+        let strip = |surface: &Surface| Box::new(surface.without_locations());
+        let strip_fields = |fields: &[(String, Surface)]| {
+            fields.iter().map(|(label, s)| (label.clone(), s.without_locations())).collect()
+        };
+
+        match self {
+            Surface::String(_, s) => Surface::String(fi, s.clone()),
+            Surface::Var(_, name) => Surface::Var(fi, name.clone()),
+            Surface::True(_) => Surface::True(fi),
+            Surface::False(_) => Surface::False(fi),
+            Surface::Nat(_, digits) => Surface::Nat(fi, digits.clone()),
+            Surface::Float(_, digits) => Surface::Float(fi, digits.clone()),
+            Surface::If(_, s1, s2, s3) => Surface::If(fi, strip(s1), strip(s2), strip(s3)),
+            Surface::Let(_, definitions, s1) => {
+                Surface::Let(fi, definitions.without_locations(), strip(s1))
+            }
+            Surface::LetPattern(_, pattern, s1, s2) => {
+                Surface::LetPattern(fi, pattern.clone(), strip(s1), strip(s2))
+            }
+            Surface::Where(_, s1, definitions) => {
+                Surface::Where(fi, strip(s1), definitions.without_locations())
+            }
+            Surface::Abstraction(_, params, s1) => {
+                Surface::Abstraction(fi, params.clone(), strip(s1))
+            }
+            Surface::Application(_, s1, s2) => Surface::Application(fi, strip(s1), strip(s2)),
+            Surface::Infix(_, first, rest) => Surface::Infix(
+                fi,
+                strip(first),
+                rest.iter()
+                    .map(|(Operator(_, symbol), operand)| {
+                        (Operator(FileInfo::default(), symbol.clone()), operand.without_locations())
+                    })
+                    .collect(),
+            ),
+            Surface::Record(_, fields) => Surface::Record(fi, strip_fields(fields)),
+            Surface::Projection(_, s1, label) => Surface::Projection(fi, strip(s1), label.clone()),
+            Surface::Successor(_, s1) => Surface::Successor(fi, strip(s1)),
+            Surface::Predecessor(_, s1) => Surface::Predecessor(fi, strip(s1)),
+            Surface::IsZero(_, s1) => Surface::IsZero(fi, strip(s1)),
+            Surface::TimesFloat(_, s1, s2) => Surface::TimesFloat(fi, strip(s1), strip(s2)),
+        }
+    }
+}
@@ -1,3 +1,9 @@
+use crate::fixity::Fixity;
+use crate::host::HostFunction;
+use crate::literals::Literals;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
 pub type OnVarArgs<'a> = (i32, &'a FileInfo, &'a Var);
 
 pub trait Visit {
@@ -28,18 +34,75 @@ pub trait Substitute {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Forgets where something was parsed, so it compares equal to the same
+/// thing parsed elsewhere or built by hand.
+pub trait WithoutLocations {
+    fn without_locations(&self) -> Self;
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Command {
-    Import(String),
+    Import(FileInfo, Import),
     Eval(FileInfo, Term),
     Bind(FileInfo, String, Binding),
+    /// `infixl 6 + = plus`
+    Fixity(FileInfo, String, Fixity),
+    /// `#literals church`
+    Pragma(FileInfo, Pragma),
+}
+
+impl Command {
+    pub fn info(&self) -> &FileInfo {
+        match self {
+            Command::Import(info, _)
+            | Command::Eval(info, _)
+            | Command::Bind(info, _, _)
+            | Command::Fixity(info, _, _)
+            | Command::Pragma(info, _) => info,
+        }
+    }
+}
+
+/// `use "path" as name (a, b)`: the file to load, the prefix of its
+/// qualified names, and the names to bring in unqualified, or `None` for all
+/// of them.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Import {
+    pub path: String,
+    pub alias: Option<String>,
+    pub exposing: Option<Vec<String>>,
+}
+
+/// A setting for the rest of the file, written `#name value`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Pragma {
+    Literals(Literals),
+}
+
+impl From<&str> for Import {
+    fn from(path: &str) -> Self {
+        Import {
+            path: path.into(),
+            alias: None,
+            exposing: None,
+        }
+    }
 }
 
-#[derive(Default, Debug, PartialEq, Clone)]
+/// Where a term came from: the file, the 1-based line and column of its
+/// first character, and its span as byte offsets into the source.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct FileInfo {
-    filename: String,
+    filename: Arc<str>,
     line_num: u32,
     line_col: u32,
+    span: (usize, usize),
+}
+
+impl Default for FileInfo {
+    fn default() -> Self {
+        FileInfo::new("", 0, 0)
+    }
 }
 
 impl FileInfo {
@@ -48,11 +111,38 @@ impl FileInfo {
             filename: filename.into(),
             line_col,
             line_num,
+            span: (0, 0),
         }
     }
+
+    pub fn with_span(mut self, start: usize, end: usize) -> Self {
+        self.span = (start, end);
+        self
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn line(&self) -> u32 {
+        self.line_num
+    }
+
+    pub fn column(&self) -> u32 {
+        self.line_col
+    }
+
+    pub fn span(&self) -> (usize, usize) {
+        self.span
+    }
+
+    /// Whether this came from the parser rather than being synthesized.
+    pub fn is_known(&self) -> bool {
+        self.line_num > 0
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Binding {
     NameBind,
     TermBind(Box<Term>),
@@ -70,7 +160,7 @@ impl Shift for Binding {
     }
 }
 
-#[derive(Debug, Default, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Var {
     pub name: String,
     pub index: i32,
@@ -107,7 +197,7 @@ impl Var {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Term {
     String(FileInfo, String),
     Var(FileInfo, Var),
@@ -115,6 +205,9 @@ pub enum Term {
     False(FileInfo),
     If(FileInfo, Box<Term>, Box<Term>, Box<Term>),
     Let(FileInfo, String, Box<Term>, Box<Term>),
+    /// `let {l = p, ...} = t1 in t2`, binding the pattern's variables in
+    /// `t2` in the order they appear.
+    LetPattern(FileInfo, Pattern, Box<Term>, Box<Term>),
     Record(FileInfo, Vec<(String, Box<Term>)>),
     Projection(FileInfo, Box<Term>, String),
     Abstraction(FileInfo, String, Box<Term>),
@@ -125,6 +218,82 @@ pub enum Term {
     IsZero(FileInfo, Box<Term>),
     Float(FileInfo, f32),
     TimesFloat(FileInfo, Box<Term>, Box<Term>),
+    Host(FileInfo, HostFunction, Vec<Term>),
+}
+
+/// A pattern for `let` (TAPL §11.8): a variable, or a record whose fields
+/// are matched by further patterns.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Pattern {
+    Var(String),
+    Record(Vec<(String, Pattern)>),
+}
+
+impl Pattern {
+    /// The variables the pattern binds, outermost first.
+    pub fn binders(&self) -> Vec<&str> {
+        match self {
+            Pattern::Var(name) => vec![name],
+            Pattern::Record(fields) => fields.iter().flat_map(|(_, p)| p.binders()).collect(),
+        }
+    }
+
+    /// `match(p, v)`: the values the binders are matched to, in the order of
+    /// `binders`. Fields of `value` the pattern does not mention are ignored.
+    pub fn matches(&self, value: &Term) -> Result<Vec<Term>, String> {
+        match (self, value) {
+            (Pattern::Var(_), _) => Ok(vec![value.clone()]),
+            (Pattern::Record(patterns), Term::Record(_, fields)) => {
+                let mut matched = vec![];
+                for (label, pattern) in patterns {
+                    match fields.iter().find(|(field, _)| field == label) {
+                        Some((_, box field)) => matched.extend(pattern.matches(field)?),
+                        None => return Err(format!("no label `{}` in {}", label, value)),
+                    }
+                }
+
+                Ok(matched)
+            }
+            (Pattern::Record(_), _) => Err(format!("expected a record, found {}", value)),
+        }
+    }
+}
+
+impl Term {
+    /// Lowers `let p = t1 in t2` on a hydrated term to plain lets that
+    /// project each binder out of `t1`, for evaluators without patterns.
+    pub fn lower_pattern_let(info: &FileInfo, pattern: &Pattern, t1: &Term, t2: &Term) -> Term {
+        fn paths(pattern: &Pattern, path: &mut Vec<String>, out: &mut Vec<(String, Vec<String>)>) {
+            match pattern {
+                Pattern::Var(name) => out.push((name.clone(), path.clone())),
+                Pattern::Record(fields) => {
+                    for (label, pattern) in fields {
+                        path.push(label.clone());
+                        paths(pattern, path, out);
+                        path.pop();
+                    }
+                }
+            }
+        }
+
+        let mut bindings = vec![];
+        paths(pattern, &mut vec![], &mut bindings);
+        let n = bindings.len() as i32;
+        let body = bindings
+            .iter()
+            .enumerate()
+            .rev()
+            .fold(t2.shift_n(1, n), |body, (i, (name, path))| {
+                let record = Term::Var(info.clone(), Var::new("record", i as i32, i as i32 + 1));
+                let projected = path.iter().fold(record, |t, label| {
+                    Term::Projection(info.clone(), box t, label.clone())
+                });
+
+                Term::Let(info.clone(), name.clone(), box projected, box body)
+            });
+
+        Term::Let(info.clone(), String::from("record"), box t1.clone(), box body)
+    }
 }
 
 impl Visit for Term {
@@ -156,6 +325,12 @@ impl Visit for Term {
                         box walk(on_var, container_size + 1, t2),
                     )
                 }
+                Term::LetPattern(file_info, pattern, box t1, box t2) => Term::LetPattern(
+                    file_info.clone(),
+                    pattern.clone(),
+                    box walk(on_var, container_size, t1),
+                    box walk(on_var, container_size + pattern.binders().len() as i32, t2),
+                ),
                 Term::Projection(file_info, box t1, l) => Term::Projection(
                     file_info.clone(),
                     box walk(on_var, container_size, t1),
@@ -199,10 +374,17 @@ impl Visit for Term {
                     box walk(on_var, container_size, t1),
                     box walk(on_var, container_size, t2),
                 ),
+                Term::Host(file_info, host, args) => Term::Host(
+                    file_info.clone(),
+                    host.clone(),
+                    args.iter()
+                        .map(|arg| walk(on_var, container_size, arg))
+                        .collect(),
+                ),
             }
         }
 
-        walk(on_var, initial_container_size, &self)
+        walk(on_var, initial_container_size, self)
     }
 }
 
@@ -226,34 +408,232 @@ impl Substitute for Term {
     }
 }
 
+impl WithoutLocations for Term {
+    fn without_locations(&self) -> Term {
+        let fi = FileInfo::default();
+        let strip = |t: &Term| box t.without_locations();
+
+        match self {
+            Term::String(_, s) => Term::String(fi, s.clone()),
+            Term::Var(_, var) => Term::Var(fi, var.clone()),
+            Term::True(_) => Term::True(fi),
+            Term::False(_) => Term::False(fi),
+            Term::If(_, t1, t2, t3) => Term::If(fi, strip(t1), strip(t2), strip(t3)),
+            Term::Let(_, name, t1, t2) => Term::Let(fi, name.clone(), strip(t1), strip(t2)),
+            Term::LetPattern(_, pattern, t1, t2) => {
+                Term::LetPattern(fi, pattern.clone(), strip(t1), strip(t2))
+            }
+            Term::Record(_, fields) => Term::Record(
+                fi,
+                fields.iter().map(|(label, t)| (label.clone(), strip(t))).collect(),
+            ),
+            Term::Projection(_, t1, label) => Term::Projection(fi, strip(t1), label.clone()),
+            Term::Abstraction(_, name, t1) => Term::Abstraction(fi, name.clone(), strip(t1)),
+            Term::Application(_, t1, t2) => Term::Application(fi, strip(t1), strip(t2)),
+            Term::Zero(_) => Term::Zero(fi),
+            Term::Successor(_, t1) => Term::Successor(fi, strip(t1)),
+            Term::Predecessor(_, t1) => Term::Predecessor(fi, strip(t1)),
+            Term::IsZero(_, t1) => Term::IsZero(fi, strip(t1)),
+            Term::Float(_, flt) => Term::Float(fi, *flt),
+            Term::TimesFloat(_, t1, t2) => Term::TimesFloat(fi, strip(t1), strip(t2)),
+            Term::Host(_, host, args) => {
+                Term::Host(fi, host.clone(), args.without_locations())
+            }
+        }
+    }
+}
+
+impl WithoutLocations for Binding {
+    fn without_locations(&self) -> Binding {
+        match self {
+            Binding::NameBind => Binding::NameBind,
+            Binding::TermBind(term) => Binding::TermBind(box term.without_locations()),
+        }
+    }
+}
+
+impl WithoutLocations for Command {
+    fn without_locations(&self) -> Command {
+        let fi = FileInfo::default();
+
+        match self {
+            Command::Import(_, import) => Command::Import(fi, import.clone()),
+            Command::Eval(_, term) => Command::Eval(fi, term.without_locations()),
+            Command::Bind(_, name, binding) => {
+                Command::Bind(fi, name.clone(), binding.without_locations())
+            }
+            Command::Fixity(_, operator, fixity) => {
+                Command::Fixity(fi, operator.clone(), fixity.clone())
+            }
+            Command::Pragma(_, pragma) => Command::Pragma(fi, pragma.clone()),
+        }
+    }
+}
+
+impl<T: WithoutLocations> WithoutLocations for Vec<T> {
+    fn without_locations(&self) -> Vec<T> {
+        self.iter().map(T::without_locations).collect()
+    }
+}
+
 impl Term {
+    /// `input` as a built-in number. There are no negative numbers, so
+    /// those are zero, as `pred 0` is.
     pub fn from_int(input: i32, file_info: FileInfo) -> Term {
-        match input {
-            0 => Term::Zero(file_info),
-            _ => Term::Successor(
-                file_info.clone(),
-                box Term::from_int(input - 1, file_info.clone()),
-            ),
+        (0..input.max(0)).fold(Term::Zero(file_info.clone()), |term, _| {
+            Term::Successor(file_info.clone(), box term)
+        })
+    }
+
+    pub fn is_free(&self, name: &str) -> bool {
+        match self {
+            Term::Var(_, var) => var.name == name,
+            Term::If(_, box t1, box t2, box t3) => {
+                t1.is_free(name) || t2.is_free(name) || t3.is_free(name)
+            }
+            Term::Let(_, bound, box t1, box t2) => {
+                t1.is_free(name) || (bound != name && t2.is_free(name))
+            }
+            Term::LetPattern(_, pattern, box t1, box t2) => {
+                t1.is_free(name) || (!pattern.binders().contains(&name) && t2.is_free(name))
+            }
+            Term::Record(_, fields) => fields.iter().any(|(_, box t)| t.is_free(name)),
+            Term::Projection(_, box t1, _) => t1.is_free(name),
+            Term::Abstraction(_, bound, box t1) => bound != name && t1.is_free(name),
+            Term::Application(_, box t1, box t2) | Term::TimesFloat(_, box t1, box t2) => {
+                t1.is_free(name) || t2.is_free(name)
+            }
+            Term::Successor(_, box t1) | Term::Predecessor(_, box t1) | Term::IsZero(_, box t1) => {
+                t1.is_free(name)
+            }
+            Term::Host(_, _, args) => args.iter().any(|t| t.is_free(name)),
+            Term::String(_, _)
+            | Term::True(_)
+            | Term::False(_)
+            | Term::Zero(_)
+            | Term::Float(_, _) => false,
         }
     }
 
+    /// Every occurrence of a variable not bound within the term, in source
+    /// order.
+    pub fn free_vars(&self) -> Vec<(String, FileInfo)> {
+        let mut out = vec![];
+        self.visit_free(&mut |name, info, _| out.push((name.into(), info.clone())));
+
+        out
+    }
+
+    /// Calls `f` with every occurrence of a variable not bound within the
+    /// term, in source order, along with the variables bound around it,
+    /// innermost last.
+    pub fn visit_free(&self, f: &mut dyn FnMut(&str, &FileInfo, &[&str])) {
+        type OnFree<'f> = dyn FnMut(&str, &FileInfo, &[&str]) + 'f;
+
+        fn walk<'t>(term: &'t Term, bound: &mut Vec<&'t str>, f: &mut OnFree) {
+            match term {
+                Term::Var(info, var) if !bound.contains(&var.name.as_str()) => {
+                    f(&var.name, info, bound)
+                }
+                Term::If(_, box t1, box t2, box t3) => {
+                    walk(t1, bound, f);
+                    walk(t2, bound, f);
+                    walk(t3, bound, f);
+                }
+                Term::Let(_, name, box t1, box t2) => {
+                    walk(t1, bound, f);
+                    bound.push(name);
+                    walk(t2, bound, f);
+                    bound.pop();
+                }
+                Term::LetPattern(_, pattern, box t1, box t2) => {
+                    walk(t1, bound, f);
+                    let binders = pattern.binders();
+                    bound.extend(&binders);
+                    walk(t2, bound, f);
+                    bound.truncate(bound.len() - binders.len());
+                }
+                Term::Record(_, fields) => fields.iter().for_each(|(_, box t)| walk(t, bound, f)),
+                Term::Abstraction(_, name, box t1) => {
+                    bound.push(name);
+                    walk(t1, bound, f);
+                    bound.pop();
+                }
+                Term::Application(_, box t1, box t2) | Term::TimesFloat(_, box t1, box t2) => {
+                    walk(t1, bound, f);
+                    walk(t2, bound, f);
+                }
+                Term::Projection(_, box t1, _)
+                | Term::Successor(_, box t1)
+                | Term::Predecessor(_, box t1)
+                | Term::IsZero(_, box t1) => walk(t1, bound, f),
+                Term::Host(_, _, args) => args.iter().for_each(|t| walk(t, bound, f)),
+                _ => {}
+            }
+        }
+
+        walk(self, &mut vec![], f)
+    }
+
     pub fn into_int(&self) -> Option<i32> {
         fn get_number(t: &Term) -> Option<i32> {
             match t {
                 Term::Zero(_) => Some(0),
-                Term::Successor(_, box x) => Some(get_number(&x)? + 1),
+                Term::Successor(_, box x) => Some(get_number(x)? + 1),
                 Term::Predecessor(_, box x) => Some(std::cmp::max(get_number(x)? - 1, 0)),
                 _ => None,
             }
         }
 
-        get_number(&self)
+        get_number(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::parser::parse;
     use crate::syntax::*;
+
+    #[test]
+    fn test_without_locations() {
+        let (first, _) = parse("λx. x;").expect("Parse error");
+        let (second, _) = parse("\n  λx.   x;").expect("Parse error");
+
+        assert_ne!(first, second);
+        assert_eq!(first.without_locations(), second.without_locations());
+        assert_eq!(first[0].without_locations().info(), &FileInfo::default());
+    }
+
+    #[test]
+    fn test_is_free() {
+        let (parsed, _) = parse("λx. x y (let z = z in z);").expect("Parse error");
+
+        if let Command::Eval(_, term) = &parsed[0] {
+            assert!(!term.is_free("x"));
+            assert!(term.is_free("y"));
+            assert!(term.is_free("z"));
+            assert!(!term.is_free("w"));
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn test_free_vars() {
+        let (parsed, _) = parse("λx. x y (let z = z in z) y;").expect("Parse error");
+
+        if let Command::Eval(_, term) = &parsed[0] {
+            let free: Vec<(String, u32)> = term
+                .free_vars()
+                .into_iter()
+                .map(|(name, info)| (name, info.column()))
+                .collect();
+
+            assert_eq!(free, [("y".into(), 7), ("z".into(), 18), ("y".into(), 26)]);
+        } else {
+            panic!()
+        }
+    }
     #[test]
     fn test_into_int() {
         let x = Term::from_int(10, FileInfo::default());
@@ -277,5 +657,7 @@ mod tests {
                 .unwrap(),
             0
         );
+
+        assert_eq!(Term::from_int(-3, FileInfo::default()).into_int().unwrap(), 0);
     }
 }
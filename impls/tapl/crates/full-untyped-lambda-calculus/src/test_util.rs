@@ -0,0 +1,78 @@
+use crate::context::*;
+use crate::evaluate::*;
+use crate::io::MemoryIo;
+use crate::parser::parse;
+use crate::syntax::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The last command of `input`, which must be a term, with the context the
+/// rest of `input` builds.
+pub fn last_term(input: &str) -> (Term, Context) {
+    let (parsed, context) = parse(input).expect("Parse error");
+
+    match parsed.last() {
+        Some(Command::Eval(_, term)) => (term.clone(), context),
+        _ => panic!("the last command is not a term"),
+    }
+}
+
+/// The last term of `input` with its variables resolved.
+pub fn hydrated(input: &str) -> Term {
+    let (term, context) = last_term(input);
+
+    hydrate_vars(&context, &term)
+}
+
+/// The last term of `input` wrapped in the globals it mentions.
+pub fn closed(input: &str) -> Term {
+    let (term, context) = last_term(input);
+
+    close_over_globals(&context, &term)
+}
+
+/// The values of every term in `input`, printed.
+pub fn values(input: &str) -> Result<Vec<String>, EvalError> {
+    Evaluator::new(&mut MemoryIo::default())
+        .eval_source(input, Context::default())
+        .map(|values| values.iter().map(|value| value.to_string()).collect())
+}
+
+/// A directory of its own under the system's temporary directory, removed
+/// with everything in it when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> TempDir {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let name = format!(
+            "full-untyped-lambda-calculus-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        );
+        let path = std::env::temp_dir().join(name);
+        fs::create_dir_all(&path).expect("Failed to create a temporary directory");
+
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `contents` to `name` in the directory, returning its path.
+    pub fn write(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, contents).expect("Failed to write a temporary file");
+
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}